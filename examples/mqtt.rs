@@ -47,15 +47,11 @@ extern crate serde;
 #[macro_use]
 extern crate serde_derive;
 
-use serde::Serialize;
-
 extern crate rapt;
 #[macro_use]
 extern crate rapt_derive;
 
-extern crate netopt;
-
-use netopt::NetworkOptions;
+use rapt::mqtt::netopt::NetworkOptions;
 use rapt::mqtt::client::{ClientOptions, ReconnectMethod};
 use rapt::{mqtt, Instrument, Listener};
 
@@ -82,35 +78,32 @@ pub fn main() {
 
     let args: Vec<_> = env::args().collect();
     if args.len() < 2 {
-        println!("Usage: cargo run --example mqtt --features mqtt_publisher,netopt,serde_json -- 127.0.0.1:1883");
+        println!("Usage: cargo run --example mqtt --features mqtt_publisher,serde_json -- 127.0.0.1:1883");
         exit(1);
     }
 
-    let ref address = args[1];
+    let address = &args[1];
 
 
-    let netopt = NetworkOptions::new();
     let mut opts = ClientOptions::new();
     opts.set_keep_alive(15);
     opts.set_reconnect(ReconnectMethod::ReconnectAfter(Duration::new(5,0)));
-    let client = opts.connect(address.as_str(), netopt).unwrap();
 
     let instruments = TestInstruments::default();
-    let mut publisher = mqtt::Publisher::new((), client, instruments, true);
+    let publisher = mqtt::Publisher::connect(address.as_str(), opts, NetworkOptions::new(),
+                                             (), instruments, true).unwrap();
 
     let datapoint = publisher.instruments().main_value.clone();
 
-    let publisher_handle = publisher.handle();
-    let publisher_thread = thread::spawn(move || publisher.run(rapt::ser::JsonSerializer));
+    let running_publisher = publisher.spawn(rapt::ser::JsonSerializer);
 
     let service_thread = thread::spawn(move ||
        for _ in 0..100 {
-           let _ = datapoint.update(|v| v.indicator += 1).unwrap();
+           datapoint.update(|v| v.indicator += 1).unwrap();
        }
     );
 
-    let _ = service_thread.join().unwrap();
-    publisher_handle.shutdown();
-    let _ = publisher_thread.join().unwrap();
+    service_thread.join().unwrap();
+    running_publisher.stop_and_join().unwrap();
 
 }
\ No newline at end of file
@@ -36,10 +36,10 @@
 // OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
 // IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
 // DEALINGS IN THE SOFTWARE.
-#![recursion_limit = "128"]
+#![recursion_limit = "256"]
 
 extern crate syn;
-use syn::{Ident, Body, MetaItem, NestedMetaItem, Lit};
+use syn::{Ident, Ty, Field, Body, MetaItem, NestedMetaItem, Lit, VariantData};
 use quote::Tokens;
 
 #[macro_use]
@@ -49,7 +49,121 @@ extern crate proc_macro;
 use proc_macro::TokenStream;
 
 #[derive(Clone)]
-struct InstrumentField { name: String, ident: Ident }
+struct InstrumentField { name: String, ident: Ident, ty: Ty, unit: Option<String>,
+                         description: Option<String>, version: Option<u64>, transform: Option<syn::Path>,
+                         initial: bool, bulk: bool }
+
+/// Field attributes specified with `#[rapt(...)]`
+struct FieldAttrs {
+    name: Option<String>,
+    unit: Option<String>,
+    description: Option<String>,
+    version: Option<u64>,
+    transform: Option<syn::Path>,
+    initial: bool,
+    bulk: bool,
+}
+
+fn field_attrs(field: &Field) -> FieldAttrs {
+    let mut attrs = FieldAttrs { name: None, unit: None, description: None, version: None, transform: None,
+                                 initial: true, bulk: true };
+    for attr in field.attrs.iter().filter(|a| a.name() == "rapt") {
+        let items = match attr.value {
+            MetaItem::List(_, ref items) => items,
+            _ => panic!("#[rapt] attribute must contain a list of options, such as #[rapt(name = \"...\")]"),
+        };
+        for item in items {
+            match *item {
+                NestedMetaItem::MetaItem(MetaItem::NameValue(ref key, ref value)) => {
+                    let string = || match *value {
+                        Lit::Str(ref str, _) => str.clone(),
+                        _ => panic!("#[rapt({:} = \"...\")] attribute can only contain a string value", key),
+                    };
+                    let boolean = || match *value {
+                        Lit::Bool(b) => b,
+                        _ => panic!("#[rapt({:} = ...)] attribute can only contain a boolean value", key),
+                    };
+                    let integer = || match *value {
+                        Lit::Int(i, _) => i,
+                        _ => panic!("#[rapt({:} = ...)] attribute can only contain an integer value", key),
+                    };
+                    match key.as_ref() {
+                        "name" => attrs.name = Some(string()),
+                        "unit" => attrs.unit = Some(string()),
+                        "description" => attrs.description = Some(string()),
+                        "version" => attrs.version = Some(integer()),
+                        "transform" => attrs.transform = Some(syn::parse_path(&string()).unwrap_or_else(|_| {
+                            panic!("#[rapt(transform = \"...\")] attribute must contain a path to a function")
+                        })),
+                        "initial" => attrs.initial = boolean(),
+                        _ => panic!("unknown #[rapt] attribute `{:}`", key),
+                    }
+                },
+                NestedMetaItem::MetaItem(MetaItem::Word(ref key)) => {
+                    match key.as_ref() {
+                        "no_bulk" => attrs.bulk = false,
+                        _ => panic!("unknown #[rapt] attribute `{:}`", key),
+                    }
+                },
+                _ => panic!("unsupported #[rapt] attribute syntax"),
+            }
+        }
+    }
+    attrs
+}
+
+/// Struct attributes specified with `#[rapt(...)]`
+struct StructAttrs {
+    accessors: bool,
+}
+
+fn struct_attrs(attrs: &[syn::Attribute]) -> StructAttrs {
+    let mut struct_attrs = StructAttrs { accessors: false };
+    for attr in attrs.iter().filter(|a| a.name() == "rapt") {
+        let items = match attr.value {
+            MetaItem::List(_, ref items) => items,
+            _ => panic!("#[rapt] attribute must contain a list of options, such as #[rapt(accessors)]"),
+        };
+        for item in items {
+            match *item {
+                NestedMetaItem::MetaItem(MetaItem::Word(ref key)) => {
+                    match key.as_ref() {
+                        "accessors" => struct_attrs.accessors = true,
+                        _ => panic!("unknown #[rapt] attribute `{:}`", key),
+                    }
+                },
+                _ => panic!("unsupported #[rapt] attribute syntax"),
+            }
+        }
+    }
+    struct_attrs
+}
+
+/// Variant name specified with `#[rapt(name = "...")]`
+fn variant_name(attrs: &[syn::Attribute]) -> Option<String> {
+    let mut name = None;
+    for attr in attrs.iter().filter(|a| a.name() == "rapt") {
+        let items = match attr.value {
+            MetaItem::List(_, ref items) => items,
+            _ => panic!("#[rapt] attribute must contain a list of options, such as #[rapt(name = \"...\")]"),
+        };
+        for item in items {
+            match *item {
+                NestedMetaItem::MetaItem(MetaItem::NameValue(ref key, Lit::Str(ref value, _))) if key == "name" =>
+                    name = Some(value.clone()),
+                _ => panic!("unsupported #[rapt] attribute, only #[rapt(name = \"...\")] is allowed on variants"),
+            }
+        }
+    }
+    name
+}
+
+fn option_tokens(value: Option<String>) -> Tokens {
+    match value {
+        Some(value) => quote!{ Some(#value) },
+        None => quote!{ None },
+    }
+}
 #[proc_macro_derive(Instruments, attributes(rapt))]
 pub fn derive_instruments(input: TokenStream) -> TokenStream {
     let input = syn::parse_derive_input(&input.to_string()).unwrap();
@@ -57,73 +171,332 @@ pub fn derive_instruments(input: TokenStream) -> TokenStream {
 
     let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
     let listener_ident = &input.generics.ty_params.iter().last().unwrap().ident;
-    let dummy_const = Ident::new(format!("_IMPL_INSTRUMENTS_FOR_{}", ident));
+    let attrs = struct_attrs(&input.attrs);
 
     match input.body {
-        Body::Enum(_) => panic!("enums are not supported for Instruments derivations"),
+        Body::Enum(_) => panic!("enum {:} can't derive Instruments, as an instrument board must be a struct. \
+                                 To use it as a single instrument value, derive InstrumentState for it \
+                                 and put it into an Instrument", ident),
         Body::Struct(variants) => {
             let instruments : Vec<InstrumentField> = variants.fields().iter().enumerate()
                 .map(|(i, f)| {
-                    let overriding_name = match f.attrs.iter()
-                        .find(|a| a.name() == "rapt") {
-                           Some(attr) => match attr.value {
-                               MetaItem::List(_, ref items) =>
-                                   items.iter().find(|item| match item {
-                                       &&NestedMetaItem::MetaItem(ref item) => item.name() == "name",
-                                       _ => false,
-                                   }).map(|item| match item {
-                                        &NestedMetaItem::MetaItem(MetaItem::NameValue(_, Lit::Str(ref str, _))) => str.clone(),
-                                       _ =>
-                                           panic!("#[rapt(name = \"...\") attribute can only contain a string value"),
-                                   }),
-                               _ => None,
-                           },
-                           None => None,
-                    };
-                    if f.ident.is_none() && overriding_name.is_none() {
+                    let attrs = field_attrs(f);
+                    if f.ident.is_none() && attrs.name.is_none() {
                         panic!("struct {:} can't derive Instruments because field #{:} has no #[rapt(name = \"..\")] attribute", ident, i);
                     }
-                    let name = if overriding_name.is_some() {
-                        overriding_name.unwrap()
-                    } else {
-                        String::from(f.ident.clone().unwrap().as_ref())
+                    let name = match attrs.name {
+                        Some(name) => name,
+                        None => String::from(f.ident.clone().unwrap().as_ref()),
                     };
-                    InstrumentField { name, ident: f.ident.clone().unwrap() }
+                    InstrumentField { name, ident: f.ident.clone().unwrap(), ty: f.ty.clone(), unit: attrs.unit,
+                                      description: attrs.description, version: attrs.version,
+                                      transform: attrs.transform, initial: attrs.initial, bulk: attrs.bulk }
             }).collect();
-            let matches : Vec<Tokens> = instruments.clone().into_iter().map(|i| {
-                    let (name, ident) = (i.name, i.ident);
-                    quote!{ #name => self . #ident . serialize(serializer).map_err(|e| _rapt::ReadError::SerializationError(e))  }
+            let serializations : Vec<Tokens> = instruments.clone().into_iter().map(|i| {
+                    let ident = i.ident;
+                    match i.transform {
+                        Some(transform) => quote!{ self . #ident . serialize_mapped(serializer, #transform) },
+                        None => quote!{ _serde::Serialize::serialize(&self . #ident, serializer) },
+                    }
                 }).collect();
+            // Names are looked up with a binary search over a sorted array, which keeps
+            // both lookups and generated code small even for large boards
+            let mut sorted : Vec<(String, usize)> = instruments.iter().enumerate().map(|(index, i)| (i.name.clone(), index)).collect();
+            sorted.sort();
+            for pair in sorted.windows(2) {
+                if pair[0].0 == pair[1].0 {
+                    panic!("struct {:} can't derive Instruments because instrument name `{:}` is used more than once", ident, pair[0].0);
+                }
+            }
+            let sorted_names : Vec<Tokens> = sorted.into_iter().map(|(name, index)| quote!{ (#name, #index) }).collect();
+            let indexed_matches : Vec<Tokens> = serializations.iter().enumerate().map(|(index, serialization)| {
+                    quote!{ #index => #serialization.map_err(|e| _rapt::ReadError::SerializationError(e))  }
+                }).collect();
+            let count = instruments.len();
             let names : Vec<Tokens> = instruments.clone().into_iter().map(|i| {
                 let name = i.name;
                 quote!{ #name }
             }).collect();
+            let name_list = {
+                let names = names.clone();
+                quote!{ &[#(#names),*] }
+            };
+            let bulk_names : Vec<Tokens> = instruments.clone().into_iter().filter(|i| i.bulk).map(|i| {
+                let name = i.name;
+                quote!{ #name }
+            }).collect();
             let wirings : Vec<Tokens> = instruments.clone().into_iter().map(|i| {
                 let (name, ident) = (i.name, i.ident);
+                let unit = i.unit.map(|unit| quote!{ self . #ident . set_unit(#unit); });
+                let description = i.description.map(|description| quote!{ self . #ident . set_description(#description); });
+                let version = i.version.map(|version| {
+                    if version > u64::from(u32::MAX) {
+                        panic!("#[rapt(version = ...)] attribute of field `{:}` doesn't fit into u32", ident);
+                    }
+                    let version = version as u32;
+                    quote!{ self . #ident . set_schema_version(#version); }
+                });
+                if i.initial {
+                    quote!{
+                        #unit
+                        #description
+                        #version
+                        self . #ident . set_name_and_listener(#name, listener.clone())
+                    }
+                } else {
+                    quote!{
+                        #unit
+                        #description
+                        #version
+                        self . #ident . set_name(#name);
+                        self . #ident . set_listener(listener.clone())
+                    }
+                }
+            }).collect();
+            let field_wirings : Vec<Tokens> = instruments.clone().into_iter().zip(wirings.iter()).map(|(i, wiring)| {
+                let method = Ident::new(format!("wire_{}_listener", i.ident));
+                let doc = format!("Wires listener into `{}` instrument only", i.name);
+                quote!{
+                    #[doc = #doc]
+                    #[allow(dead_code)]
+                    pub fn #method(&mut self, listener: #listener_ident) {
+                        #wiring;
+                    }
+                }
+            }).collect();
+            let returning_wirings : Vec<Tokens> = instruments.clone().into_iter().zip(wirings.iter()).map(|(i, wiring)| {
+                let name = i.name;
+                quote!{
+                    #wiring;
+                    wired.push(#name);
+                }
+            }).collect();
+            let updated : Vec<Tokens> = instruments.clone().into_iter().enumerate().map(|(index, i)| {
+                let ident = i.ident;
+                quote!{ Some(#index) => self . #ident . has_been_updated() }
+            }).collect();
+            let poisoned : Vec<Tokens> = instruments.clone().into_iter().enumerate().map(|(index, i)| {
+                let ident = i.ident;
+                quote!{ Some(#index) => self . #ident . is_poisoned() }
+            }).collect();
+            let accessors : Vec<Tokens> = if attrs.accessors {
+                instruments.clone().into_iter().map(|i| {
+                    let (ident, ty) = (i.ident, i.ty);
+                    let doc = format!("Returns a handle to `{}` instrument", i.name);
+                    quote!{
+                        #[doc = #doc]
+                        #[allow(dead_code)]
+                        pub fn #ident(&self) -> #ty where #ty: Clone {
+                            self . #ident . clone()
+                        }
+                    }
+                }).collect()
+            } else {
+                vec![]
+            };
+            let units : Vec<Tokens> = instruments.clone().into_iter().map(|i| option_tokens(i.unit)).collect();
+            let descriptions : Vec<Tokens> = instruments.clone().into_iter().map(|i| option_tokens(i.description)).collect();
+            let resets : Vec<Tokens> = instruments.clone().into_iter().map(|i| {
+                let ident = i.ident;
+                quote!{
+                    if let Err(e) = _rapt::Reset::reset(&self . #ident) {
+                        if result.is_ok() {
+                            result = Err(e);
+                        }
+                    }
+                }
+            }).collect();
+            let reset_bounds : Vec<Tokens> = instruments.clone().into_iter().map(|i| {
+                let ty = i.ty;
+                quote!{ #ty : _rapt::Reset }
+            }).collect();
+            let restores : Vec<Tokens> = instruments.clone().into_iter().map(|i| {
+                let (ident, name) = (i.ident, i.name);
                 quote!{
-                    self . #ident . set_name_and_listener(#name, listener.clone())
+                    if let Some(reading) = snapshot.get(#name) {
+                        _rapt::Restore::restore(&self . #ident, reading)?;
+                    }
                 }
             }).collect();
+            let restore_bounds : Vec<Tokens> = instruments.clone().into_iter().map(|i| {
+                let ty = i.ty;
+                quote!{ #ty : _rapt::Restore }
+            }).collect();
+            let updated_since : Vec<Tokens> = instruments.clone().into_iter().map(|i| {
+                let ident = i.ident;
+                quote!{ || _rapt::UpdatedSince::updated_since(&self . #ident, &since) }
+            }).collect();
+            let updated_since_bounds : Vec<Tokens> = instruments.clone().into_iter().map(|i| {
+                let ty = i.ty;
+                quote!{ #ty : _rapt::UpdatedSince<__RaptSince> }
+            }).collect();
+            let describe_readings : Vec<Tokens> = instruments.clone().into_iter().filter(|i| i.bulk).map(|i| {
+                let (ty, name) = (i.ty, i.name);
+                quote!{ (#name, <#ty as _rapt::DescribeReading<__RaptGenerator>>::describe_reading(generator)) }
+            }).collect();
+            let describe_bounds : Vec<Tokens> = instruments.clone().into_iter().filter(|i| i.bulk).map(|i| {
+                let ty = i.ty;
+                quote!{ #ty : _rapt::DescribeReading<__RaptGenerator, Description = __RaptDescription> }
+            }).collect();
+            let reports : Vec<Tokens> = instruments.clone().into_iter().enumerate().filter(|(_, i)| i.bulk).map(|(index, i)| {
+                let (ident, name) = (i.ident, i.name);
+                quote!{
+                    let mut ser = _rapt::ser::InstantiateSerializer::instantiate_serializer(is, Vec::new());
+                    if _rapt::Instruments::serialize_reading_at(self, #index, &mut ser).is_ok() {
+                        report.push((#name, _rapt::ser::IntoWriter::into_writer(ser), _rapt::LastUpdated::last_update_at(&self . #ident)));
+                    }
+                }
+            }).collect();
+            let report_bounds : Vec<Tokens> = instruments.clone().into_iter().filter(|i| i.bulk).map(|i| {
+                let ty = i.ty;
+                quote!{ #ty : _rapt::LastUpdated }
+            }).collect();
+            // Kept apart from the rest of the block to stay within `quote!` recursion limit
+            let export_methods = quote! {
+                /// Describes readings of instruments included in exports of the entire board,
+                /// paired with their names
+                ///
+                /// With `json_schema` feature enabled, this can be passed to
+                /// `rapt::schema::board_schema`. See `rapt::DescribeReading` for details.
+                #[allow(dead_code)]
+                pub fn describe_readings<__RaptGenerator, __RaptDescription>(generator: &mut __RaptGenerator)
+                                         -> Vec<(&'static str, __RaptDescription)> where #(#describe_bounds),* {
+                   vec![#(#describe_readings),*]
+                }
+
+                /// Serializes instruments included in exports of the entire board, along with
+                /// their names and timestamps
+                ///
+                /// Readings are listed in declaration order. Every reading is serialized into its
+                /// own buffer with a serializer instantiated with `is`. Instruments that fail to
+                /// serialize are skipped. Timestamps are only present if `timestamp_instruments`
+                /// feature is enabled, see `rapt::LastUpdated`.
+                #[allow(dead_code)]
+                pub fn full_report<__RaptIS, __RaptS>(&self, is: &__RaptIS) -> Vec<(&'static str, Vec<u8>, Option<_rapt::Timestamp>)>
+                    where for<'__rapt> __RaptIS: _rapt::ser::InstantiateSerializer<'__rapt, Vec<u8>, Target = __RaptS>,
+                          __RaptS: _rapt::ser::IntoWriter<Vec<u8>>, for<'__rapt> &'__rapt mut __RaptS: _serde::Serializer,
+                          #(#report_bounds),* {
+                   let mut report = Vec::new();
+                   #(#reports)*
+                   report
+                }
+            };
             let impl_block = quote! {
                 impl #impl_generics _rapt::Instruments<#listener_ident> for #ident #ty_generics #where_clause {
                    fn serialize_reading<K : AsRef<str>, S: _serde::Serializer>(&self, key: K, serializer: S) -> Result<S::Ok, _rapt::ReadError<S::Error>> {
-                      match key.as_ref() {
-                        #(#matches),*,
+                      match Self::__rapt_instrument_index(key.as_ref()) {
+                           Some(index) => self.serialize_reading_at(index, serializer),
+                           None => Err(_rapt::ReadError::NotFound),
+                      }
+                   }
+                   fn serialize_reading_at<S: _serde::Serializer>(&self, index: usize, serializer: S) -> Result<S::Ok, _rapt::ReadError<S::Error>> {
+                      match index {
+                        #(#indexed_matches),*,
                            _ => Err(_rapt::ReadError::NotFound),
                       }
                    }
                    fn instrument_names(&self) -> Vec<&'static str> {
                       vec![#(#names),*]
                    }
-                   fn wire_listener(&mut self, listener: L) {
+                   fn instrument_count(&self) -> usize {
+                      #count
+                   }
+                   fn bulk_instrument_names(&self) -> Vec<&'static str> {
+                      vec![#(#bulk_names),*]
+                   }
+                   fn instrument_unit<K : AsRef<str>>(&self, key: K) -> Option<&'static str> {
+                      const UNITS: &[Option<&str>] = &[#(#units),*];
+                      Self::__rapt_instrument_index(key.as_ref()).and_then(|index| UNITS[index])
+                   }
+                   fn instrument_description<K : AsRef<str>>(&self, key: K) -> Option<&'static str> {
+                      const DESCRIPTIONS: &[Option<&str>] = &[#(#descriptions),*];
+                      Self::__rapt_instrument_index(key.as_ref()).and_then(|index| DESCRIPTIONS[index])
+                   }
+                   fn instrument_has_been_updated<K : AsRef<str>>(&self, key: K) -> bool {
+                      match Self::__rapt_instrument_index(key.as_ref()) {
+                        #(#updated),*,
+                           _ => false,
+                      }
+                   }
+                   fn instrument_is_poisoned<K : AsRef<str>>(&self, key: K) -> bool {
+                      match Self::__rapt_instrument_index(key.as_ref()) {
+                        #(#poisoned),*,
+                           _ => false,
+                      }
+                   }
+                   fn wire_listener(&mut self, listener: #listener_ident) {
+                      _rapt::Listener::on_wired(&listener, #name_list);
                       #(#wirings);*
                    }
                 }
+
+                impl #impl_generics #ident #ty_generics #where_clause {
+                   /// Returns the position of the instrument in `instrument_names`
+                   #[doc(hidden)]
+                   fn __rapt_instrument_index(key: &str) -> Option<usize> {
+                      const SORTED: &[(&str, usize)] = &[#(#sorted_names),*];
+                      SORTED.binary_search_by(|&(name, _)| name.cmp(key)).ok().map(|found| SORTED[found].1)
+                   }
+
+                   #(#field_wirings)*
+
+                   /// Wires listener into all instruments, returning names of wired instruments
+                   /// in declaration order
+                   ///
+                   /// This is the same as `wire_listener`, but allows verifying that wiring
+                   /// covered the entire board.
+                   #[allow(dead_code)]
+                   pub fn wire_listener_returning(&mut self, listener: #listener_ident) -> Vec<&'static str> {
+                      _rapt::Listener::on_wired(&listener, #name_list);
+                      let mut wired = Vec::new();
+                      #(#returning_wirings)*
+                      wired
+                   }
+
+                   #(#accessors)*
+
+                   /// Resets all instruments to their default values
+                   ///
+                   /// Every instrument is reset (and notifies the listener) individually,
+                   /// even if some of them fail. The first error encountered is returned.
+                   #[allow(dead_code)]
+                   pub fn reset_all(&self) -> Result<(), _rapt::UpdateError> where #(#reset_bounds),* {
+                      let mut result = Ok(());
+                      #(#resets)*
+                      result
+                   }
+
+                   /// Restores instruments from a snapshot of their readings, keyed by instrument names
+                   ///
+                   /// Snapshot entries that don't name an instrument are ignored, instruments missing
+                   /// from the snapshot are left as they are. Restoring is not an update: listeners
+                   /// are not notified. See `rapt::Restore` for details. Stops at the first error.
+                   #[allow(dead_code)]
+                   pub fn restore_from<'__rapt, __RaptKey, __RaptReading>(&self, snapshot: &'__rapt ::std::collections::HashMap<__RaptKey, __RaptReading>)
+                                       -> Result<(), <&'__rapt __RaptReading as _serde::Deserializer<'__rapt>>::Error>
+                       where __RaptKey: ::std::borrow::Borrow<str> + ::std::hash::Hash + Eq,
+                             &'__rapt __RaptReading: _serde::Deserializer<'__rapt>, #(#restore_bounds),* {
+                      #(#restores)*
+                      Ok(())
+                   }
+
+                   /// Returns `true` if any instrument has been updated strictly after `since`
+                   ///
+                   /// Stops at the first updated instrument and doesn't serialize anything, which
+                   /// makes it a cheap check before serializing the whole board. Requires
+                   /// `timestamp_instruments` feature, `since` is a `DateTime<Utc>`.
+                   #[allow(dead_code)]
+                   pub fn any_updated_since<__RaptSince>(&self, since: __RaptSince) -> bool where #(#updated_since_bounds),* {
+                      false #(#updated_since)*
+                   }
+
+                   #export_methods
+                }
             };
 
             let generated = quote! {
                 #[allow(non_upper_case_globals, unused_attributes, unused_qualifications)]
-                const #dummy_const: () = {
+                const _: () = {
                     extern crate rapt as _rapt;
                     extern crate serde as _serde;
                     #impl_block
@@ -133,3 +506,105 @@ pub fn derive_instruments(input: TokenStream) -> TokenStream {
         },
     }
 }
+
+/// Derives `Listener` for an enum dispatching notifications to one of several listeners
+///
+/// Every variant must either carry a single listener, which notifications are delegated
+/// to, or no data at all, in which case notifications are discarded.
+#[proc_macro_derive(Listener)]
+pub fn derive_listener(input: TokenStream) -> TokenStream {
+    let input = syn::parse_derive_input(&input.to_string()).unwrap();
+    let ident = input.ident;
+
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let variants = match input.body {
+        Body::Enum(variants) => variants,
+        Body::Struct(_) => panic!("struct {:} can't derive Listener, only enums can", ident),
+    };
+    let (updated, wired) : (Vec<Tokens>, Vec<Tokens>) = variants.into_iter().map(|v| {
+        let variant = v.ident;
+        match v.data {
+            VariantData::Tuple(ref fields) if fields.len() == 1 => (
+                quote!{ #ident :: #variant (ref listener) => _rapt::Listener::instrument_updated(listener, name) },
+                quote!{ #ident :: #variant (ref listener) => _rapt::Listener::on_wired(listener, names) },
+            ),
+            VariantData::Unit => (quote!{ #ident :: #variant => () }, quote!{ #ident :: #variant => () }),
+            _ => panic!("variant {:}::{:} must either carry a single listener or no data to derive Listener",
+                        ident, variant),
+        }
+    }).unzip();
+
+    let generated = quote! {
+        #[allow(non_upper_case_globals, unused_attributes, unused_qualifications)]
+        const _: () = {
+            extern crate rapt as _rapt;
+
+            impl #impl_generics _rapt::Listener for #ident #ty_generics #where_clause {
+               fn instrument_updated(&self, name: &'static str) {
+                  match *self {
+                    #(#updated),*
+                  }
+               }
+
+               fn on_wired(&self, names: &[&'static str]) {
+                  match *self {
+                    #(#wired),*
+                  }
+               }
+            }
+        };
+    };
+    generated.parse().unwrap()
+}
+
+/// Derives an instrument value out of an enum representing a state
+///
+/// The enum serializes as the name of its active variant (data carried by variants,
+/// if any, is not serialized), which can be overridden with `#[rapt(name = "...")]`.
+/// The name is also available through the generated `variant_name` method.
+#[proc_macro_derive(InstrumentState, attributes(rapt))]
+pub fn derive_instrument_state(input: TokenStream) -> TokenStream {
+    let input = syn::parse_derive_input(&input.to_string()).unwrap();
+    let ident = input.ident;
+
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let variants = match input.body {
+        Body::Enum(variants) => variants,
+        Body::Struct(_) => panic!("struct {:} can't derive InstrumentState, only enums can", ident),
+    };
+    let names : Vec<Tokens> = variants.into_iter().map(|v| {
+        let name = variant_name(&v.attrs).unwrap_or_else(|| String::from(v.ident.as_ref()));
+        let variant = v.ident;
+        match v.data {
+            VariantData::Struct(_) => quote!{ #ident :: #variant { .. } => #name },
+            VariantData::Tuple(_) => quote!{ #ident :: #variant ( .. ) => #name },
+            VariantData::Unit => quote!{ #ident :: #variant => #name },
+        }
+    }).collect();
+
+    let generated = quote! {
+        #[allow(non_upper_case_globals, unused_attributes, unused_qualifications)]
+        const _: () = {
+            extern crate serde as _serde;
+
+            impl #impl_generics #ident #ty_generics #where_clause {
+               /// Returns the name of the active variant
+               #[allow(dead_code)]
+               pub fn variant_name(&self) -> &'static str {
+                  match *self {
+                    #(#names),*
+                  }
+               }
+            }
+
+            impl #impl_generics _serde::Serialize for #ident #ty_generics #where_clause {
+               fn serialize<S: _serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                  serializer.serialize_str(self.variant_name())
+               }
+            }
+        };
+    };
+    generated.parse().unwrap()
+}
@@ -63,7 +63,7 @@ struct Datapoint {
 #[derive(Instruments, Default)]
 struct TestInstruments<L: Listener> {
     dp: Instrument<Datapoint, L>,
-    #[rapt(name = "info")]
+    #[rapt(name = "info", unit = "bytes", description = "Information size")]
     dp1: Instrument<Datapoint, L>,
 }
 
@@ -105,4 +105,350 @@ fn names() {
     let i = TestInstruments::<()>::default();
 
     assert_eq!(vec!["dp", "info"], i.instrument_names());
-}
\ No newline at end of file
+}
+#[test]
+fn reset_all() {
+    let i = TestInstruments::<()>::default();
+
+    assert!(i.dp.update(|v| v.value = 1).is_ok());
+    assert!(i.dp1.update(|v| v.value = 2).is_ok());
+
+    assert!(i.reset_all().is_ok());
+
+    assert_eq!(i.dp.read().unwrap().value, 0);
+    assert_eq!(i.dp1.read().unwrap().value, 0);
+}
+
+#[test]
+fn unit_attribute() {
+    let mut i = TestInstruments::<()>::default();
+
+    assert_eq!(i.instrument_unit("info"), Some("bytes"));
+    assert_eq!(i.instrument_unit("dp"), None);
+    assert_eq!(i.instrument_unit("missing_name"), None);
+
+    i.wire_listener(());
+    assert_eq!(i.dp1.unit(), Some("bytes"));
+    assert_eq!(i.dp.unit(), None);
+}
+
+#[test]
+// Tests that descriptions are exposed by the board and set on instruments
+fn description_attribute() {
+    let mut i = TestInstruments::<()>::default();
+
+    assert_eq!(i.instrument_description("info"), Some("Information size"));
+    assert_eq!(i.instrument_description("dp"), None);
+    assert_eq!(i.instrument_description("missing_name"), None);
+
+    i.wire_listener(());
+    assert_eq!(i.dp1.description(), Some("Information size"));
+    assert_eq!(i.dp.description(), None);
+}
+
+#[test]
+// Tests that instruments can be read by their position in instrument_names()
+fn indexed_reading() {
+    let i = TestInstruments::<()>::default();
+    assert_eq!(i.instrument_count(), 2);
+
+    let mut by_name = serde_msgpack::Serializer::new_named(Vec::new());
+    i.serialize_reading("info", &mut by_name).unwrap();
+    let mut by_index = serde_msgpack::Serializer::new_named(Vec::new());
+    i.serialize_reading_at(1, &mut by_index).unwrap();
+    assert_eq!(by_name.into_inner(), by_index.into_inner());
+
+    let mut ser = serde_msgpack::Serializer::new_named(Vec::new());
+    assert_matches!(i.serialize_reading_at(2, &mut ser), Err(ReadError::NotFound));
+}
+
+#[derive(Instruments)]
+struct BoundedInstruments<Listen> where Listen: Listener + Send + Sync + 'static {
+    dp: Instrument<Datapoint, Listen>,
+}
+
+// Bounds on the listener type parameter (whatever its name is) are carried over
+#[test]
+fn listener_bounds() {
+    fn assert_instruments<I: Instruments<L>, L: Listener>(_: &I) {}
+    let mut i = BoundedInstruments::<()> { dp: Instrument::new(Datapoint::default()) };
+    i.wire_listener(());
+    assert_instruments::<_, ()>(&i);
+    assert_eq!(vec!["dp"], i.instrument_names());
+}
+
+// Per-instrument wiring only affects that instrument
+#[test]
+fn per_instrument_wiring() {
+    use std::sync::mpsc;
+
+    let mut i = TestInstruments { dp: Instrument::default(), dp1: Instrument::default() };
+    let (tx, rx) = mpsc::channel();
+    let (tx1, rx1) = mpsc::channel();
+    i.wire_dp_listener(tx);
+    i.wire_dp1_listener(tx1);
+    assert_eq!(rx.try_recv().unwrap(), "dp");
+    assert_eq!(rx1.try_recv().unwrap(), "info");
+
+    assert!(i.dp1.update(|v| v.value = 1).is_ok());
+    assert!(rx.try_recv().is_err());
+    assert_eq!(rx1.try_recv().unwrap(), "info");
+    assert_eq!(i.dp1.unit(), Some("bytes"));
+}
+
+// Wiring reports wired instruments in declaration order
+#[test]
+fn wire_listener_returning() {
+    use std::sync::mpsc;
+
+    let mut i = TestInstruments { dp: Instrument::default(), dp1: Instrument::default() };
+    let (tx, rx) = mpsc::channel();
+    assert_eq!(i.wire_listener_returning(tx), vec!["dp", "info"]);
+    assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec!["dp", "info"]);
+}
+
+mod private {
+    use rapt::*;
+
+    #[derive(Instruments, Default)]
+    #[rapt(accessors)]
+    pub struct PrivateInstruments<L: Listener> {
+        #[rapt(name = "value")]
+        counter: Instrument<u32, L>,
+    }
+}
+
+// Accessors return handles to private instruments
+#[test]
+fn accessors() {
+    let i = private::PrivateInstruments::<()>::default();
+    assert!(i.counter().update(|v| *v = 1).is_ok());
+    assert_eq!(*i.counter().read().unwrap(), 1);
+}
+
+#[derive(Instruments, Default)]
+struct HierarchicalInstruments<L: Listener> {
+    #[rapt(name = "value/main")]
+    main: Instrument<Datapoint, L>,
+    #[rapt(name = "value/supplemental")]
+    supplemental: Instrument<Datapoint, L>,
+    status: Instrument<Datapoint, L>,
+}
+
+// Names are grouped by their prefixes
+#[test]
+fn grouped_names() {
+    let i = HierarchicalInstruments::<()>::default();
+    let names = i.grouped_instrument_names("/");
+
+    let groups: Vec<_> = names.groups().map(|(segment, group)| (segment, group.instrument())).collect();
+    assert_eq!(groups, vec![("value", None), ("status", Some("status"))]);
+    let value: Vec<_> = names.group("value").unwrap().groups()
+        .map(|(segment, group)| (segment, group.instrument())).collect();
+    assert_eq!(value, vec![("main", Some("value/main")), ("supplemental", Some("value/supplemental"))]);
+}
+
+#[derive(InstrumentState, Debug, PartialEq)]
+#[allow(dead_code)]
+enum State {
+    Stopped,
+    #[rapt(name = "running")]
+    Started(u32),
+    Failed { reason: String },
+}
+
+#[derive(Instruments)]
+struct StateInstruments<L: Listener> {
+    state: Instrument<State, L>,
+}
+
+// Enums are serialized as names of their active variants
+#[test]
+fn instrument_state() {
+    let i = StateInstruments::<()> { state: Instrument::new(State::Stopped) };
+    assert_eq!(i.state.read().unwrap().variant_name(), "Stopped");
+
+    i.state.update(|v| *v = State::Started(1)).unwrap();
+    assert_eq!(i.state.read().unwrap().variant_name(), "running");
+
+    let mut ser = serde_msgpack::Serializer::new(Vec::new());
+    State::Failed { reason: "timeout".into() }.serialize(&mut ser).unwrap();
+    let mut expected = serde_msgpack::Serializer::new(Vec::new());
+    "Failed".serialize(&mut expected).unwrap();
+    assert_eq!(ser.into_inner(), expected.into_inner());
+}
+
+fn megabytes(bytes: &u64) -> u64 {
+    bytes / 1024 / 1024
+}
+
+#[derive(Instruments)]
+struct TransformInstruments<L: Listener> {
+    #[rapt(transform = "megabytes")]
+    memory: Instrument<u64, L>,
+}
+
+#[derive(Deserialize)]
+struct Reading {
+    value: Option<u64>,
+}
+
+#[test]
+// Tests that transformed values are serialized, but not stored
+fn transform_attribute() {
+    let i = TransformInstruments::<()> { memory: Instrument::new(5 * 1024 * 1024) };
+
+    let mut ser = serde_msgpack::Serializer::new_named(Vec::new());
+    i.serialize_reading("memory", &mut ser).unwrap();
+    let reading: Reading = serde_msgpack::from_slice(&ser.into_inner()).unwrap();
+    assert_eq!(reading.value, Some(5));
+
+    let mut ser = serde_msgpack::Serializer::new_named(Vec::new());
+    i.serialize_reading_at(0, &mut ser).unwrap();
+    let reading: Reading = serde_msgpack::from_slice(&ser.into_inner()).unwrap();
+    assert_eq!(reading.value, Some(5));
+
+    assert_eq!(*i.memory.read().unwrap(), 5 * 1024 * 1024);
+}
+
+#[derive(Clone, Default)]
+struct StructureListener {
+    names: std::sync::Arc<std::sync::Mutex<Vec<&'static str>>>,
+}
+
+impl Listener for StructureListener {
+    fn instrument_updated(&self, _name: &'static str) {}
+
+    fn on_wired(&self, names: &[&'static str]) {
+        self.names.lock().unwrap().extend_from_slice(names);
+    }
+}
+
+#[test]
+// Tests that the listener learns instrument names once when wired
+fn on_wired() {
+    let listener = StructureListener::default();
+    let mut i = TestInstruments::default();
+    i.wire_listener(listener.clone());
+    assert_eq!(*listener.names.lock().unwrap(), vec!["dp", "info"]);
+
+    i.wire_dp_listener(listener.clone());
+    assert_eq!(listener.names.lock().unwrap().len(), 2);
+}
+
+#[derive(Instruments, Default)]
+struct LargeInstruments<L: Listener> {
+    z: Instrument<u8, L>, y: Instrument<u8, L>, x: Instrument<u8, L>, w: Instrument<u8, L>,
+    v: Instrument<u8, L>, u: Instrument<u8, L>, t: Instrument<u8, L>, s: Instrument<u8, L>,
+    r: Instrument<u8, L>, q: Instrument<u8, L>, p: Instrument<u8, L>, o: Instrument<u8, L>,
+    n: Instrument<u8, L>, m: Instrument<u8, L>, l: Instrument<u8, L>, k: Instrument<u8, L>,
+    j: Instrument<u8, L>, i: Instrument<u8, L>, h: Instrument<u8, L>, g: Instrument<u8, L>,
+    f: Instrument<u8, L>, e: Instrument<u8, L>, d: Instrument<u8, L>, c: Instrument<u8, L>,
+    b: Instrument<u8, L>,
+    #[rapt(name = "aa", unit = "items")]
+    a: Instrument<u8, L>,
+}
+
+#[test]
+// Tests that every instrument of a large board is found by name
+fn large_board() {
+    let i = LargeInstruments::<()>::default();
+    i.m.update(|v| *v = 1).unwrap();
+
+    for name in i.instrument_names() {
+        let mut ser = serde_msgpack::Serializer::new_named(Vec::new());
+        assert!(i.serialize_reading(name, &mut ser).is_ok(), "{} not found", name);
+        assert_eq!(i.instrument_has_been_updated(name), name == "m");
+    }
+    assert_eq!(i.instrument_unit("aa"), Some("items"));
+    assert_eq!(i.instrument_unit("a"), None);
+
+    let mut ser = serde_msgpack::Serializer::new_named(Vec::new());
+    assert_matches!(i.serialize_reading("a", &mut ser), Err(ReadError::NotFound));
+}
+
+#[derive(Listener, Clone)]
+enum Sinks {
+    Channel(std::sync::mpsc::Sender<&'static str>),
+    Counting(CountingListener),
+    Discard,
+}
+
+#[derive(Clone, Default)]
+struct CountingListener {
+    wired: std::sync::Arc<std::sync::atomic::AtomicUsize>,
+}
+
+impl Listener for CountingListener {
+    fn instrument_updated(&self, _name: &'static str) {}
+
+    fn on_wired(&self, names: &[&'static str]) {
+        self.wired.fetch_add(names.len(), std::sync::atomic::Ordering::SeqCst);
+    }
+}
+
+#[test]
+// Tests that listener enums delegate to their active variants
+fn listener_enum() {
+    let (tx, rx) = std::sync::mpsc::channel();
+    let mut i = TestInstruments { dp: Instrument::default(), dp1: Instrument::default() };
+    i.wire_dp_listener(Sinks::Channel(tx));
+    i.wire_dp1_listener(Sinks::Discard);
+    assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec!["dp"]);
+    i.dp.update(|v| v.value = 1).unwrap();
+    i.dp1.update(|v| v.value = 1).unwrap();
+    assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec!["dp"]);
+
+    let counting = CountingListener::default();
+    i.wire_listener(Sinks::Counting(counting.clone()));
+    assert_eq!(counting.wired.load(std::sync::atomic::Ordering::SeqCst), 2);
+}
+
+#[derive(Instruments)]
+struct OnDemandInstruments<L: Listener> {
+    #[rapt(unit = "requests", description = "Requests served", version = 2)]
+    requests: AtomicView<std::sync::atomic::AtomicU64, L>,
+    #[rapt(description = "Answer", version = 3)]
+    lazy: LazyInstrument<u64, fn() -> u64, L>,
+    #[rapt(unit = "percent", description = "Doubled", version = 4)]
+    computed: computed::ComputedInstrument<u64, u64, L>,
+    #[rapt(description = "Sampled", version = 5)]
+    sampled: sampled::SampledInstrument<u32, L>,
+    #[rapt(description = "Uptime", version = 6)]
+    uptime: uptime::Uptime<L>,
+}
+
+#[derive(Deserialize)]
+struct VersionedReading {
+    schema_version: Option<u32>,
+}
+
+#[test]
+// Tests that metadata attributes apply to instruments other than Instrument
+fn on_demand_metadata() {
+    fn answer() -> u64 {
+        42
+    }
+    let mut i = OnDemandInstruments::<()> {
+        requests: AtomicView::new(std::sync::Arc::new(std::sync::atomic::AtomicU64::new(0))),
+        lazy: LazyInstrument::new(answer),
+        computed: computed::ComputedInstrument::new(21, |v| v * 2),
+        sampled: sampled::SampledInstrument::new(0, 2),
+        uptime: uptime::Uptime::new(),
+    };
+    i.wire_listener(());
+
+    assert_eq!(i.instrument_description("requests"), Some("Requests served"));
+    assert_eq!((i.requests.unit(), i.requests.description()), (Some("requests"), Some("Requests served")));
+    assert_eq!(i.lazy.description(), Some("Answer"));
+    assert_eq!((i.computed.unit(), i.computed.description()), (Some("percent"), Some("Doubled")));
+    assert_eq!(i.sampled.description(), Some("Sampled"));
+    assert_eq!(i.uptime.description(), Some("Uptime"));
+
+    for &(name, version) in &[("requests", 2), ("lazy", 3), ("computed", 4), ("sampled", 5), ("uptime", 6)] {
+        let mut ser = serde_msgpack::Serializer::new_named(Vec::new());
+        i.serialize_reading(name, &mut ser).unwrap();
+        let reading: VersionedReading = serde_msgpack::from_slice(&ser.into_inner()).unwrap();
+        assert_eq!(reading.schema_version, Some(version));
+    }
+}
@@ -0,0 +1,177 @@
+// Copyright 2017 All Contributors (see CONTRIBUTORS file)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+//
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+
+//! # Atomic views
+//!
+//! Hot-path counters are often kept in plain atomics, and routing every increment through
+//! an [`Instrument`] is not always desirable. [`AtomicView`] exposes such an atomic as a
+//! read-only instrument: it loads the current value when serialized.
+//!
+//! Since the atomic is updated out-of-band, listeners are never notified about its updates
+//! and no update timestamp is tracked.
+//!
+//! [`Instrument`]: ../struct.Instrument.html
+//! [`AtomicView`]: struct.AtomicView.html
+
+use serde::{Serialize, Serializer, Deserializer};
+#[cfg(feature = "timestamp_instruments")]
+use chrono::prelude::*;
+
+use std::marker::PhantomData;
+use std::sync::Arc;
+use std::sync::atomic::{self, Ordering};
+
+use super::{LastUpdated, Listener, Metadata, Restore, Timestamp};
+#[cfg(feature = "timestamp_instruments")]
+use super::UpdatedSince;
+
+/// Atomic types that can be viewed by [`AtomicView`]
+///
+/// [`AtomicView`]: struct.AtomicView.html
+pub trait AtomicValue {
+    /// Type of the value
+    type Value: Serialize;
+    /// Loads current value
+    fn load_value(&self) -> Self::Value;
+}
+
+macro_rules! atomic_value {
+    ($($atomic: ident => $value: ty),*) => {
+        $(
+        impl AtomicValue for atomic::$atomic {
+            type Value = $value;
+            fn load_value(&self) -> $value {
+                self.load(Ordering::Relaxed)
+            }
+        }
+        )*
+    };
+}
+
+atomic_value!(AtomicBool => bool,
+              AtomicU8 => u8, AtomicU16 => u16, AtomicU32 => u32, AtomicU64 => u64,
+              AtomicUsize => usize,
+              AtomicI8 => i8, AtomicI16 => i16, AtomicI32 => i32, AtomicI64 => i64,
+              AtomicIsize => isize);
+
+/// A read-only instrument over an existing atomic
+///
+/// Serializes the same way as [`Instrument`] does, except for the `last_update_at` field
+/// which is never present.
+///
+/// ```rust
+/// extern crate rapt;
+///
+/// use std::sync::Arc;
+/// use std::sync::atomic::{AtomicU64, Ordering};
+/// use rapt::AtomicView;
+///
+/// fn main() {
+///     let requests = Arc::new(AtomicU64::new(0));
+///     let view = AtomicView::<_, ()>::new(requests.clone());
+///     requests.fetch_add(1, Ordering::Relaxed);
+///     assert_eq!(view.read(), 1);
+/// }
+/// ```
+///
+/// [`Instrument`]: ../struct.Instrument.html
+pub struct AtomicView<A: AtomicValue, L: Listener> {
+    atomic: Arc<A>,
+    metadata: Metadata,
+    listener: PhantomData<L>,
+}
+
+impl<A: AtomicValue, L: Listener> Clone for AtomicView<A, L> {
+    fn clone(&self) -> Self {
+        AtomicView {
+            atomic: self.atomic.clone(),
+            metadata: self.metadata,
+            listener: PhantomData,
+        }
+    }
+}
+
+impl<A: AtomicValue, L: Listener> AtomicView<A, L> {
+    /// Creates a new view over an atomic
+    pub fn new(atomic: Arc<A>) -> Self {
+        AtomicView {
+            atomic,
+            metadata: Metadata::default(),
+            listener: PhantomData,
+        }
+    }
+
+    metadata_accessors!(metadata);
+    on_demand_wiring!(L);
+
+    /// Loads current value of the atomic
+    pub fn read(&self) -> A::Value {
+        self.atomic.load_value()
+    }
+}
+
+impl<A: AtomicValue, L: Listener> Serialize for AtomicView<A, L> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where
+        S: Serializer {
+        self.metadata.serialize_computed(serializer, self.read())
+    }
+}
+
+/// Always `true`, as the value is computed on demand
+#[cfg(feature = "timestamp_instruments")]
+impl<A: AtomicValue, L: Listener> UpdatedSince<DateTime<Utc>> for AtomicView<A, L> {
+    fn updated_since(&self, _since: &DateTime<Utc>) -> bool {
+        true
+    }
+}
+
+/// Ignores the reading, as the value is computed on demand
+impl<A: AtomicValue, L: Listener> Restore for AtomicView<A, L> {
+    fn restore<'de, D: Deserializer<'de>>(&self, _reading: D) -> Result<(), D::Error> {
+        Ok(())
+    }
+}
+
+/// Never present, as the value is computed on demand
+impl<A: AtomicValue, L: Listener> LastUpdated for AtomicView<A, L> {
+    fn last_update_at(&self) -> Option<Timestamp> {
+        None
+    }
+}
@@ -0,0 +1,181 @@
+// Copyright 2017 All Contributors (see CONTRIBUTORS file)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+//
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+
+//! # Computed instruments
+//!
+//! Some values are derived from other instruments (for example, error rate is errors
+//! divided by requests). Instead of maintaining them on every update of their sources,
+//! [`ComputedInstrument`] holds handles (clones) of source instruments and computes its
+//! value out of them whenever it is read or serialized.
+//!
+//! Just like [lazy instruments], computed instruments can't be updated and never notify
+//! their listeners.
+//!
+//! [`ComputedInstrument`]: struct.ComputedInstrument.html
+//! [lazy instruments]: ../lazy/index.html
+
+use serde::{Serialize, Serializer, Deserializer};
+#[cfg(feature = "timestamp_instruments")]
+use chrono::prelude::*;
+
+use std::marker::PhantomData;
+
+use super::{LastUpdated, Listener, Metadata, Restore, Timestamp};
+#[cfg(feature = "timestamp_instruments")]
+use super::UpdatedSince;
+
+/// An instrument computing its value out of source instruments
+///
+/// `S` is typically an instrument handle or a tuple of them. The computation runs on
+/// every read, and it reads each source's current value under that source's lock, one
+/// source at a time, so the sources are not guaranteed to be read at the same instant.
+///
+/// Serializes the same way as [`Instrument`] does, except for the `last_update_at` field
+/// which is never present.
+///
+/// ```rust
+/// extern crate rapt;
+/// extern crate serde;
+/// #[macro_use]
+/// extern crate rapt_derive;
+///
+/// use rapt::{Instrument, Listener};
+/// use rapt::computed::ComputedInstrument;
+///
+/// type Counters<L> = (Instrument<u64, L>, Instrument<u64, L>);
+///
+/// #[derive(Instruments)]
+/// struct AppInstruments<L: Listener> {
+///     requests: Instrument<u64, L>,
+///     errors: Instrument<u64, L>,
+///     error_rate: ComputedInstrument<Counters<L>, f64, L>,
+/// }
+///
+/// fn error_rate<L: Listener>(&(ref requests, ref errors): &Counters<L>) -> f64 {
+///     let requests = *requests.read().unwrap();
+///     if requests == 0 { 0.0 } else { *errors.read().unwrap() as f64 / requests as f64 }
+/// }
+///
+/// fn main() {
+///     let requests = Instrument::new(0);
+///     let errors = Instrument::new(0);
+///     let instruments = AppInstruments::<()> {
+///         error_rate: ComputedInstrument::new((requests.clone(), errors.clone()), error_rate),
+///         requests,
+///         errors,
+///     };
+///     instruments.requests.update(|v| *v = 4).unwrap();
+///     instruments.errors.update(|v| *v = 1).unwrap();
+///     assert_eq!(instruments.error_rate.read(), 0.25);
+/// }
+/// ```
+///
+/// [`Instrument`]: ../struct.Instrument.html
+pub struct ComputedInstrument<S, T: Serialize, L: Listener> {
+    sources: S,
+    compute: fn(&S) -> T,
+    metadata: Metadata,
+    listener: PhantomData<L>,
+}
+
+impl<S: Clone, T: Serialize, L: Listener> Clone for ComputedInstrument<S, T, L> {
+    fn clone(&self) -> Self {
+        ComputedInstrument {
+            sources: self.sources.clone(),
+            compute: self.compute,
+            metadata: self.metadata,
+            listener: PhantomData,
+        }
+    }
+}
+
+impl<S, T: Serialize, L: Listener> ComputedInstrument<S, T, L> {
+    /// Creates a new instrument computing its value out of `sources` with `compute`
+    pub fn new(sources: S, compute: fn(&S) -> T) -> Self {
+        ComputedInstrument {
+            sources,
+            compute,
+            metadata: Metadata::default(),
+            listener: PhantomData,
+        }
+    }
+
+    metadata_accessors!(metadata);
+    on_demand_wiring!(L);
+
+    /// Returns source instruments
+    pub fn sources(&self) -> &S {
+        &self.sources
+    }
+
+    /// Computes current value
+    pub fn read(&self) -> T {
+        (self.compute)(&self.sources)
+    }
+}
+
+impl<S, T: Serialize, L: Listener> Serialize for ComputedInstrument<S, T, L> {
+    fn serialize<Ser>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error> where
+        Ser: Serializer {
+        self.metadata.serialize_computed(serializer, self.read())
+    }
+}
+
+/// Always `true`, as the value is computed on demand
+#[cfg(feature = "timestamp_instruments")]
+impl<S, T: Serialize, L: Listener> UpdatedSince<DateTime<Utc>> for ComputedInstrument<S, T, L> {
+    fn updated_since(&self, _since: &DateTime<Utc>) -> bool {
+        true
+    }
+}
+
+/// Ignores the reading, as the value is computed on demand
+impl<S, T: Serialize, L: Listener> Restore for ComputedInstrument<S, T, L> {
+    fn restore<'de, D: Deserializer<'de>>(&self, _reading: D) -> Result<(), D::Error> {
+        Ok(())
+    }
+}
+
+/// Never present, as the value is computed on demand
+impl<S, T: Serialize, L: Listener> LastUpdated for ComputedInstrument<S, T, L> {
+    fn last_update_at(&self) -> Option<Timestamp> {
+        None
+    }
+}
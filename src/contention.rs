@@ -0,0 +1,134 @@
+// Copyright 2017 All Contributors (see CONTRIBUTORS file)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+//
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+
+//! # Lock contention statistics
+//!
+//! _This module is only present if `lock_contention` feature is enabled.
+//! It is disabled by default, as it adds some overhead to every instrument operation._
+//!
+//! When enabled, every [`Instrument`] counts how many times its value lock has been
+//! acquired and how many of those acquisitions had to wait for another thread to
+//! release the lock. This helps diagnosing instruments that are slow to update or read.
+//!
+//! ```
+//! extern crate rapt;
+//!
+//! use rapt::Instrument;
+//!
+//! fn main() {
+//!     let requests: Instrument<u64, ()> = Instrument::new(0);
+//!     requests.update(|v| *v += 1).unwrap();
+//!     assert_eq!(*requests.read().unwrap(), 1);
+//!
+//!     let contention = requests.lock_contention();
+//!     assert_eq!(contention.acquisitions, 2);
+//!     assert_eq!(contention.blocked, 0);
+//! }
+//! ```
+//!
+//! [`Instrument`]: ../struct.Instrument.html
+
+use serde::{Serialize, Serializer};
+use serde::ser::SerializeStruct;
+
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard, LockResult, TryLockError};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Lock contention statistics of an instrument
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LockContention {
+    /// Number of times the lock has been acquired
+    pub acquisitions: u64,
+    /// Number of acquisitions that had to wait for the lock to be released
+    pub blocked: u64,
+}
+
+impl Serialize for LockContention {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where
+        S: Serializer {
+        let mut ss = serializer.serialize_struct("LockContention", 2)?;
+        ss.serialize_field("acquisitions", &self.acquisitions)?;
+        ss.serialize_field("blocked", &self.blocked)?;
+        ss.end()
+    }
+}
+
+/// Contention counters, shared by all clones of an instrument
+#[derive(Debug, Default)]
+pub(crate) struct Counters {
+    acquisitions: AtomicU64,
+    blocked: AtomicU64,
+}
+
+impl Counters {
+    /// Acquires a read lock, counting the acquisition
+    pub(crate) fn read<'a, T>(&self, lock: &'a RwLock<T>) -> LockResult<RwLockReadGuard<'a, T>> {
+        self.acquisitions.fetch_add(1, Ordering::Relaxed);
+        match lock.try_read() {
+            Ok(guard) => Ok(guard),
+            Err(TryLockError::Poisoned(poisoned)) => Err(poisoned),
+            Err(TryLockError::WouldBlock) => {
+                self.blocked.fetch_add(1, Ordering::Relaxed);
+                lock.read()
+            },
+        }
+    }
+
+    /// Acquires a write lock, counting the acquisition
+    pub(crate) fn write<'a, T>(&self, lock: &'a RwLock<T>) -> LockResult<RwLockWriteGuard<'a, T>> {
+        self.acquisitions.fetch_add(1, Ordering::Relaxed);
+        match lock.try_write() {
+            Ok(guard) => Ok(guard),
+            Err(TryLockError::Poisoned(poisoned)) => Err(poisoned),
+            Err(TryLockError::WouldBlock) => {
+                self.blocked.fetch_add(1, Ordering::Relaxed);
+                lock.write()
+            },
+        }
+    }
+
+    /// Returns current statistics
+    pub(crate) fn get(&self) -> LockContention {
+        LockContention {
+            acquisitions: self.acquisitions.load(Ordering::Relaxed),
+            blocked: self.blocked.load(Ordering::Relaxed),
+        }
+    }
+}
@@ -0,0 +1,298 @@
+// Copyright 2017 All Contributors (see CONTRIBUTORS file)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+//
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+
+//! # Deduplication
+//!
+//! Publishers only publish current values of instruments, and an update doesn't
+//! necessarily change the value. [`Dedup`] tracks what has been published last for each
+//! instrument so that repeated readings can be filtered out.
+//!
+//! The same [`checksum`] is available to consumers, for example to compute HTTP `ETag`s
+//! of readings serialized with [`serialize_checksummed`].
+//!
+//! Values flapping between a few states are never repeated consecutively, so [`FlapDamping`]
+//! goes further and holds back values that have been published recently until they settle.
+//!
+//! [`Dedup`]: struct.Dedup.html
+//! [`FlapDamping`]: struct.FlapDamping.html
+//! [`checksum`]: fn.checksum.html
+//! [`serialize_checksummed`]: fn.serialize_checksummed.html
+
+use serde::Serializer;
+
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+use super::{Instruments, Listener, ReadError};
+use super::ser::{FieldFilter, InstantiateSerializer, IntoWriter};
+
+/// Computes a 64-bit checksum of `bytes`
+///
+/// The algorithm is [FNV-1a], and it is not going to change, so checksums can be stored
+/// or compared across processes and versions of this crate.
+///
+/// [FNV-1a]: http://www.isthe.com/chongo/tech/comp/fnv/index.html#FNV-1a
+pub fn checksum(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3))
+}
+
+/// Serializes a particular instrument with `is` and returns the payload along with
+/// its [`checksum`]
+///
+/// Serialization errors are converted to strings, as their type depends on the serializer's
+/// lifetime. Please note that if `timestamp_instruments` feature is enabled, the payload
+/// includes `last_update_at`, so the checksum changes on every update.
+///
+/// ```rust
+/// extern crate rapt;
+/// extern crate serde;
+/// #[macro_use]
+/// extern crate rapt_derive;
+///
+/// use rapt::{Instrument, Listener};
+/// use rapt::dedup::{checksum, serialize_checksummed};
+/// use rapt::ser::JsonSerializer;
+///
+/// #[derive(Instruments)]
+/// struct AppInstruments<L: Listener> {
+///     requests: Instrument<u64, L>,
+/// }
+///
+/// fn main() {
+///     let instruments = AppInstruments::<()> { requests: Instrument::new(42) };
+///     let (payload, etag) = serialize_checksummed(&instruments, "requests", &JsonSerializer).unwrap();
+///     assert_eq!(etag, checksum(&payload));
+/// }
+/// ```
+///
+/// [`checksum`]: fn.checksum.html
+pub fn serialize_checksummed<I, L, IS, S, K>(instruments: &I, name: K, is: &IS) -> Result<(Vec<u8>, u64), ReadError<String>>
+    where I: Instruments<L>, L: Listener, K: AsRef<str>,
+          for<'a> IS: InstantiateSerializer<'a, Vec<u8>, Target=S>,
+          S: IntoWriter<Vec<u8>>, for<'a> &'a mut S: Serializer {
+    let mut ser = is.instantiate_serializer(Vec::new());
+    match instruments.serialize_reading(name, &mut ser) {
+        Ok(_) => (),
+        Err(ReadError::NotFound) => return Err(ReadError::NotFound),
+        Err(ReadError::SerializationError(err)) => return Err(ReadError::SerializationError(err.to_string())),
+    }
+    let payload = ser.into_writer();
+    let checksum = checksum(&payload);
+    Ok((payload, checksum))
+}
+
+/// Tracks readings' checksums to detect repeated readings
+///
+/// By default, the checksum is computed over instrument's `value` only. This way, a reading
+/// with the same value is recognized as a repeated one even though its `last_update_at`
+/// (if `timestamp_instruments` feature is enabled) has changed.
+///
+/// To consider other fields, use [`Dedup#with_filter`]. To hash entire readings, use
+/// [`Dedup#entire`].
+///
+/// [`Dedup#with_filter`]: struct.Dedup.html#method.with_filter
+/// [`Dedup#entire`]: struct.Dedup.html#method.entire
+#[derive(Debug, Clone)]
+pub struct Dedup {
+    filter: Option<FieldFilter>,
+    last_checksums: HashMap<&'static str, u64>,
+    buffer: Vec<u8>,
+}
+
+impl Default for Dedup {
+    fn default() -> Self {
+        Dedup::with_filter(FieldFilter::new(vec!["value"]))
+    }
+}
+
+impl Dedup {
+    /// Creates a new tracker hashing instrument values
+    pub fn new() -> Self {
+        Dedup::default()
+    }
+
+    /// Creates a new tracker hashing entire readings
+    ///
+    /// Please note that if `timestamp_instruments` feature is enabled, this includes
+    /// `last_update_at`, which changes on every update, so every update will be considered
+    /// a change.
+    pub fn entire() -> Self {
+        Dedup { filter: None, last_checksums: HashMap::new(), buffer: Vec::new() }
+    }
+
+    /// Creates a new tracker hashing only fields included by the filter
+    ///
+    /// The reading is serialized again, through the filter, to compute the checksum.
+    /// For example, `FieldFilter::new(vec!["value.status"])` will only consider a reading
+    /// changed when the `status` field of the instrument's value changes.
+    pub fn with_filter(filter: FieldFilter) -> Self {
+        Dedup { filter: Some(filter), ..Dedup::entire() }
+    }
+
+    /// Forgets the last reading of the `name` instrument, so that the next one is
+    /// considered changed
+    pub fn forget(&mut self, name: &str) {
+        self.last_checksums.remove(name);
+    }
+
+    /// Returns the checksum of the last reading of the `name` instrument seen, if any
+    pub fn last_checksum(&self, name: &str) -> Option<u64> {
+        self.last_checksums.get(name).cloned()
+    }
+
+    /// Returns `true` if the reading of the `name` instrument is different from the last
+    /// one seen (or if it is the first one), and remembers it
+    ///
+    /// `payload` is the reading serialized with `is`. It is only re-serialized if there
+    /// is a filter.
+    pub fn is_changed<I, L, IS, S>(&mut self, instruments: &I, name: &'static str, payload: &[u8], is: &IS) -> bool
+        where I: Instruments<L>, L: Listener,
+              for<'a> IS: InstantiateSerializer<'a, Vec<u8>, Target=S>,
+              S: IntoWriter<Vec<u8>>, for<'a> &'a mut S: Serializer {
+        let checksum = match self.filter {
+            None => checksum(payload),
+            Some(ref filter) => {
+                let mut buffer = ::std::mem::take(&mut self.buffer);
+                buffer.clear();
+                let mut ser = is.instantiate_serializer(buffer);
+                let filtered = instruments.serialize_reading(name, filter.serializer(&mut ser)).is_ok();
+                self.buffer = ser.into_writer();
+                if !filtered {
+                    // Can't tell, consider it changed
+                    self.last_checksums.remove(name);
+                    return true;
+                }
+                checksum(&self.buffer)
+            },
+        };
+        self.last_checksums.insert(name, checksum) != Some(checksum)
+    }
+}
+
+/// Holds back readings flapping between a few values
+///
+/// When a value oscillates (`A`, `B`, `A`, `B`, ...), every reading differs from the
+/// previous one, so [`Dedup`] lets all of them through. With flap damping, a changed reading
+/// whose checksum has already been published within `window` is held back. It is only
+/// published once it has stayed current for `cooldown`. If the value returns to the one
+/// published last in the meantime, nothing is published at all.
+///
+/// Checksums are the ones computed by [`Dedup`], so by default only values are compared.
+///
+/// [`Dedup`]: struct.Dedup.html
+#[derive(Debug, Clone)]
+pub struct FlapDamping {
+    window: Duration,
+    cooldown: Duration,
+    /// Checksums of published readings along with the time of publishing, oldest first.
+    /// The last one is the checksum of the currently published reading.
+    published: HashMap<&'static str, VecDeque<(u64, Instant)>>,
+    /// Readings held back, along with the time they became current
+    held: HashMap<&'static str, (u64, Instant)>,
+}
+
+impl FlapDamping {
+    /// Creates a new damper holding back readings published within `window`
+    /// until they have been current for `cooldown`
+    pub fn new(window: Duration, cooldown: Duration) -> Self {
+        FlapDamping { window, cooldown, published: HashMap::new(), held: HashMap::new() }
+    }
+
+    /// Returns `true` if a changed reading of the `name` instrument with a given checksum
+    /// should be published now, and remembers it as published if so
+    pub fn admit(&mut self, name: &'static str, checksum: u64, now: Instant) -> bool {
+        let window = self.window;
+        let published = self.published.entry(name).or_default();
+        while published.len() > 1 && published.front().map_or(false, |&(_, at)| now.duration_since(at) >= window) {
+            published.pop_front();
+        }
+        if published.back().map_or(false, |&(last, _)| last == checksum) {
+            // Back to the published value
+            self.held.remove(name);
+            return false;
+        }
+        if published.iter().any(|&(recent, _)| recent == checksum) {
+            match self.held.get(name) {
+                Some(&(held, _)) if held == checksum => (),
+                _ => { self.held.insert(name, (checksum, now)); },
+            }
+            return false;
+        }
+        self.held.remove(name);
+        published.push_back((checksum, now));
+        true
+    }
+
+    /// Returns names of instruments whose held back readings have been current for the
+    /// cooldown by `now`, along with their checksums, and stops holding them back
+    ///
+    /// The value might have changed since it was held back, so settled readings are not
+    /// remembered as published until [`FlapDamping#settle`] is called.
+    ///
+    /// [`FlapDamping#settle`]: struct.FlapDamping.html#method.settle
+    pub fn settled(&mut self, now: Instant) -> Vec<(&'static str, u64)> {
+        let cooldown = self.cooldown;
+        let settled: Vec<_> = self.held.iter()
+            .filter(|&(_, &(_, since))| now.duration_since(since) >= cooldown)
+            .map(|(&name, &(checksum, _))| (name, checksum))
+            .collect();
+        for &(name, _) in &settled {
+            self.held.remove(name);
+        }
+        settled
+    }
+
+    /// Remembers a settled reading of the `name` instrument with a given checksum as published
+    pub fn settle(&mut self, name: &'static str, checksum: u64, now: Instant) {
+        self.published.entry(name).or_default().push_back((checksum, now));
+    }
+
+    /// Returns the earliest time a held back reading settles, if there's one
+    pub fn next_settlement(&self) -> Option<Instant> {
+        self.held.values().map(|&(_, since)| since + self.cooldown).min()
+    }
+
+    /// Forgets what has been published for the `name` instrument, so that its next
+    /// reading is admitted
+    pub fn forget(&mut self, name: &str) {
+        self.published.remove(name);
+        self.held.remove(name);
+    }
+}
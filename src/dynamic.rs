@@ -0,0 +1,226 @@
+// Copyright 2017 All Contributors (see CONTRIBUTORS file)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+//
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+
+//! # Dynamic instrument boards
+//!
+//! _This module is only present if `dynamic_instruments` feature is enabled.
+//! It is disabled by default._
+//!
+//! Derived instrument boards have a fixed set of instruments. Plugin architectures
+//! and the like need to register instruments that are only discovered at runtime,
+//! which is what [`DynamicBoard`] is for. It holds any instruments implementing
+//! [`DynInstrument`], an object-safe counterpart of the instrument interface.
+//!
+//! ```rust
+//! extern crate rapt;
+//!
+//! use rapt::{Instrument, Instruments};
+//! use rapt::dynamic::DynamicBoard;
+//! use std::sync::mpsc;
+//!
+//! fn main() {
+//!     let (tx, rx) = mpsc::channel();
+//!     let mut board = DynamicBoard::new();
+//!     board.wire_listener(tx);
+//!
+//!     let requests: Instrument<u64, _> = Instrument::new(0);
+//!     let requests = board.register("requests", requests).ok().expect("already registered");
+//!     requests.update(|v| *v += 1).unwrap();
+//!
+//!     assert_eq!(board.instrument_names(), vec!["requests"]);
+//!     assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec!["requests", "requests"]);
+//! }
+//! ```
+//!
+//! [`DynamicBoard`]: struct.DynamicBoard.html
+//! [`DynInstrument`]: trait.DynInstrument.html
+
+use super::{Instrument, Instruments, Listener, ReadError};
+use serde::{Serialize, Serializer};
+
+use std::any::Any;
+
+/// Object-safe instrument interface
+///
+/// Implemented by [`Instrument`]. Serialization is type-erased with [`erased_serde`].
+///
+/// [`Instrument`]: ../struct.Instrument.html
+/// [`erased_serde`]: https://docs.rs/erased-serde
+pub trait DynInstrument<L: Listener>: erased_serde::Serialize + Send + Sync {
+    /// Sets the name of the instrument and the listener
+    fn set_name_and_listener(&mut self, name: &'static str, listener: L);
+    /// Returns the unit of the instrument value, if any
+    fn unit(&self) -> Option<&'static str>;
+    /// Returns `true` if the instrument has ever been updated
+    fn has_been_updated(&self) -> bool;
+    /// Returns the instrument as `Any`, see [`downcast_ref`]
+    ///
+    /// [`downcast_ref`]: #method.downcast_ref
+    fn as_any(&self) -> &dyn Any;
+}
+
+erased_serde::serialize_trait_object!(<L> DynInstrument<L> where L: Listener);
+
+impl<'a, L: Listener + 'static> dyn DynInstrument<L> + 'a {
+    /// Returns the instrument as `Instrument<T, L>`, or `None` if it is of a different type
+    ///
+    /// ```rust
+    /// extern crate rapt;
+    ///
+    /// use rapt::Instrument;
+    /// use rapt::dynamic::DynamicBoard;
+    ///
+    /// fn main() {
+    ///     let mut board = DynamicBoard::<()>::new();
+    ///     board.register("requests", Instrument::new(42u64)).ok().expect("already registered");
+    ///
+    ///     let requests = board.get("requests").unwrap();
+    ///     assert_eq!(*requests.downcast_ref::<u64>().unwrap().read().unwrap(), 42);
+    ///     assert!(requests.downcast_ref::<String>().is_none());
+    /// }
+    /// ```
+    pub fn downcast_ref<T: Serialize + 'static>(&self) -> Option<&Instrument<T, L>> {
+        self.as_any().downcast_ref()
+    }
+}
+
+impl<T: Serialize + Send + Sync + 'static, L: Listener + Send + Sync + 'static> DynInstrument<L> for Instrument<T, L> {
+    fn set_name_and_listener(&mut self, name: &'static str, listener: L) {
+        Instrument::set_name_and_listener(self, name, listener)
+    }
+
+    fn unit(&self) -> Option<&'static str> {
+        Instrument::unit(self)
+    }
+
+    fn has_been_updated(&self) -> bool {
+        Instrument::has_been_updated(self)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// Instrument board with instruments registered at runtime
+///
+/// Instruments are kept in registration order. Once a listener is wired, it is also
+/// wired into every instrument registered later. Handles returned by [`register`] before
+/// that don't get the listener though, so the board should be wired first.
+///
+/// [`register`]: #method.register
+pub struct DynamicBoard<L: Listener> {
+    instruments: Vec<(&'static str, Box<dyn DynInstrument<L>>)>,
+    listener: Option<L>,
+}
+
+impl<L: Listener> Default for DynamicBoard<L> {
+    fn default() -> Self {
+        DynamicBoard::new()
+    }
+}
+
+impl<L: Listener> DynamicBoard<L> {
+    /// Creates an empty board
+    pub fn new() -> Self {
+        DynamicBoard { instruments: vec![], listener: None }
+    }
+
+    /// Registers an instrument under `name`, returning a handle (a clone) to it
+    ///
+    /// The instrument should be updated through the returned handle, as it is wired into
+    /// the board's listener. Fails (returning the instrument back) if there is an instrument
+    /// with such name already.
+    pub fn register<I>(&mut self, name: &'static str, mut instrument: I) -> Result<I, I>
+        where I: DynInstrument<L> + Clone + 'static {
+        if self.instruments.iter().any(|&(n, _)| n == name) {
+            return Err(instrument);
+        }
+        if let Some(ref listener) = self.listener {
+            instrument.set_name_and_listener(name, listener.clone());
+        }
+        self.instruments.push((name, Box::new(instrument.clone())));
+        Ok(instrument)
+    }
+
+    /// Unregisters the instrument with such name, returning it
+    pub fn unregister(&mut self, name: &str) -> Option<Box<dyn DynInstrument<L>>> {
+        let index = self.instruments.iter().position(|&(n, _)| n == name)?;
+        Some(self.instruments.remove(index).1)
+    }
+
+    /// Returns the instrument with such name
+    ///
+    /// Use [`downcast_ref`] to get the typed instrument back.
+    ///
+    /// [`downcast_ref`]: trait.DynInstrument.html#method.downcast_ref
+    pub fn get(&self, name: &str) -> Option<&dyn DynInstrument<L>> {
+        self.instruments.iter().find(|&&(n, _)| n == name).map(|(_, instrument)| &**instrument)
+    }
+}
+
+impl<L: Listener> Instruments<L> for DynamicBoard<L> {
+    fn serialize_reading<K : AsRef<str>, S: Serializer>(&self, key: K, serializer: S) -> Result<S::Ok, ReadError<S::Error>> {
+        match self.get(key.as_ref()) {
+            Some(instrument) => instrument.serialize(serializer).map_err(ReadError::SerializationError),
+            None => Err(ReadError::NotFound),
+        }
+    }
+
+    fn instrument_names(&self) -> Vec<&'static str> {
+        self.instruments.iter().map(|&(name, _)| name).collect()
+    }
+
+    fn instrument_unit<K : AsRef<str>>(&self, key: K) -> Option<&'static str> {
+        self.get(key.as_ref()).and_then(|instrument| instrument.unit())
+    }
+
+    fn instrument_has_been_updated<K : AsRef<str>>(&self, key: K) -> bool {
+        self.get(key.as_ref()).map(|instrument| instrument.has_been_updated()).unwrap_or(false)
+    }
+
+    fn wire_listener(&mut self, listener: L) {
+        listener.on_wired(&self.instrument_names());
+        for &mut (name, ref mut instrument) in &mut self.instruments {
+            instrument.set_name_and_listener(name, listener.clone());
+        }
+        self.listener = Some(listener);
+    }
+}
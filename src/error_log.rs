@@ -0,0 +1,163 @@
+// Copyright 2017 All Contributors (see CONTRIBUTORS file)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+//
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+
+//! # Error log
+//!
+//! _This module is only present if `timestamp_instruments` feature is enabled
+//! (it is enabled by default)._
+//!
+//! A common question to ask a running application is "what were the last few errors
+//! this subsystem hit?" [`ErrorLogInstrument`] answers it by keeping a bounded log
+//! of timestamped error messages.
+//!
+//! [`ErrorLogInstrument`]: struct.ErrorLogInstrument.html
+
+use serde::{Serialize, Serializer};
+use chrono::prelude::*;
+
+use std::collections::VecDeque;
+use std::fmt::Display;
+use std::sync::{RwLockReadGuard, LockResult};
+
+use super::{Instrument, LastUpdated, Listener, Reset, Timestamp, UpdateError, UpdatedSince};
+
+/// Log entries, oldest first
+pub type ErrorLog = VecDeque<(DateTime<Utc>, String)>;
+
+/// An instrument holding a bounded log of recent errors
+///
+/// Every recorded error is timestamped and notifies the listener. Once the log
+/// reaches its capacity, the oldest entries are evicted.
+///
+/// Serializes the same way as [`Instrument`] does, with the value being an array of
+/// `[timestamp, message]` entries, oldest first.
+///
+/// ```rust
+/// extern crate rapt;
+///
+/// use rapt::error_log::ErrorLogInstrument;
+///
+/// fn main() {
+///     let errors = ErrorLogInstrument::<()>::new(2);
+///     for err in &["timeout", "refused", "reset"] {
+///         errors.record(err).unwrap();
+///     }
+///     let log = errors.read().unwrap();
+///     assert_eq!(log.iter().map(|e| e.1.as_str()).collect::<Vec<_>>(), vec!["refused", "reset"]);
+/// }
+/// ```
+///
+/// [`Instrument`]: ../struct.Instrument.html
+#[derive(Clone)]
+pub struct ErrorLogInstrument<L: Listener> {
+    instrument: Instrument<ErrorLog, L>,
+    capacity: usize,
+}
+
+impl<L: Listener> ErrorLogInstrument<L> {
+    /// Creates a new error log holding up to `capacity` entries
+    pub fn new(capacity: usize) -> Self {
+        ErrorLogInstrument {
+            instrument: Instrument::new(VecDeque::with_capacity(capacity)),
+            capacity,
+        }
+    }
+
+    metadata_accessors!(instrument.metadata);
+    wrapped_wiring!(instrument, L);
+
+    /// Returns the maximum number of entries kept
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns `true` if any error has ever been recorded
+    pub fn has_been_updated(&self) -> bool {
+        self.instrument.has_been_updated()
+    }
+
+    /// Returns `true` if a thread has panicked while recording an error
+    pub fn is_poisoned(&self) -> bool {
+        self.instrument.is_poisoned()
+    }
+
+    /// Thread-safe log reader
+    pub fn read(&self) -> LockResult<RwLockReadGuard<'_, ErrorLog>> {
+        self.instrument.read()
+    }
+
+    /// Records an error, evicting the oldest entries past capacity
+    pub fn record<E: Display>(&self, err: E) -> Result<(), UpdateError> {
+        let entry = (super::now(), err.to_string());
+        let capacity = self.capacity;
+        self.instrument.update(|log| {
+            log.push_back(entry.clone());
+            while log.len() > capacity {
+                log.pop_front();
+            }
+        })
+    }
+}
+
+/// Clears the log
+impl<L: Listener> Reset for ErrorLogInstrument<L> {
+    fn reset(&self) -> Result<(), UpdateError> {
+        self.instrument.reset()
+    }
+}
+
+impl<L: Listener> Serialize for ErrorLogInstrument<L> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where
+        S: Serializer {
+        self.instrument.serialize(serializer)
+    }
+}
+
+impl<L: Listener> UpdatedSince<DateTime<Utc>> for ErrorLogInstrument<L> {
+    fn updated_since(&self, since: &DateTime<Utc>) -> bool {
+        self.instrument.updated_since(since)
+    }
+}
+
+impl<L: Listener> LastUpdated for ErrorLogInstrument<L> {
+    fn last_update_at(&self) -> Option<Timestamp> {
+        self.instrument.last_update_at()
+    }
+}
@@ -0,0 +1,136 @@
+// Copyright 2017 All Contributors (see CONTRIBUTORS file)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+//
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+
+//! # eventfd Listener
+//!
+//! _This module is only present on Linux if `eventfd_listener` feature is enabled.
+//! It is disabled by default._
+//!
+//! [`EventFdListener`] notifies an [eventfd] about every instrument update. This allows
+//! event loops built around `poll`/`epoll` (including those not written in Rust) to learn
+//! about updates without a Rust channel.
+//!
+//! Every update increments the eventfd counter by one, so reading the eventfd yields the
+//! number of updates since the last read. If the counter is about to overflow, the update
+//! is not counted: the reader has pending notifications to process anyway.
+//!
+//! [`EventFdListener`]: struct.EventFdListener.html
+//! [eventfd]: http://man7.org/linux/man-pages/man2/eventfd.2.html
+
+use libc;
+
+use super::Listener;
+
+use std::io;
+use std::sync::Arc;
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+
+/// Owned eventfd, closed when dropped
+struct EventFd(RawFd);
+
+impl Drop for EventFd {
+    fn drop(&mut self) {
+        unsafe {
+            libc::close(self.0);
+        }
+    }
+}
+
+/// [`Listener`] that notifies an eventfd
+///
+/// ```rust
+/// extern crate rapt;
+///
+/// use std::os::unix::io::AsRawFd;
+/// use rapt::Listener;
+/// use rapt::eventfd::EventFdListener;
+///
+/// fn main() {
+///     let listener = EventFdListener::new().unwrap();
+///     // register `listener.as_raw_fd()` with an event loop
+///     # let _ = listener.as_raw_fd();
+///     listener.instrument_updated("instrument");
+/// }
+/// ```
+///
+/// [`Listener`]: ../trait.Listener.html
+#[derive(Clone)]
+pub struct EventFdListener {
+    fd: Arc<EventFd>,
+}
+
+impl EventFdListener {
+    /// Creates a new eventfd and a listener for it
+    pub fn new() -> io::Result<Self> {
+        let fd = unsafe { libc::eventfd(0, libc::EFD_CLOEXEC | libc::EFD_NONBLOCK) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(EventFdListener { fd: Arc::new(EventFd(fd)) })
+    }
+}
+
+impl AsRawFd for EventFdListener {
+    fn as_raw_fd(&self) -> RawFd {
+        (self.fd).0
+    }
+}
+
+/// Takes the ownership of an existing eventfd
+///
+/// The eventfd should be non-blocking (created with `EFD_NONBLOCK`), otherwise
+/// updates will block when its counter is about to overflow.
+impl FromRawFd for EventFdListener {
+    unsafe fn from_raw_fd(fd: RawFd) -> Self {
+        EventFdListener { fd: Arc::new(EventFd(fd)) }
+    }
+}
+
+impl Listener for EventFdListener {
+    #[allow(unused_variables)]
+    fn instrument_updated(&self, name: &'static str) {
+        let value: u64 = 1;
+        // The only expected failure is EAGAIN, when the counter is about to overflow,
+        // in which case it is fine to skip this update.
+        unsafe {
+            libc::write(self.as_raw_fd(), &value as *const u64 as *const libc::c_void, 8);
+        }
+    }
+}
@@ -0,0 +1,352 @@
+// Copyright 2017 All Contributors (see CONTRIBUTORS file)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+//
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+
+//! # Flat readings
+//!
+//! The simplest format numeric dashboards and time-series databases can consume
+//! is a flat map of names to numbers:
+//!
+//! ```json
+//! {"requests": 42, "errors": 3}
+//! ```
+//!
+//! [`FlatReadings`] serializes an instrument board this way, by extracting a [`Scalar`] out
+//! of every instrument's value. Numbers are taken as is and booleans become `0` or `1`.
+//! Instruments with any other values (strings, structs, sequences, and so on) are skipped.
+//!
+//! [`FlatReadings`]: struct.FlatReadings.html
+//! [`Scalar`]: enum.Scalar.html
+
+use serde::{Serialize, Serializer};
+use serde::ser::{self, Impossible, SerializeMap};
+
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::marker::PhantomData;
+
+use super::{Instruments, Listener};
+
+/// Numeric value of an instrument
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Scalar {
+    Signed(i64),
+    Unsigned(u64),
+    Float(f64),
+}
+
+impl Scalar {
+    /// Returns the value as `f64`, possibly losing precision
+    pub fn as_f64(&self) -> f64 {
+        match *self {
+            Scalar::Signed(v) => v as f64,
+            Scalar::Unsigned(v) => v as f64,
+            Scalar::Float(v) => v,
+        }
+    }
+}
+
+impl Serialize for Scalar {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where
+        S: Serializer {
+        match *self {
+            Scalar::Signed(v) => serializer.serialize_i64(v),
+            Scalar::Unsigned(v) => serializer.serialize_u64(v),
+            Scalar::Float(v) => serializer.serialize_f64(v),
+        }
+    }
+}
+
+/// Flat map of instrument names to their numeric values
+///
+/// ```rust
+/// extern crate rapt;
+/// extern crate serde;
+/// extern crate serde_json;
+/// #[macro_use]
+/// extern crate rapt_derive;
+///
+/// use rapt::{Instrument, Listener};
+/// use rapt::flat::FlatReadings;
+///
+/// #[derive(Instruments)]
+/// struct AppInstruments<L: Listener> {
+///     requests: Instrument<u64, L>,
+///     status: Instrument<String, L>,
+/// }
+///
+/// fn main() {
+///     let instruments = AppInstruments::<()> {
+///         requests: Instrument::new(42),
+///         status: Instrument::new("started".into()),
+///     };
+///     let json = serde_json::to_string(&FlatReadings::new(&instruments)).unwrap();
+///     assert_eq!(json, r#"{"requests":42}"#);
+/// }
+/// ```
+pub struct FlatReadings<'a, I: Instruments<L> + 'a, L: Listener> {
+    instruments: &'a I,
+    listener: PhantomData<L>,
+}
+
+impl<'a, I: Instruments<L> + 'a, L: Listener> FlatReadings<'a, I, L> {
+    /// Creates flat readings of the instrument board
+    pub fn new(instruments: &'a I) -> Self {
+        FlatReadings { instruments, listener: PhantomData }
+    }
+
+    /// Reads numeric values of all instruments that have them
+    ///
+    /// Instruments excluded from bulk reads (see [`Instruments#bulk_instrument_names`])
+    /// are skipped.
+    ///
+    /// [`Instruments#bulk_instrument_names`]: ../trait.Instruments.html#method.bulk_instrument_names
+    pub fn readings(&self) -> Vec<(&'static str, Scalar)> {
+        self.instruments.bulk_instrument_names().into_iter()
+            .filter_map(|name| self.instruments.serialize_reading(name, ScalarSerializer { top: true }).ok()
+                                   .map(|scalar| (name, scalar)))
+            .collect()
+    }
+}
+
+impl<'a, I: Instruments<L> + 'a, L: Listener> Serialize for FlatReadings<'a, I, L> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where
+        S: Serializer {
+        let readings = self.readings();
+        let mut map = serializer.serialize_map(Some(readings.len()))?;
+        for (name, scalar) in readings {
+            map.serialize_entry(name, &scalar)?;
+        }
+        map.end()
+    }
+}
+
+/// Signals that a value is not a scalar
+#[derive(Debug)]
+struct NotScalar;
+
+impl fmt::Display for NotScalar {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("not a scalar")
+    }
+}
+
+impl Error for NotScalar {}
+
+impl ser::Error for NotScalar {
+    fn custom<T: fmt::Display>(_msg: T) -> Self {
+        NotScalar
+    }
+}
+
+/// Extracts a scalar out of instrument's value
+///
+/// `top` is set for the instrument itself, which is the only struct allowed.
+struct ScalarSerializer {
+    top: bool,
+}
+
+/// Captures instrument's `value` field
+struct InstrumentValue {
+    value: Result<Scalar, NotScalar>,
+}
+
+impl ser::SerializeStruct for InstrumentValue {
+    type Ok = Scalar;
+    type Error = NotScalar;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, key: &'static str, value: &T)
+                                             -> Result<(), NotScalar> {
+        if key == "value" {
+            self.value = value.serialize(ScalarSerializer { top: false });
+        }
+        Ok(())
+    }
+
+    fn end(self) -> Result<Scalar, NotScalar> {
+        self.value
+    }
+}
+
+impl Serializer for ScalarSerializer {
+    type Ok = Scalar;
+    type Error = NotScalar;
+    type SerializeSeq = Impossible<Scalar, NotScalar>;
+    type SerializeTuple = Impossible<Scalar, NotScalar>;
+    type SerializeTupleStruct = Impossible<Scalar, NotScalar>;
+    type SerializeTupleVariant = Impossible<Scalar, NotScalar>;
+    type SerializeMap = Impossible<Scalar, NotScalar>;
+    type SerializeStruct = InstrumentValue;
+    type SerializeStructVariant = Impossible<Scalar, NotScalar>;
+
+    fn serialize_bool(self, v: bool) -> Result<Scalar, NotScalar> {
+        Ok(Scalar::Unsigned(v as u64))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Scalar, NotScalar> {
+        Ok(Scalar::Signed(v.into()))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Scalar, NotScalar> {
+        Ok(Scalar::Signed(v.into()))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Scalar, NotScalar> {
+        Ok(Scalar::Signed(v.into()))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Scalar, NotScalar> {
+        Ok(Scalar::Signed(v))
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<Scalar, NotScalar> {
+        Ok(i64::try_from(v).map(Scalar::Signed).unwrap_or(Scalar::Float(v as f64)))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Scalar, NotScalar> {
+        Ok(Scalar::Unsigned(v.into()))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Scalar, NotScalar> {
+        Ok(Scalar::Unsigned(v.into()))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Scalar, NotScalar> {
+        Ok(Scalar::Unsigned(v.into()))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Scalar, NotScalar> {
+        Ok(Scalar::Unsigned(v))
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<Scalar, NotScalar> {
+        Ok(u64::try_from(v).map(Scalar::Unsigned).unwrap_or(Scalar::Float(v as f64)))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Scalar, NotScalar> {
+        Ok(Scalar::Float(v.into()))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Scalar, NotScalar> {
+        Ok(Scalar::Float(v))
+    }
+
+    fn serialize_char(self, _v: char) -> Result<Scalar, NotScalar> {
+        Err(NotScalar)
+    }
+
+    fn serialize_str(self, _v: &str) -> Result<Scalar, NotScalar> {
+        Err(NotScalar)
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<Scalar, NotScalar> {
+        Err(NotScalar)
+    }
+
+    fn serialize_none(self) -> Result<Scalar, NotScalar> {
+        Err(NotScalar)
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<Scalar, NotScalar> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Scalar, NotScalar> {
+        Err(NotScalar)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Scalar, NotScalar> {
+        Err(NotScalar)
+    }
+
+    fn serialize_unit_variant(self, _name: &'static str, _variant_index: u32,
+                              _variant: &'static str) -> Result<Scalar, NotScalar> {
+        Err(NotScalar)
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(self, _name: &'static str, value: &T)
+                                                      -> Result<Scalar, NotScalar> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(self, _name: &'static str, _variant_index: u32,
+                                                       _variant: &'static str, _value: &T)
+                                                       -> Result<Scalar, NotScalar> {
+        Err(NotScalar)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, NotScalar> {
+        Err(NotScalar)
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, NotScalar> {
+        Err(NotScalar)
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, _len: usize)
+                              -> Result<Self::SerializeTupleStruct, NotScalar> {
+        Err(NotScalar)
+    }
+
+    fn serialize_tuple_variant(self, _name: &'static str, _variant_index: u32,
+                               _variant: &'static str, _len: usize)
+                               -> Result<Self::SerializeTupleVariant, NotScalar> {
+        Err(NotScalar)
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, NotScalar> {
+        Err(NotScalar)
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize)
+                        -> Result<Self::SerializeStruct, NotScalar> {
+        if self.top {
+            Ok(InstrumentValue { value: Err(NotScalar) })
+        } else {
+            Err(NotScalar)
+        }
+    }
+
+    fn serialize_struct_variant(self, _name: &'static str, _variant_index: u32,
+                                _variant: &'static str, _len: usize)
+                                -> Result<Self::SerializeStructVariant, NotScalar> {
+        Err(NotScalar)
+    }
+}
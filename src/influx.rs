@@ -0,0 +1,232 @@
+// Copyright 2017 All Contributors (see CONTRIBUTORS file)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+//
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+
+//! # InfluxDB line protocol
+//!
+//! _This module is only present if `influx` feature is enabled.
+//! It is disabled by default._
+//!
+//! [`LineProtocol`] renders an instrument board in [InfluxDB line protocol], ready to be
+//! written to an Influx endpoint:
+//!
+//! ```text
+//! requests,unit=requests value=42i 1500000000000000000
+//! service,host=web-1 load=0.5,status=1i 1500000000000000000
+//! ```
+//!
+//! Every instrument becomes a measurement named after it. Numeric (and boolean) values
+//! are written as a `value` field; for struct values, each numeric or boolean field
+//! becomes a field of its own (in the order of their names). Instrument's unit, if any,
+//! and tags added with [`LineProtocol#with_tag`] become tags. If `timestamp_instruments`
+//! feature is enabled, the time of the last update becomes the line's timestamp (in
+//! nanoseconds), otherwise the timestamp is left for the server to assign.
+//!
+//! Instruments with no numeric values (strings, sequences, and so on) are skipped;
+//! [`LineProtocol#write_to`] reports their names.
+//!
+//! [InfluxDB line protocol]: https://docs.influxdata.com/influxdb/v2/reference/syntax/line-protocol/
+//! [`LineProtocol`]: struct.LineProtocol.html
+//! [`LineProtocol#with_tag`]: struct.LineProtocol.html#method.with_tag
+//! [`LineProtocol#write_to`]: struct.LineProtocol.html#method.write_to
+
+use serde_json::Value;
+#[cfg(feature = "timestamp_instruments")]
+use chrono::DateTime;
+
+use std::fmt::{self, Write};
+use std::marker::PhantomData;
+
+use super::{Instruments, Listener};
+
+/// Renders an instrument board in InfluxDB line protocol
+///
+/// This is a shorthand for `LineProtocol::new(instruments).render()`.
+pub fn render_influx_line_protocol<I: Instruments<L>, L: Listener>(instruments: &I) -> String {
+    LineProtocol::new(instruments).render()
+}
+
+/// InfluxDB line protocol rendering of an instrument board
+///
+/// Instruments excluded from bulk reads (see [`Instruments#bulk_instrument_names`]) are skipped.
+///
+/// ```rust
+/// extern crate rapt;
+/// extern crate serde;
+/// #[macro_use]
+/// extern crate rapt_derive;
+///
+/// use rapt::{Instrument, Listener};
+/// use rapt::influx::LineProtocol;
+///
+/// #[derive(Instruments)]
+/// struct AppInstruments<L: Listener> {
+///     requests: Instrument<u64, L>,
+///     status: Instrument<String, L>,
+/// }
+///
+/// fn main() {
+///     let instruments = AppInstruments::<()> {
+///         requests: Instrument::new(42),
+///         status: Instrument::new("started".into()),
+///     };
+///     let lines = LineProtocol::new(&instruments).with_tag("host", "web-1");
+///     let mut output = String::new();
+///     assert_eq!(lines.write_to(&mut output).unwrap(), vec!["status"]);
+///     assert!(output.starts_with("requests,host=web-1 value=42i"));
+/// }
+/// ```
+///
+/// [`Instruments#bulk_instrument_names`]: ../trait.Instruments.html#method.bulk_instrument_names
+pub struct LineProtocol<'a, I: Instruments<L> + 'a, L: Listener> {
+    instruments: &'a I,
+    tags: Vec<(String, String)>,
+    listener: PhantomData<L>,
+}
+
+impl<'a, I: Instruments<L> + 'a, L: Listener> LineProtocol<'a, I, L> {
+    /// Creates line protocol rendering of the instrument board
+    pub fn new(instruments: &'a I) -> Self {
+        LineProtocol { instruments, tags: vec![], listener: PhantomData }
+    }
+
+    /// Adds a tag to every line
+    pub fn with_tag<K: Into<String>, V: Into<String>>(mut self, key: K, value: V) -> Self {
+        self.tags.push((key.into(), value.into()));
+        self
+    }
+
+    /// Writes a line for every instrument with numeric values
+    ///
+    /// Returns names of skipped instruments, so that the caller can warn about them.
+    pub fn write_to<W: Write>(&self, w: &mut W) -> Result<Vec<&'static str>, fmt::Error> {
+        let mut skipped = vec![];
+        for name in self.instruments.bulk_instrument_names() {
+            let reading = match self.instruments.serialize_reading(name, serde_json::value::Serializer) {
+                Ok(reading) => reading,
+                Err(_) => {
+                    skipped.push(name);
+                    continue;
+                },
+            };
+            let fields = fields(&reading["value"]);
+            if fields.is_empty() {
+                skipped.push(name);
+                continue;
+            }
+            escape(w, name, &[',', ' '])?;
+            if let Some(unit) = self.instruments.instrument_unit(name) {
+                write_tag(w, "unit", unit)?;
+            }
+            for (key, value) in &self.tags {
+                write_tag(w, key, value)?;
+            }
+            for (i, (key, value)) in fields.iter().enumerate() {
+                w.write_char(if i == 0 { ' ' } else { ',' })?;
+                escape(w, key, &[',', '=', ' '])?;
+                write!(w, "={}", value)?;
+            }
+            if let Some(timestamp) = timestamp(&reading) {
+                write!(w, " {}", timestamp)?;
+            }
+            w.write_char('\n')?;
+        }
+        Ok(skipped)
+    }
+
+    /// Renders all lines, skipping instruments with no numeric values
+    pub fn render(&self) -> String {
+        let mut output = String::new();
+        // Writing to a string never fails
+        let _ = self.write_to(&mut output);
+        output
+    }
+}
+
+/// Collects fields out of instrument's value
+fn fields(value: &Value) -> Vec<(&str, String)> {
+    match *value {
+        Value::Object(ref map) => map.iter()
+            .filter_map(|(key, value)| field_value(value).map(|value| (key.as_str(), value)))
+            .collect(),
+        ref value => field_value(value).map(|value| vec![("value", value)]).unwrap_or_default(),
+    }
+}
+
+/// Formats a numeric or boolean field value
+fn field_value(value: &Value) -> Option<String> {
+    match *value {
+        Value::Bool(v) => Some(v.to_string()),
+        Value::Number(ref v) => Some(match v.as_i64() {
+            Some(v) => format!("{}i", v),
+            None => format!("{}", v.as_f64()?),
+        }),
+        _ => None,
+    }
+}
+
+/// Returns the time of the last update in nanoseconds, if known
+#[cfg(feature = "timestamp_instruments")]
+fn timestamp(reading: &Value) -> Option<i64> {
+    DateTime::parse_from_rfc3339(reading["last_update_at"].as_str()?).ok()?.timestamp_nanos_opt()
+}
+
+/// Returns the time of the last update in nanoseconds, if known
+#[cfg(not(feature = "timestamp_instruments"))]
+fn timestamp(_reading: &Value) -> Option<i64> {
+    None
+}
+
+fn write_tag<W: Write>(w: &mut W, key: &str, value: &str) -> fmt::Result {
+    w.write_char(',')?;
+    escape(w, key, &[',', '=', ' '])?;
+    w.write_char('=')?;
+    escape(w, value, &[',', '=', ' '])
+}
+
+/// Writes `s`, escaping `special` characters with a backslash
+fn escape<W: Write>(w: &mut W, s: &str, special: &[char]) -> fmt::Result {
+    for c in s.chars() {
+        if special.contains(&c) {
+            w.write_char('\\')?;
+        }
+        w.write_char(c)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,142 @@
+// Copyright 2017 All Contributors (see CONTRIBUTORS file)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+//
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+
+//! # Lazy instruments
+//!
+//! Some values are expensive to compute and are only interesting when someone actually
+//! reads them (for example, current memory usage). Instead of computing and storing them
+//! periodically, [`LazyInstrument`] computes the value on demand, when it is read or serialized.
+//!
+//! Since there is no stored state, lazy instruments can't be updated, listeners are
+//! never notified about their changes and no update timestamp is tracked.
+//!
+//! [`LazyInstrument`]: struct.LazyInstrument.html
+
+use serde::{Serialize, Serializer, Deserializer};
+#[cfg(feature = "timestamp_instruments")]
+use chrono::prelude::*;
+
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use super::{LastUpdated, Listener, Metadata, Restore, Timestamp};
+#[cfg(feature = "timestamp_instruments")]
+use super::UpdatedSince;
+
+/// An instrument computing its value on demand
+///
+/// Serializes the same way as [`Instrument`] does, except for the `last_update_at` field
+/// which is never present.
+///
+/// ```rust
+/// extern crate rapt;
+///
+/// use rapt::LazyInstrument;
+///
+/// fn main() {
+///     let answer = LazyInstrument::<_, _, ()>::new(|| 6 * 7);
+///     assert_eq!(answer.read(), 42);
+/// }
+/// ```
+///
+/// [`Instrument`]: ../struct.Instrument.html
+pub struct LazyInstrument<T: Serialize, F: Fn() -> T, L: Listener> {
+    function: Arc<F>,
+    metadata: Metadata,
+    listener: PhantomData<L>,
+}
+
+impl<T: Serialize, F: Fn() -> T, L: Listener> Clone for LazyInstrument<T, F, L> {
+    fn clone(&self) -> Self {
+        LazyInstrument {
+            function: self.function.clone(),
+            metadata: self.metadata,
+            listener: PhantomData,
+        }
+    }
+}
+
+impl<T: Serialize, F: Fn() -> T, L: Listener> LazyInstrument<T, F, L> {
+    /// Creates a new instrument computing its value with `function`
+    pub fn new(function: F) -> Self {
+        LazyInstrument {
+            function: Arc::new(function),
+            metadata: Metadata::default(),
+            listener: PhantomData,
+        }
+    }
+
+    metadata_accessors!(metadata);
+    on_demand_wiring!(L);
+
+    /// Computes current value
+    pub fn read(&self) -> T {
+        (self.function)()
+    }
+}
+
+impl<T: Serialize, F: Fn() -> T, L: Listener> Serialize for LazyInstrument<T, F, L> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where
+        S: Serializer {
+        self.metadata.serialize_computed(serializer, self.read())
+    }
+}
+
+/// Always `true`, as the value is computed on demand
+#[cfg(feature = "timestamp_instruments")]
+impl<T: Serialize, F: Fn() -> T, L: Listener> UpdatedSince<DateTime<Utc>> for LazyInstrument<T, F, L> {
+    fn updated_since(&self, _since: &DateTime<Utc>) -> bool {
+        true
+    }
+}
+
+/// Ignores the reading, as the value is computed on demand
+impl<T: Serialize, F: Fn() -> T, L: Listener> Restore for LazyInstrument<T, F, L> {
+    fn restore<'de, D: Deserializer<'de>>(&self, _reading: D) -> Result<(), D::Error> {
+        Ok(())
+    }
+}
+
+/// Never present, as the value is computed on demand
+impl<T: Serialize, F: Fn() -> T, L: Listener> LastUpdated for LazyInstrument<T, F, L> {
+    fn last_update_at(&self) -> Option<Timestamp> {
+        None
+    }
+}
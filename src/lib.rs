@@ -81,6 +81,50 @@
 //!
 //! It is parametrized over [`Listener`].
 //!
+//! Individual instruments can be annotated with `#[rapt(...)]` attribute that accepts
+//! following options:
+//!
+//! * `name = "..."` overrides instrument name (field name is used by default), names must
+//!   be unique within the board
+//! * `unit = "..."` specifies the unit of the value (such as `bytes`), for exporters
+//! * `description = "..."` provides a human-readable description of the instrument, for exporters
+//! * `version = N` adds `schema_version` field to instrument readings, so that consumers can
+//!   tell which version of the value type they are reading
+//! * `transform = "path::to::fn"` serializes the value transformed by a function taking
+//!   a reference to it (such as converting bytes to megabytes), while the stored value is
+//!   left intact (see [`Instrument#serialize_mapped`])
+//! * `initial = false` prevents the listener from being notified when it is wired, so
+//!   that the instrument stays silent until it is actually updated
+//! * `no_bulk` excludes the instrument from exports of the entire board (it can still be
+//!   read by name and is wired to the listener as usual), which is useful for instruments
+//!   that are expensive to serialize or sensitive
+//!
+//! The board itself can be annotated with `#[rapt(accessors)]` to generate a method
+//! returning a handle (a clone) of every instrument, named after its field. This gives
+//! access to instruments even if fields are private.
+//!
+//! Besides implementing [`Instruments`], the derivation also generates a `reset_all` method
+//! that resets every instrument on the board to its default value (available when all
+//! instruments implement [`Reset`]). Every instrument notifies the listener individually, just
+//! like a regular update would.
+//!
+//! It also generates a `wire_<field>_listener` method for every instrument, which wires the
+//! listener into that instrument only. This allows routing different instruments to different
+//! sinks. Since the board is parametrized over a single listener type, such listener would
+//! typically be an enum dispatching to the actual sinks. `#[derive(Listener)]` implements
+//! [`Listener`] for such enum, as long as its variants carry a single listener or no data.
+//!
+//! Besides [`Instrument`], boards can contain [`AtomicView`] instruments that expose
+//! existing atomics (read-only), [`LazyInstrument`] instruments that compute their values
+//! on demand, [`ComputedInstrument`] that derives its value from other instruments,
+//! [`SampledInstrument`] that only applies one in every N updates, [`RateInstrument`] that
+//! reports how often events occur, and [`ErrorLogInstrument`] that keeps a bounded log of recent errors.
+//!
+//! Enums can't be instrument boards, but an enum representing a state is a natural instrument
+//! value. `#[derive(InstrumentState)]` makes it serialize as the name of its active variant
+//! (overridable with `#[rapt(name = "...")]` on the variant), so that `Instrument<State, L>`
+//! reports the current state.
+//!
 //! ## Listener
 //!
 //! [`Listener`] is a trait that allows instruments to notify interested parties about updates
@@ -98,7 +142,6 @@
 //! #[macro_use]
 //! extern crate assert_matches;
 //!
-//! use serde::Serialize;
 //! use rapt::{Listener, Instrument};
 //!
 //! #[derive(Debug, Clone, Copy, Serialize)]
@@ -134,54 +177,167 @@
 //!
 //! [`Instrument`]: struct.Instrument.html
 //! [`Instrument#update`]: struct.Instrument.html#method.update
+//! [`Instrument#serialize_mapped`]: struct.Instrument.html#method.serialize_mapped
 //! [`Instruments`]: struct.Instruments.html
 //! [`Listener`]: trait.Listener.html
+//! [`Reset`]: trait.Reset.html
+//! [`AtomicView`]: atomic/struct.AtomicView.html
+//! [`LazyInstrument`]: lazy/struct.LazyInstrument.html
+//! [`ErrorLogInstrument`]: error_log/struct.ErrorLogInstrument.html
+//! [`ComputedInstrument`]: computed/struct.ComputedInstrument.html
+//! [`SampledInstrument`]: sampled/struct.SampledInstrument.html
+//! [`RateInstrument`]: rate/struct.RateInstrument.html
 
 extern crate serde;
 
-use serde::{Serialize, Serializer};
+use serde::{Serialize, Serializer, Deserialize, Deserializer};
 use serde::ser::SerializeStruct;
+use serde::de::{self, DeserializeOwned};
 
-use std::sync::{Arc, RwLock, RwLockReadGuard, LockResult};
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard, LockResult, TryLockError};
+use std::thread;
+use std::time::{Duration, Instant};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::convert::Infallible;
 
 #[cfg(feature = "timestamp_instruments")]
 extern crate chrono;
 #[cfg(feature = "timestamp_instruments")]
 use chrono::prelude::*;
 
+#[macro_use]
+mod metadata;
+use metadata::Metadata;
+
+/// Returns current time, or the Unix epoch if the system clock can't be read
+///
+/// `Utc::now` panics if the clock is set before the epoch. A timestamp is not worth
+/// failing instrument creation or updates over, so the epoch serves as a sentinel.
+#[cfg(feature = "timestamp_instruments")]
+pub(crate) fn now() -> DateTime<Utc> {
+    use std::time::{SystemTime, UNIX_EPOCH};
+    #[cfg(feature = "test_util")]
+    {
+        if let Some(now) = test_util::now() {
+            return now;
+        }
+    }
+    SystemTime::now().duration_since(UNIX_EPOCH).ok()
+        .and_then(|since| Utc.timestamp_opt(since.as_secs() as i64, since.subsec_nanos()).single())
+        .unwrap_or_else(|| Utc.timestamp_opt(0, 0).unwrap())
+}
+
 /// A thread-safe wrapper for a Serde-serializable value
 ///
 /// It is parametrized over [`Listener`]
 ///
 /// Instruments are cloneable and the wrapped value can be safely updated using [`Instrument#update`].
 ///
+/// Please note that cloning an instrument produces another *handle* to the same value:
+/// updates made through any of the clones are seen by all of them. To get an independent
+/// copy of the instrument, use [`Instrument#deep_clone`].
+///
+/// If `timestamp_instruments` feature is enabled, the time of the last update is serialized
+/// as `last_update_at`. Should the system clock be unreadable (set before the Unix epoch),
+/// the epoch is recorded instead, rather than failing the update.
+///
 /// [`Listener`]: trait.Listener.html
-#[derive(Clone)]
+/// [`Instrument#update`]: struct.Instrument.html#method.update
+/// [`Instrument#deep_clone`]: struct.Instrument.html#method.deep_clone
 pub struct Instrument<T: Serialize, L: Listener> {
     data: Arc<RwLock<T>>,
-    name: Option<&'static str>,
+    metadata: Metadata,
     listener: Option<L>,
+    ever_updated: Arc<AtomicBool>,
+    throttle: Option<throttle::Throttle>,
+    report_poisoned: bool,
+    on_change: Option<ChangeCallback<T>>,
+    threshold: Option<threshold::InstrumentThreshold<T>>,
     #[cfg(feature = "timestamp_instruments")]
     timestamp: Arc<RwLock<DateTime<Utc>>>,
+    #[cfg(feature = "lock_contention")]
+    contention: Arc<contention::Counters>,
 }
 
+/// Clones share the value, so it doesn't have to be `Clone` itself
+impl<T: Serialize, L: Listener> Clone for Instrument<T, L> {
+    fn clone(&self) -> Self {
+        Instrument {
+            data: self.data.clone(),
+            metadata: self.metadata,
+            listener: self.listener.clone(),
+            ever_updated: self.ever_updated.clone(),
+            throttle: self.throttle.clone(),
+            report_poisoned: self.report_poisoned,
+            on_change: self.on_change.clone(),
+            threshold: self.threshold.clone(),
+            #[cfg(feature = "timestamp_instruments")]
+            timestamp: self.timestamp.clone(),
+            #[cfg(feature = "lock_contention")]
+            contention: self.contention.clone(),
+        }
+    }
+}
+
+/// Callback set with [`Instrument#on_change`]
+///
+/// [`Instrument#on_change`]: struct.Instrument.html#method.on_change
+type ChangeCallback<T> = Arc<dyn Fn(&T) + Send + Sync>;
+
 /// An error that might occur during [`Instrument#update`]
 ///
+/// `E` is the error of the closure passed to [`Instrument#try_update`]. Other updates
+/// can't fail that way, so it defaults to `Infallible`.
+///
 /// [`Instrument#update`]: struct.Instrument.html#method.update
+/// [`Instrument#try_update`]: struct.Instrument.html#method.try_update
 #[derive(Debug)]
-pub enum UpdateError {
+pub enum UpdateError<E = Infallible> {
     PoisonedData,
     PoisonedTimestamp,
+    /// The closure has rejected the update
+    Rejected(E),
+}
+
+/// An error that might occur during [`Instrument#read_timeout`]
+///
+/// [`Instrument#read_timeout`]: struct.Instrument.html#method.read_timeout
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReadTimeoutError {
+    /// The instrument has been poisoned
+    Poisoned,
+    /// The value has stayed locked for writing for the entire timeout
+    TimedOut,
+}
+
+impl UpdateError {
+    /// Converts an error that can't carry a closure error into one of any closure error type
+    fn widen<E>(self) -> UpdateError<E> {
+        match self {
+            UpdateError::PoisonedData => UpdateError::PoisonedData,
+            UpdateError::PoisonedTimestamp => UpdateError::PoisonedTimestamp,
+            UpdateError::Rejected(never) => match never {},
+        }
+    }
 }
 
 impl<T: Serialize + Default, L: Listener> Default for Instrument<T, L> {
     fn default() -> Self {
         Instrument {
             data: Default::default(),
-            name: None,
+            metadata: Metadata::default(),
             listener: None,
+            ever_updated: Arc::new(AtomicBool::new(false)),
+            throttle: None,
+            report_poisoned: false,
+            on_change: None,
+            threshold: None,
             #[cfg(feature = "timestamp_instruments")]
-            timestamp: Arc::new(RwLock::new(Utc::now())),
+            timestamp: Arc::new(RwLock::new(now())),
+            #[cfg(feature = "lock_contention")]
+            contention: Default::default(),
         }
     }
 }
@@ -191,71 +347,930 @@ impl<T: Serialize, L: Listener> Instrument<T, L> {
     pub fn new(data: T) -> Self {
         Instrument {
             data: Arc::new(RwLock::new(data)),
-            name: None,
+            metadata: Metadata::default(),
             listener: None,
+            ever_updated: Arc::new(AtomicBool::new(false)),
+            throttle: None,
+            report_poisoned: false,
+            on_change: None,
+            threshold: None,
             #[cfg(feature = "timestamp_instruments")]
-            timestamp: Arc::new(RwLock::new(Utc::now())),
+            timestamp: Arc::new(RwLock::new(now())),
+            #[cfg(feature = "lock_contention")]
+            contention: Default::default(),
         }
     }
 
-    fn serialization_field_count() -> usize {
-        #[allow(unused_mut)]
+    /// Limits how often the listener is notified about updates
+    ///
+    /// Updates arriving less than `interval` after the last notification still change
+    /// the value and bump the timestamp, but their notifications are held back and
+    /// coalesced into one. It is sent with the first update once the interval has passed,
+    /// or by [`Instrument#notify_pending`], which should be called periodically so that
+    /// the listener learns about the last update of a burst. This way, the listener is
+    /// notified at most once per interval. This reduces traffic for all listeners of
+    /// instruments that can be updated in a tight loop.
+    ///
+    /// [`Instrument#notify_pending`]: struct.Instrument.html#method.notify_pending
+    pub fn with_min_notify_interval(mut self, interval: Duration) -> Self {
+        self.throttle = Some(throttle::Throttle::new(interval));
+        self
+    }
+
+    /// Sets a callback invoked with the new value on every update
+    ///
+    /// Unlike the listener, which is wired into all instruments of a board, the callback
+    /// is specific to this instrument (and its clones), which allows reacting to changes of
+    /// a particular value. Both are invoked by the updating thread: first the callback, then
+    /// the listener. The callback is invoked even if the listener is not notified (because
+    /// of [`Instrument#with_min_notify_interval`], or if there's none).
+    ///
+    /// The value is still locked for writing while the callback runs, so it must not access
+    /// the instrument itself. Copies made with [`Instrument#deep_clone`] have no callback.
+    ///
+    /// ```rust
+    /// extern crate rapt;
+    ///
+    /// use rapt::Instrument;
+    /// use std::sync::mpsc;
+    ///
+    /// fn main() {
+    ///     let (tx, rx) = mpsc::channel();
+    ///     let tx = std::sync::Mutex::new(tx);
+    ///     let config = Instrument::<String, ()>::new("v1".into())
+    ///         .on_change(move |v: &String| tx.lock().unwrap().send(v.clone()).unwrap());
+    ///     config.update(|v| *v = "v2".into()).unwrap();
+    ///     assert_eq!(rx.recv().unwrap(), "v2");
+    /// }
+    /// ```
+    ///
+    /// [`Instrument#with_min_notify_interval`]: struct.Instrument.html#method.with_min_notify_interval
+    /// [`Instrument#deep_clone`]: struct.Instrument.html#method.deep_clone
+    pub fn on_change<F>(mut self, on_change: F) -> Self where F: Fn(&T) + Send + Sync + 'static {
+        self.on_change = Some(Arc::new(on_change));
+        self
+    }
+
+    /// Notifies the listener only about changes exceeding `threshold`
+    ///
+    /// Every update is compared against the last significant value (initially, the current
+    /// one). Updates that don't move the value beyond the threshold still change it and
+    /// invoke the [`Instrument#on_change`] callback, but don't notify the listener. If
+    /// `bump_timestamp` is `false`, they don't bump the timestamp either, so that the
+    /// timestamp tells when the value last changed significantly.
+    ///
+    /// This is only available for numeric values (see [`Numeric`]). Threshold is shared
+    /// by all clones of the instrument, while [`Instrument#deep_clone`] starts it over.
+    ///
+    /// ```rust
+    /// extern crate rapt;
+    ///
+    /// use rapt::{Instrument, Threshold};
+    /// use std::sync::mpsc;
+    ///
+    /// fn main() {
+    ///     let (tx, rx) = mpsc::channel();
+    ///     let mut load = Instrument::new(1.0).with_threshold(Threshold::Relative(0.05), true);
+    ///     load.set_name_and_listener("load", tx);
+    ///     load.update(|v| *v = 1.02).unwrap();
+    ///     load.update(|v| *v = 1.1).unwrap();
+    ///     load.update(|v| *v = 1.0).unwrap();
+    ///     assert_eq!(rx.try_iter().count(), 3);
+    /// }
+    /// ```
+    ///
+    /// [`Instrument#on_change`]: struct.Instrument.html#method.on_change
+    /// [`Instrument#deep_clone`]: struct.Instrument.html#method.deep_clone
+    /// [`Numeric`]: threshold/trait.Numeric.html
+    pub fn with_threshold(mut self, threshold: Threshold, bump_timestamp: bool) -> Self where T: threshold::Numeric {
+        self.threshold = {
+            let data = self.data.read().unwrap_or_else(|poisoned| poisoned.into_inner());
+            Some(threshold::InstrumentThreshold::new(threshold, bump_timestamp, &*data))
+        };
+        self
+    }
+
+    /// Reports a poisoned value explicitly
+    ///
+    /// If a thread has panicked while updating the instrument, the last-known value is still
+    /// serialized, but it might have been left half-updated and it won't change anymore. With
+    /// this option, such instrument is serialized with an additional `"error": "poisoned"` field.
+    pub fn with_poison_reporting(mut self) -> Self {
+        self.report_poisoned = true;
+        self
+    }
+
+    fn serialization_field_count(&self) -> usize {
         let mut c = 1;
+        if self.metadata.schema_version.is_some() {
+            c += 1;
+        }
         if cfg!(feature = "timestamp_instruments") {
             c += 1;
         }
         c
     }
 
-    /// Sets the name of the instrument. FOR INTERNAL USE ONLY.
-    pub fn set_name(&mut self, name: &'static str) {
-        self.name = Some(name)
-    }
+    metadata_accessors!(metadata);
 
     /// Sets the name of the instrument and the listener. FOR INTERNAL USE ONLY.
+    ///
+    /// The listener is notified immediately so that it can pick up the initial value.
     pub fn set_name_and_listener(&mut self, name: &'static str, listener: L) {
-        self.name = Some(name);
+        self.metadata.name = Some(name);
         listener.instrument_updated(name);
         self.listener = Some(listener);
     }
 
+    /// Sets the listener without notifying it. FOR INTERNAL USE ONLY.
+    pub fn set_listener(&mut self, listener: L) {
+        self.listener = Some(listener);
+    }
+
+    /// Replaces the listener, returning the previous one (if any)
+    ///
+    /// Unlike wiring (see [`Instruments#wire_listener`]), this doesn't notify the new
+    /// listener: it is meant for swapping listeners of live instruments, for example to
+    /// drain or shut down the previous one. The listener belongs to this handle only,
+    /// clones of the instrument made earlier keep notifying the previous listener.
+    ///
+    /// [`Instruments#wire_listener`]: trait.Instruments.html#tymethod.wire_listener
+    pub fn replace_listener(&mut self, listener: L) -> Option<L> {
+        self.listener.replace(listener)
+    }
+
+    /// Creates an independent copy of the instrument
+    ///
+    /// Unlike `clone`, which creates another handle to the same value, this clones the
+    /// value itself, so that the copy is detached from the original instrument. The copy
+    /// keeps the name, the unit, the description, the schema version, the minimum notification
+    /// interval, the threshold and poison reporting, so that it serializes the same way. It
+    /// gets a fresh timestamp and has no listener.
+    ///
+    /// Whether the instrument [has been updated] is carried over as well.
+    ///
+    /// The value is cloned even if the instrument has been poisoned.
+    ///
+    /// [has been updated]: struct.Instrument.html#method.has_been_updated
+    pub fn deep_clone(&self) -> Self where T: Clone {
+        let data = self.snapshot();
+        Instrument {
+            metadata: self.metadata,
+            ever_updated: Arc::new(AtomicBool::new(self.has_been_updated())),
+            throttle: self.throttle.as_ref().map(throttle::Throttle::restart),
+            report_poisoned: self.report_poisoned,
+            threshold: self.threshold.as_ref().map(|threshold| threshold.restart(&data)),
+            ..Instrument::new(data)
+        }
+    }
+
+    /// Returns `true` if the value has ever been updated
+    ///
+    /// Instruments start with a value that is often meaningless until the first actual
+    /// update (think of a `Default` value). Wiring a listener notifies it without updating
+    /// the instrument, so it doesn't count as an update.
+    pub fn has_been_updated(&self) -> bool {
+        self.ever_updated.load(Ordering::Relaxed)
+    }
+
+    /// Returns `true` if a thread has panicked while updating the instrument
+    ///
+    /// Poisoned instruments fail to update, their readings carry the last-known value
+    /// (see [`Instrument#read_poisoned`]).
+    ///
+    /// [`Instrument#read_poisoned`]: struct.Instrument.html#method.read_poisoned
+    pub fn is_poisoned(&self) -> bool {
+        #[cfg(feature = "timestamp_instruments")]
+        {
+            if self.timestamp.is_poisoned() {
+                return true;
+            }
+        }
+        self.data.is_poisoned()
+    }
+
     /// Thread-safe value reader
-    pub fn read(&self) -> LockResult<RwLockReadGuard<T>> {
-        self.data.read()
+    pub fn read(&self) -> LockResult<RwLockReadGuard<'_, T>> {
+        self.read_data()
+    }
+
+    /// Same as [`Instrument#read`], but reads the value even if the instrument has been poisoned
+    ///
+    /// A panicking update leaves the value as it was at the moment of the panic, which is
+    /// often when it is most interesting. This is what serialization falls back to.
+    ///
+    /// ```rust
+    /// extern crate rapt;
+    ///
+    /// use std::thread;
+    /// use rapt::Instrument;
+    ///
+    /// fn main() {
+    ///     let progress = Instrument::<u8, ()>::new(0);
+    ///     let updater = progress.clone();
+    ///     let _ = thread::spawn(move || updater.update(|v| { *v = 50; panic!("stalled") })).join();
+    ///     assert!(progress.read().is_err());
+    ///     assert_eq!(*progress.read_poisoned(), 50);
+    /// }
+    /// ```
+    ///
+    /// [`Instrument#read`]: struct.Instrument.html#method.read
+    pub fn read_poisoned(&self) -> RwLockReadGuard<'_, T> {
+        self.read_data().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Returns a copy of the value
+    ///
+    /// The value is only locked for reading while it is being cloned, which is both shorter
+    /// and less verbose than holding a guard returned by [`Instrument#read`]. Unlike `read`,
+    /// this recovers the value even if the instrument has been poisoned, returning whatever
+    /// the value was left at.
+    ///
+    /// ```rust
+    /// extern crate rapt;
+    ///
+    /// use rapt::Instrument;
+    ///
+    /// fn main() {
+    ///     let peers = Instrument::<Vec<&str>, ()>::new(vec!["a", "b"]);
+    ///     let snapshot = peers.snapshot();
+    ///     peers.update(|v| v.clear()).unwrap();
+    ///     assert_eq!(snapshot, vec!["a", "b"]);
+    /// }
+    /// ```
+    ///
+    /// [`Instrument#read`]: struct.Instrument.html#method.read
+    pub fn snapshot(&self) -> T where T: Clone {
+        self.read_poisoned().clone()
+    }
+
+    /// Non-blocking value reader
+    ///
+    /// Returns `None` if the value is locked for writing at the moment, or if the instrument
+    /// has been poisoned (see [`Instrument#is_poisoned`]).
+    ///
+    /// [`Instrument#is_poisoned`]: struct.Instrument.html#method.is_poisoned
+    pub fn try_read(&self) -> Option<RwLockReadGuard<'_, T>> {
+        self.data.try_read().ok()
+    }
+
+    /// Value reader waiting for no longer than `timeout`
+    ///
+    /// `RwLock` can't wait with a timeout, so this retries acquiring the lock, backing off
+    /// for increasingly long periods (up to a millisecond), until `timeout` has passed.
+    ///
+    /// ```rust
+    /// extern crate rapt;
+    ///
+    /// use std::time::Duration;
+    /// use rapt::{Instrument, ReadTimeoutError};
+    ///
+    /// fn main() {
+    ///     let status = Instrument::<u8, ()>::new(0);
+    ///     status.update(|_| {
+    ///         let res = status.read_timeout(Duration::from_millis(10));
+    ///         assert!(matches!(res, Err(ReadTimeoutError::TimedOut)));
+    ///     }).unwrap();
+    ///     assert_eq!(*status.read_timeout(Duration::from_millis(10)).unwrap(), 0);
+    /// }
+    /// ```
+    pub fn read_timeout(&self, timeout: Duration) -> Result<RwLockReadGuard<'_, T>, ReadTimeoutError> {
+        let deadline = Instant::now() + timeout;
+        let mut backoff = Duration::from_micros(10);
+        loop {
+            match self.data.try_read() {
+                Ok(data) => return Ok(data),
+                Err(TryLockError::Poisoned(_)) => return Err(ReadTimeoutError::Poisoned),
+                Err(TryLockError::WouldBlock) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return Err(ReadTimeoutError::TimedOut);
+                    }
+                    thread::sleep(backoff.min(deadline - now));
+                    backoff = (backoff * 2).min(Duration::from_millis(1));
+                },
+            }
+        }
     }
 
     /// Thread-safe value writer
-    pub fn update<F>(&self, f: F) -> Result<(), UpdateError> where F: Fn(&mut T) -> () {
-        match self.data.write() {
-            Ok(mut data) => {
-                f(&mut *data);
+    ///
+    /// The closure's result is returned once the update is complete. This allows capturing
+    /// something computed while the value is still locked for writing, such as the new value
+    /// of a counter or the previous value. The listener is notified after the closure returns.
+    ///
+    /// ```rust
+    /// extern crate rapt;
+    ///
+    /// use rapt::Instrument;
+    ///
+    /// fn main() {
+    ///     let requests = Instrument::<u64, ()>::new(41);
+    ///     let count = requests.update(|v| { *v += 1; *v }).unwrap();
+    ///     assert_eq!(count, 42);
+    /// }
+    /// ```
+    pub fn update<F, R>(&self, f: F) -> Result<R, UpdateError> where F: FnOnce(&mut T) -> R {
+        self.update_with(f).map(|(result, _)| result)
+    }
+
+    /// Same as [`Instrument#update`], but also tells whether a listener has been notified
+    ///
+    /// Returns `Ok(true)` if a listener has been wired and notified of the update.
+    ///
+    /// [`Instrument#update`]: struct.Instrument.html#method.update
+    pub fn update_notified<F>(&self, f: F) -> Result<bool, UpdateError> where F: FnOnce(&mut T) {
+        self.update_with(f).map(|(_, notified)| notified)
+    }
+
+    /// Sends the notification held back by the minimum notification interval, if the
+    /// interval has passed since the last one, returns `true` if the listener has been notified
+    ///
+    /// Without further updates, the last update of a burst is only announced when this is
+    /// called, so instruments with a minimum notification interval should be polled
+    /// periodically (say, every interval). Does nothing for other instruments.
+    ///
+    /// ```rust
+    /// extern crate rapt;
+    ///
+    /// use std::sync::mpsc;
+    /// use std::thread;
+    /// use std::time::Duration;
+    /// use rapt::Instrument;
+    ///
+    /// fn main() {
+    ///     let (tx, rx) = mpsc::channel();
+    ///     let mut i = Instrument::new(0).with_min_notify_interval(Duration::from_millis(10));
+    ///     i.set_name("value");
+    ///     i.set_listener(tx);
+    ///     i.update(|v| *v = 1).unwrap();
+    ///     i.update(|v| *v = 2).unwrap();
+    ///     assert_eq!(rx.try_iter().count(), 1);
+    ///     thread::sleep(Duration::from_millis(10));
+    ///     assert!(i.notify_pending());
+    ///     assert!(!i.notify_pending());
+    ///     assert_eq!(rx.try_iter().count(), 1);
+    /// }
+    /// ```
+    pub fn notify_pending(&self) -> bool {
+        match (&self.listener, self.metadata.name, &self.throttle) {
+            (Some(l), Some(n), Some(throttle)) if throttle.take_pending() => {
+                l.instrument_updated(n);
+                true
+            },
+            _ => false,
+        }
+    }
+
+    /// Same as [`Instrument#update`], but only completes the update if `f` has changed the value
+    ///
+    /// Requires `T: Clone + PartialEq`: the value is cloned before `f` runs and compared with
+    /// the result afterwards. If they are equal, the timestamp is not bumped, the change callback
+    /// is not invoked, the listener is not notified and the instrument is not considered updated.
+    /// This saves publishers from processing readings that haven't changed.
+    ///
+    /// Returns `Ok(true)` if the value has changed.
+    ///
+    /// ```rust
+    /// extern crate rapt;
+    ///
+    /// use std::sync::mpsc;
+    /// use rapt::Instrument;
+    ///
+    /// fn main() {
+    ///     let (tx, rx) = mpsc::channel();
+    ///     let mut state = Instrument::new("stopped");
+    ///     state.set_name("state");
+    ///     state.set_listener(tx);
+    ///     assert!(state.update_if_changed(|v| *v = "started").unwrap());
+    ///     assert!(!state.update_if_changed(|v| *v = "started").unwrap());
+    ///     assert_eq!(rx.try_iter().count(), 1);
+    /// }
+    /// ```
+    ///
+    /// [`Instrument#update`]: struct.Instrument.html#method.update
+    pub fn update_if_changed<F>(&self, f: F) -> Result<bool, UpdateError>
+        where F: FnOnce(&mut T), T: Clone + PartialEq {
+        let significant = {
+            #[cfg(feature = "lock_order")]
+            let _held = lock_order::hold(self.lock_id(), self.metadata.name);
+            match self.write_data() {
+                Ok(mut data) => {
+                    let old = data.clone();
+                    f(&mut *data);
+                    if *data == old {
+                        return Ok(false);
+                    }
+                    self.updated(&data)?
+                },
+                Err(_) => return Err(UpdateError::PoisonedData),
+            }
+        };
+        self.notify(significant);
+        Ok(true)
+    }
+
+    /// Runs `f` over the value locked for writing and completes the update, returns the
+    /// result of `f` and whether the listener has been notified
+    fn update_with<F, R>(&self, f: F) -> Result<(R, bool), UpdateError> where F: FnOnce(&mut T) -> R {
+        let (result, significant) = {
+            #[cfg(feature = "lock_order")]
+            let _held = lock_order::hold(self.lock_id(), self.metadata.name);
+            match self.write_data() {
+                Ok(mut data) => {
+                    let result = f(&mut *data);
+                    (result, self.updated(&data)?)
+                },
+                Err(_) => return Err(UpdateError::PoisonedData),
+            }
+        };
+        Ok((result, self.notify(significant)))
+    }
+
+    /// Fallible value writer
+    ///
+    /// If the closure fails, its error is returned as [`UpdateError::Rejected`] and the
+    /// update is considered not to have happened: the timestamp is not bumped and the
+    /// listener is not notified. Note that changes made by the closure before failing are
+    /// *not* rolled back (they can't be), it's up to the closure to leave the value consistent.
+    ///
+    /// ```rust
+    /// extern crate rapt;
+    ///
+    /// use rapt::{Instrument, UpdateError};
+    ///
+    /// fn main() {
+    ///     let port = Instrument::<u16, ()>::new(80);
+    ///     assert!(port.try_update(|v| "8080".parse().map(|p| *v = p)).is_ok());
+    ///     let res = port.try_update(|v| "http".parse().map(|p| *v = p));
+    ///     assert!(matches!(res, Err(UpdateError::Rejected(_))));
+    ///     assert_eq!(*port.read().unwrap(), 8080);
+    /// }
+    /// ```
+    ///
+    /// [`UpdateError::Rejected`]: enum.UpdateError.html#variant.Rejected
+    pub fn try_update<F, E>(&self, f: F) -> Result<(), UpdateError<E>> where F: FnOnce(&mut T) -> Result<(), E> {
+        let significant = {
+            #[cfg(feature = "lock_order")]
+            let _held = lock_order::hold(self.lock_id(), self.metadata.name);
+            match self.write_data() {
+                Ok(mut data) => {
+                    f(&mut *data).map_err(UpdateError::Rejected)?;
+                    self.updated(&data).map_err(UpdateError::widen)?
+                },
+                Err(_) => return Err(UpdateError::PoisonedData),
+            }
+        };
+        self.notify(significant);
+        Ok(())
+    }
+
+    /// Replaces the value with `new` only if it is currently equal to `expected`
+    ///
+    /// Comparison and replacement happen atomically, under the write lock. Returns `true`
+    /// if the value has been replaced, which is a regular update: the timestamp is bumped
+    /// and the listener (if any) is notified. Otherwise, nothing happens.
+    ///
+    /// ```rust
+    /// extern crate rapt;
+    ///
+    /// use rapt::Instrument;
+    ///
+    /// fn main() {
+    ///     let state = Instrument::<&str, ()>::new("stopped");
+    ///     assert!(state.compare_and_set(&"stopped", "started").unwrap());
+    ///     assert!(!state.compare_and_set(&"stopped", "started").unwrap());
+    ///     assert_eq!(*state.read().unwrap(), "started");
+    /// }
+    /// ```
+    pub fn compare_and_set(&self, expected: &T, new: T) -> Result<bool, UpdateError> where T: PartialEq {
+        let significant = {
+            #[cfg(feature = "lock_order")]
+            let _held = lock_order::hold(self.lock_id(), self.metadata.name);
+            match self.write_data() {
+                Ok(mut data) => {
+                    if *data != *expected {
+                        return Ok(false);
+                    }
+                    *data = new;
+                    self.updated(&data)?
+                },
+                Err(_) => return Err(UpdateError::PoisonedData),
+            }
+        };
+        self.notify(significant);
+        Ok(true)
+    }
+
+    /// Bumps the timestamp and invokes the change callback once the value has been updated,
+    /// returns `true` if the update is significant (see [`Instrument#with_threshold`])
+    ///
+    /// Should be called while the data is still locked for writing.
+    ///
+    /// [`Instrument#with_threshold`]: struct.Instrument.html#method.with_threshold
+    fn updated(&self, data: &T) -> Result<bool, UpdateError> {
+        self.ever_updated.store(true, Ordering::Relaxed);
+        let significant = self.threshold.as_ref().map_or(true, |threshold| threshold.is_significant(data));
+        #[cfg(feature = "timestamp_instruments")]
+        {
+            if significant || self.threshold.as_ref().map_or(true, |threshold| threshold.bumps_timestamp()) {
                 match self.timestamp.write() {
-                    Ok(mut timestamp) => {
-                        *timestamp = Utc::now();
-                        match (&self.listener, &self.name) {
-                            (&Some(ref l), &Some(ref n)) => l.instrument_updated(n),
-                            _ => (),
-                        }
-                        Ok(())
-                    },
-                    Err(_) => Err(UpdateError::PoisonedData),
+                    Ok(mut timestamp) => *timestamp = now(),
+                    Err(_) => return Err(UpdateError::PoisonedTimestamp),
                 }
-            },
-            Err(_) => Err(UpdateError::PoisonedData),
+            }
         }
+        if let Some(ref on_change) = self.on_change {
+            on_change(data);
+        }
+        Ok(significant)
+    }
+
+    /// Notifies the listener about a significant update (unless throttled), returns `true`
+    /// if the listener has been notified
+    ///
+    /// Must be called once the data is no longer locked: listeners may block until
+    /// the value is read (such as MQTT publisher's [`Backpressure::Block`]).
+    ///
+    /// [`Backpressure::Block`]: mqtt/enum.Backpressure.html#variant.Block
+    fn notify(&self, significant: bool) -> bool {
+        if !significant {
+            return false;
+        }
+        if let (Some(l), Some(n)) = (&self.listener, self.metadata.name) {
+            if self.throttle.as_ref().map_or(false, throttle::Throttle::throttled) {
+                return false;
+            }
+            l.instrument_updated(n);
+            return true;
+        }
+        false
+    }
+
+    /// Resets the value to its default
+    ///
+    /// This is an [`Instrument#update`] that replaces the value with `T::default()`,
+    /// so the timestamp is bumped and the listener (if any) is notified.
+    ///
+    /// [`Instrument#update`]: struct.Instrument.html#method.update
+    pub fn reset(&self) -> Result<(), UpdateError> where T: Default {
+        self.update(|v| *v = Default::default())
+    }
+
+    /// Sets the value for the duration of a scope
+    ///
+    /// The value is set to `enter` immediately and to `exit` once the returned guard
+    /// is dropped. Both are regular updates: the timestamp is bumped and the listener
+    /// (if any) is notified.
+    ///
+    /// ```rust
+    /// extern crate rapt;
+    ///
+    /// use rapt::Instrument;
+    ///
+    /// fn main() {
+    ///     let running = Instrument::<bool, ()>::new(false);
+    ///     {
+    ///         let _guard = running.scoped(true, false).unwrap();
+    ///         assert!(*running.read().unwrap());
+    ///     }
+    ///     assert!(!*running.read().unwrap());
+    /// }
+    /// ```
+    pub fn scoped(&self, enter: T, exit: T) -> Result<ScopeGuard<'_, T, L>, UpdateError> {
+        self.replace(enter)?;
+        Ok(ScopeGuard { instrument: self, exit: Some(exit) })
+    }
+
+    /// Replaces the value
+    fn replace(&self, value: T) -> Result<(), UpdateError> {
+        let significant = {
+            #[cfg(feature = "lock_order")]
+            let _held = lock_order::hold(self.lock_id(), self.metadata.name);
+            match self.write_data() {
+                Ok(mut data) => {
+                    *data = value;
+                    self.updated(&data)?
+                },
+                Err(_) => return Err(UpdateError::PoisonedData),
+            }
+        };
+        self.notify(significant);
+        Ok(())
+    }
+
+    /// Returns lock contention statistics of the instrument
+    ///
+    /// _Only available if `lock_contention` feature is enabled._
+    ///
+    /// Statistics are shared by all clones of the instrument.
+    #[cfg(feature = "lock_contention")]
+    pub fn lock_contention(&self) -> contention::LockContention {
+        self.contention.get()
+    }
+
+    #[cfg(feature = "lock_contention")]
+    fn read_data(&self) -> LockResult<RwLockReadGuard<'_, T>> {
+        #[cfg(feature = "lock_order")]
+        lock_order::acquiring(self.lock_id(), self.metadata.name);
+        self.contention.read(&self.data)
+    }
+
+    #[cfg(not(feature = "lock_contention"))]
+    fn read_data(&self) -> LockResult<RwLockReadGuard<'_, T>> {
+        #[cfg(feature = "lock_order")]
+        lock_order::acquiring(self.lock_id(), self.metadata.name);
+        self.data.read()
+    }
+
+    #[cfg(feature = "lock_contention")]
+    fn write_data(&self) -> LockResult<RwLockWriteGuard<'_, T>> {
+        self.contention.write(&self.data)
+    }
+
+    #[cfg(not(feature = "lock_contention"))]
+    fn write_data(&self) -> LockResult<RwLockWriteGuard<'_, T>> {
+        self.data.write()
+    }
+
+    /// Identifies the value lock, shared by all clones of the instrument
+    #[cfg(feature = "lock_order")]
+    fn lock_id(&self) -> usize {
+        Arc::as_ptr(&self.data) as *const () as usize
+    }
+}
+
+/// Sets instrument's value when dropped
+///
+/// Created by [`Instrument#scoped`]. If the instrument has been poisoned by the
+/// time the guard is dropped, the value is left as is.
+///
+/// [`Instrument#scoped`]: struct.Instrument.html#method.scoped
+#[must_use = "the value is set back as soon as the guard is dropped"]
+pub struct ScopeGuard<'a, T: Serialize + 'a, L: Listener + 'a> {
+    instrument: &'a Instrument<T, L>,
+    exit: Option<T>,
+}
+
+impl<'a, T: Serialize + 'a, L: Listener + 'a> Drop for ScopeGuard<'a, T, L> {
+    fn drop(&mut self) {
+        if let Some(exit) = self.exit.take() {
+            // Drop can't fail, and there is nothing sensible to do about a poisoned instrument
+            let _ = self.instrument.replace(exit);
+        }
+    }
+}
+
+/// Instruments that can be reset to a default value
+///
+/// This is what derived instrument boards use to implement `reset_all`.
+/// [`Instrument`] implements it whenever its value implements `Default`.
+///
+/// [`Instrument`]: struct.Instrument.html
+pub trait Reset {
+    /// Resets the instrument to its default value
+    fn reset(&self) -> Result<(), UpdateError>;
+}
+
+impl<T: Serialize + Default, L: Listener> Reset for Instrument<T, L> {
+    fn reset(&self) -> Result<(), UpdateError> {
+        Instrument::reset(self)
+    }
+}
+
+/// Instruments that can be restored from their serialized readings
+///
+/// This is what derived instrument boards use to implement `restore_from`. Readings are
+/// expected to have the same structure [`Instrument`] is serialized with, only the `value`
+/// field is used. Restoring is not an update: listeners are not notified, the timestamp
+/// is not bumped and the instrument is not considered updated. Readings with no value
+/// (of poisoned instruments) are ignored.
+///
+/// Instruments computing their values on demand (such as [`AtomicView`]) ignore readings.
+///
+/// [`Instrument`]: struct.Instrument.html
+/// [`AtomicView`]: atomic/struct.AtomicView.html
+pub trait Restore {
+    /// Sets the value of the instrument to the value of the serialized `reading`
+    fn restore<'de, D: Deserializer<'de>>(&self, reading: D) -> Result<(), D::Error>;
+}
+
+/// Fails if the instrument has been poisoned
+impl<T: Serialize + DeserializeOwned, L: Listener> Restore for Instrument<T, L> {
+    fn restore<'de, D: Deserializer<'de>>(&self, reading: D) -> Result<(), D::Error> {
+        if let Some(value) = ReadingValue::deserialize(reading)?.0 {
+            #[cfg(feature = "lock_order")]
+            let _held = lock_order::hold(self.lock_id(), self.metadata.name);
+            match self.write_data() {
+                Ok(mut data) => *data = value,
+                Err(_) => return Err(de::Error::custom("instrument is poisoned")),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Value of a serialized reading, other fields are ignored
+struct ReadingValue<T>(Option<T>);
+
+impl<'de, T: DeserializeOwned> Deserialize<'de> for ReadingValue<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct ReadingVisitor<T>(PhantomData<T>);
+
+        impl<'de, T: DeserializeOwned> de::Visitor<'de> for ReadingVisitor<T> {
+            type Value = ReadingValue<T>;
+
+            fn expecting(&self, formatter: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+                formatter.write_str("an instrument reading")
+            }
+
+            fn visit_map<A: de::MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                let mut value = None;
+                while let Some(key) = map.next_key::<String>()? {
+                    if key == "value" {
+                        value = map.next_value()?;
+                    } else {
+                        map.next_value::<de::IgnoredAny>()?;
+                    }
+                }
+                Ok(ReadingValue(value))
+            }
+        }
+
+        deserializer.deserialize_map(ReadingVisitor(PhantomData))
+    }
+}
+
+/// Instruments that can tell whether they have been updated after a point in time
+///
+/// This is what derived instrument boards use to implement `any_updated_since`. If
+/// `timestamp_instruments` feature is enabled, all instruments implement it for `DateTime<Utc>`.
+/// Instruments computing their values on demand (such as [`AtomicView`]) can change at any
+/// time, so they always report being updated.
+///
+/// [`AtomicView`]: atomic/struct.AtomicView.html
+pub trait UpdatedSince<T> {
+    /// Returns `true` if the instrument has been updated strictly after `since`
+    fn updated_since(&self, since: &T) -> bool;
+}
+
+/// Instruments that have never been updated are not considered updated, regardless of
+/// their creation time. A poisoned timestamp is considered updated.
+#[cfg(feature = "timestamp_instruments")]
+impl<T: Serialize, L: Listener> UpdatedSince<DateTime<Utc>> for Instrument<T, L> {
+    fn updated_since(&self, since: &DateTime<Utc>) -> bool {
+        self.has_been_updated() && self.timestamp.read().map(|timestamp| *timestamp > *since).unwrap_or(true)
+    }
+}
+
+/// Time of an instrument's last update
+///
+/// This is `DateTime<Utc>` if `timestamp_instruments` feature is enabled. Otherwise,
+/// instruments have no timestamps, which is represented by an uninhabited type.
+#[cfg(feature = "timestamp_instruments")]
+pub type Timestamp = DateTime<Utc>;
+
+/// Time of an instrument's last update
+///
+/// This is `DateTime<Utc>` if `timestamp_instruments` feature is enabled. Otherwise,
+/// instruments have no timestamps, which is represented by an uninhabited type.
+#[cfg(not(feature = "timestamp_instruments"))]
+pub type Timestamp = NoTimestamp;
+
+/// Timestamp of instruments if `timestamp_instruments` feature is disabled, which can't
+/// have a value
+#[cfg(not(feature = "timestamp_instruments"))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NoTimestamp {}
+
+/// Instruments that can tell when they were last updated
+///
+/// This is what derived instrument boards use to implement `full_report`. Instruments
+/// computing their values on demand (such as [`AtomicView`]) have no timestamp.
+///
+/// [`AtomicView`]: atomic/struct.AtomicView.html
+pub trait LastUpdated {
+    /// Returns the timestamp serialized as `last_update_at`, if there's one
+    fn last_update_at(&self) -> Option<Timestamp>;
+}
+
+/// The timestamp is only present if `timestamp_instruments` feature is enabled and
+/// it hasn't been poisoned
+impl<T: Serialize, L: Listener> LastUpdated for Instrument<T, L> {
+    #[cfg(feature = "timestamp_instruments")]
+    fn last_update_at(&self) -> Option<Timestamp> {
+        self.timestamp.read().ok().map(|timestamp| *timestamp)
+    }
+
+    #[cfg(not(feature = "timestamp_instruments"))]
+    fn last_update_at(&self) -> Option<Timestamp> {
+        None
     }
 }
+
+/// Instruments that can describe the structure of their readings
+///
+/// This is what derived instrument boards use to implement `describe_readings`. `G` is
+/// the generator producing descriptions. If `json_schema` feature is enabled, instruments
+/// implement it for `schemars::gen::SchemaGenerator` (see [`schema`] module).
+///
+/// [`schema`]: schema/index.html
+pub trait DescribeReading<G> {
+    /// Description of a reading
+    type Description;
+    /// Describes readings of this instrument type
+    fn describe_reading(generator: &mut G) -> Self::Description;
+}
+
+impl<L: Listener> Instrument<Duration, L> {
+    /// Runs `f`, sets the value to the time it took and returns its result
+    ///
+    /// The time is measured with a monotonic clock. Setting the value is a regular
+    /// update: the timestamp is bumped and the listener (if any) is notified. If the
+    /// instrument has been poisoned, the value is left as is, since the result of `f`
+    /// matters more than its timing.
+    ///
+    /// ```rust
+    /// extern crate rapt;
+    ///
+    /// use std::time::Duration;
+    /// use rapt::Instrument;
+    ///
+    /// fn main() {
+    ///     let latency = Instrument::<Duration, ()>::default();
+    ///     let answer = latency.time(|| 6 * 7);
+    ///     assert_eq!(answer, 42);
+    ///     assert!(latency.has_been_updated());
+    /// }
+    /// ```
+    pub fn time<F, R>(&self, f: F) -> R where F: FnOnce() -> R {
+        let started = Instant::now();
+        let result = f();
+        let _ = self.replace(started.elapsed());
+        result
+    }
+}
+
 impl<T: Serialize, L: Listener> Serialize for Instrument<T, L> {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where
         S: Serializer {
-        let mut ss = serializer.serialize_struct("Instrument", Instrument::<T, L>::serialization_field_count())?;
-        match self.data.read() {
-            Ok(res) => ss.serialize_field("value", &Some(&*res))?,
-            Err(_) => ss.serialize_field("value", &None::<T>)?,
+        self.serialize_with_field(serializer, None::<(&'static str, ())>)
+    }
+}
+
+impl<T: Serialize, L: Listener> Instrument<T, L> {
+    /// Serializes the instrument with its value transformed by `f`
+    ///
+    /// The stored value is left intact, so this allows to present it differently (for
+    /// example, rounded or converted to other units) without storing a copy. Derived
+    /// instrument boards use it for fields annotated with `#[rapt(transform = "path::to::fn")]`.
+    ///
+    /// ```rust
+    /// extern crate rapt;
+    /// extern crate serde_json;
+    ///
+    /// use rapt::Instrument;
+    ///
+    /// fn main() {
+    ///     let memory = Instrument::<u64, ()>::new(3 * 1024 * 1024);
+    ///     let v = memory.serialize_mapped(serde_json::value::Serializer, |bytes| bytes / 1024 / 1024).unwrap();
+    ///     assert_eq!(v["value"], 3);
+    ///     assert_eq!(*memory.read().unwrap(), 3 * 1024 * 1024);
+    /// }
+    /// ```
+    pub fn serialize_mapped<S: Serializer, U: Serialize, F: FnOnce(&T) -> U>(&self, serializer: S, f: F)
+                                                                           -> Result<S::Ok, S::Error> {
+        let value = f(&*self.read_poisoned());
+        self.serialize_value(serializer, &value, None::<(&'static str, ())>)
+    }
+
+    /// Serializes the instrument with an extra field following the value
+    fn serialize_with_field<S: Serializer, E: Serialize>(&self, serializer: S, extra: Option<(&'static str, E)>)
+                                                         -> Result<S::Ok, S::Error> {
+        let data = self.read_poisoned();
+        self.serialize_value(serializer, &*data, extra)
+    }
+
+    /// Serializes the instrument with a given value (the last-known one if poisoned)
+    fn serialize_value<S: Serializer, V: Serialize, E: Serialize>(&self, serializer: S, value: &V,
+                                                                  extra: Option<(&'static str, E)>)
+                                                                  -> Result<S::Ok, S::Error> {
+        let report_poisoned = self.report_poisoned && self.is_poisoned();
+        let count = self.serialization_field_count() + if extra.is_some() { 1 } else { 0 } +
+            if report_poisoned { 1 } else { 0 };
+        let mut ss = serializer.serialize_struct("Instrument", count)?;
+        ss.serialize_field("value", value)?;
+        if report_poisoned {
+            ss.serialize_field("error", "poisoned")?;
         }
-        if cfg!(feature = "timestamp_instruments") {
-            ss.serialize_field("last_update_at", &&*self.timestamp)?;
+        if let Some((key, value)) = extra {
+            ss.serialize_field(key, &value)?;
         }
+        if let Some(version) = self.metadata.schema_version {
+            ss.serialize_field("schema_version", &version)?;
+        }
+        #[cfg(feature = "timestamp_instruments")]
+        ss.serialize_field("last_update_at", &&*self.timestamp)?;
         ss.end()
     }
 }
@@ -280,16 +1295,420 @@ pub enum ReadError<E> {
 pub trait Instruments<L: Listener> {
     /// Serialize a particular instrument if it is present, fail otherwise.
     fn serialize_reading<K : AsRef<str>, S: Serializer>(&self, key: K, serializer: S) -> Result<S::Ok, ReadError<S::Error>>;
+    /// Serialize a particular instrument, merging `context` into its fields
+    ///
+    /// This allows exporters to add dimensions that are only known at serialization time.
+    /// Context entries clashing with fields instruments serialize (listed in [`INSTRUMENT_FIELDS`])
+    /// are ignored. See [`ContextSerializer`] for details.
+    ///
+    /// [`INSTRUMENT_FIELDS`]: ser/constant.INSTRUMENT_FIELDS.html
+    /// [`ContextSerializer`]: ser/struct.ContextSerializer.html
+    fn serialize_reading_with_context<K : AsRef<str>, S: Serializer>(&self, key: K, context: &HashMap<&str, String>,
+                                                                      serializer: S) -> Result<S::Ok, ReadError<S::Error>> {
+        self.serialize_reading(key, ser::ContextSerializer::new(context, serializer))
+    }
+    /// Serialize an instrument by its position in [`instrument_names`], fail if it is out of range
+    ///
+    /// This allows exporters iterating over the whole board to avoid matching instrument
+    /// names. Derived boards dispatch on the index directly.
+    ///
+    /// [`instrument_names`]: #tymethod.instrument_names
+    fn serialize_reading_at<S: Serializer>(&self, index: usize, serializer: S) -> Result<S::Ok, ReadError<S::Error>> {
+        match self.instrument_names().get(index) {
+            Some(name) => self.serialize_reading(name, serializer),
+            None => Err(ReadError::NotFound),
+        }
+    }
     /// Returns a list of instrument names
     fn instrument_names(&self) -> Vec<&'static str>;
+    /// Returns the number of instruments
+    fn instrument_count(&self) -> usize {
+        self.instrument_names().len()
+    }
+    /// Returns a list of names of instruments that should be included when reading
+    /// all instruments at once
+    ///
+    /// Exporters serializing the entire board should use it instead of
+    /// [`Instruments#instrument_names`]. When derived, instruments annotated with
+    /// `#[rapt(no_bulk)]` are excluded (but can still be read by name).
+    ///
+    /// [`Instruments#instrument_names`]: trait.Instruments.html#tymethod.instrument_names
+    fn bulk_instrument_names(&self) -> Vec<&'static str> {
+        self.instrument_names()
+    }
+    /// Returns the unit of a particular instrument, if it has one
+    ///
+    /// Exporters can use it to display values or name metrics properly. When derived,
+    /// it is specified with `#[rapt(unit = "...")]` attribute.
+    #[allow(unused_variables)]
+    fn instrument_unit<K : AsRef<str>>(&self, key: K) -> Option<&'static str> {
+        None
+    }
+    /// Returns the human-readable description of a particular instrument, if it has one
+    ///
+    /// Exporters can use it to document metrics (such as Prometheus `# HELP` lines). When
+    /// derived, it is specified with `#[rapt(description = "...")]` attribute.
+    #[allow(unused_variables)]
+    fn instrument_description<K : AsRef<str>>(&self, key: K) -> Option<&'static str> {
+        None
+    }
+    /// Returns the number of bytes a particular instrument is serialized into by serializers
+    /// instantiated with `is`
+    ///
+    /// The reading is serialized into a [`ByteCounter`], so the bytes are not kept. This helps
+    /// finding instruments that dominate published payloads.
+    ///
+    /// [`ByteCounter`]: ser/struct.ByteCounter.html
+    fn serialized_size<K: AsRef<str>, IS, S>(&self, key: K, is: &IS) -> Result<usize, ReadError<String>>
+        where for<'a> IS: ser::InstantiateSerializer<'a, ser::ByteCounter, Target=S>,
+              S: ser::IntoWriter<ser::ByteCounter>, for<'a> &'a mut S: Serializer {
+        let mut ser = is.instantiate_serializer(ser::ByteCounter::default());
+        match self.serialize_reading(key, &mut ser) {
+            Ok(_) => (),
+            Err(ReadError::NotFound) => return Err(ReadError::NotFound),
+            Err(ReadError::SerializationError(err)) => return Err(ReadError::SerializationError(err.to_string())),
+        }
+        Ok(ser.into_writer().count())
+    }
+    /// Returns serialized sizes of all instruments, see [`Instruments#serialized_size`]
+    ///
+    /// Just like [`Instruments#to_json_value`], only instruments listed by
+    /// [`Instruments#bulk_instrument_names`] are included.
+    ///
+    /// [`Instruments#serialized_size`]: trait.Instruments.html#method.serialized_size
+    /// [`Instruments#to_json_value`]: trait.Instruments.html#method.to_json_value
+    /// [`Instruments#bulk_instrument_names`]: trait.Instruments.html#method.bulk_instrument_names
+    fn serialized_sizes<IS, S>(&self, is: &IS) -> Result<HashMap<&'static str, usize>, ReadError<String>>
+        where for<'a> IS: ser::InstantiateSerializer<'a, ser::ByteCounter, Target=S>,
+              S: ser::IntoWriter<ser::ByteCounter>, for<'a> &'a mut S: Serializer {
+        let mut sizes = HashMap::new();
+        for name in self.bulk_instrument_names() {
+            sizes.insert(name, self.serialized_size(name, is)?);
+        }
+        Ok(sizes)
+    }
+    /// Returns `true` if a particular instrument has ever been updated
+    ///
+    /// Unknown instruments haven't. When derived, it is determined by instrument's
+    /// `has_been_updated` method. Otherwise, all known instruments are considered updated.
+    fn instrument_has_been_updated<K : AsRef<str>>(&self, key: K) -> bool {
+        self.instrument_names().contains(&key.as_ref())
+    }
+    /// Returns a list of names of instruments that have ever been updated
+    ///
+    /// Exporters can use it to skip instruments that still have their initial values.
+    fn updated_instrument_names(&self) -> Vec<&'static str> {
+        self.instrument_names().into_iter().filter(|name| self.instrument_has_been_updated(name)).collect()
+    }
+    /// Returns `true` if a particular instrument has been poisoned by a panic
+    ///
+    /// Unknown instruments aren't. When derived, it is determined by instrument's
+    /// `is_poisoned` method. Otherwise, no instruments are considered poisoned.
+    #[allow(unused_variables)]
+    fn instrument_is_poisoned<K : AsRef<str>>(&self, key: K) -> bool {
+        false
+    }
+    /// Returns a list of names of instruments that have been poisoned by a panic
+    ///
+    /// A supervisor can use it to decide whether to recover or restart.
+    fn poisoned_instruments(&self) -> Vec<&'static str> {
+        self.instrument_names().into_iter().filter(|name| self.instrument_is_poisoned(name)).collect()
+    }
+    /// Returns instrument names grouped by their prefixes
+    ///
+    /// Names are split by `separator`, so that `value/main` and `value/supplemental`
+    /// end up in the same `value` group. See [`NameGroup`] for details.
+    ///
+    /// [`NameGroup`]: struct.NameGroup.html
+    fn grouped_instrument_names(&self, separator: &str) -> NameGroup {
+        NameGroup::new(self.instrument_names(), separator)
+    }
+    /// Serializes all instruments into a JSON object keyed by instrument names
+    ///
+    /// _Only available if `serde_json` feature is enabled._
+    ///
+    /// Only instruments listed by [`Instruments#bulk_instrument_names`] are included.
+    ///
+    /// [`Instruments#bulk_instrument_names`]: trait.Instruments.html#method.bulk_instrument_names
+    ///
+    /// This avoids serializing into bytes and parsing them back when the readings
+    /// need to be inspected or merged into a larger JSON document.
+    #[cfg(feature = "serde_json")]
+    fn to_json_value(&self) -> Result<serde_json::Value, ReadError<serde_json::Error>> {
+        let mut map = serde_json::Map::new();
+        for name in self.bulk_instrument_names() {
+            let value = self.serialize_reading(name, serde_json::value::Serializer)?;
+            map.insert(name.into(), value);
+        }
+        Ok(serde_json::Value::Object(map))
+    }
+    /// Collects readings of all instruments into a map keyed by instrument names
+    ///
+    /// _Only available if `serde_json` feature is enabled._
+    ///
+    /// Every reading is serialized into a JSON value and then deserialized into `T`,
+    /// which can be `serde_json::Value` itself or a type mirroring the reading (such as
+    /// a struct with a `value` field). Just like [`Instruments#to_json_value`], only
+    /// instruments listed by [`Instruments#bulk_instrument_names`] are included.
+    ///
+    /// [`Instruments#to_json_value`]: trait.Instruments.html#method.to_json_value
+    /// [`Instruments#bulk_instrument_names`]: trait.Instruments.html#method.bulk_instrument_names
+    #[cfg(feature = "serde_json")]
+    fn collect_readings<T>(&self) -> Result<HashMap<&'static str, T>, ReadError<serde_json::Error>>
+        where T: serde::de::DeserializeOwned {
+        let mut readings = HashMap::new();
+        for name in self.bulk_instrument_names() {
+            let value = self.serialize_reading(name, serde_json::value::Serializer)?;
+            readings.insert(name, serde_json::from_value(value).map_err(ReadError::SerializationError)?);
+        }
+        Ok(readings)
+    }
     /// Wires listener into all instruments. If not used, no update notifications will be delivered
+    ///
+    /// Implementations should call [`Listener#on_wired`] with all instrument names first.
+    ///
+    /// [`Listener#on_wired`]: trait.Listener.html#method.on_wired
     fn wire_listener(&mut self, listener: L);
+    /// Creates a default board with the listener wired into all instruments
+    ///
+    /// This is the same as creating the board with `Default` and calling
+    /// [`Instruments#wire_listener`] on it.
+    ///
+    /// [`Instruments#wire_listener`]: trait.Instruments.html#tymethod.wire_listener
+    fn with_listener(listener: L) -> Self where Self: Default + Sized {
+        let mut instruments = Self::default();
+        instruments.wire_listener(listener);
+        instruments
+    }
+}
+
+/// Read-only view of an instrument board
+///
+/// Exposes the reading side of [`Instruments`] only, so that a board can be shared
+/// with code (such as request handlers) that should neither wire listeners nor update
+/// instruments. Cloning the view is cheap and shares the board.
+///
+/// ```rust
+/// extern crate rapt;
+/// extern crate serde;
+/// extern crate serde_json;
+/// #[macro_use]
+/// extern crate rapt_derive;
+///
+/// use std::sync::Arc;
+/// use rapt::{Instrument, Listener, ReadOnlyInstruments};
+///
+/// #[derive(Instruments)]
+/// struct AppInstruments<L: Listener> {
+///     requests: Instrument<u64, L>,
+/// }
+///
+/// fn main() {
+///     let instruments = Arc::new(AppInstruments::<()> { requests: Instrument::new(42) });
+///     let view = ReadOnlyInstruments::new(instruments.clone());
+///     let mut ser = serde_json::Serializer::new(Vec::new());
+///     assert!(view.serialize_reading("requests", &mut ser).is_ok());
+///     assert_eq!(view.instrument_names(), vec!["requests"]);
+/// }
+/// ```
+///
+/// [`Instruments`]: trait.Instruments.html
+pub struct ReadOnlyInstruments<I: Instruments<L>, L: Listener> {
+    instruments: Arc<I>,
+    listener: PhantomData<L>,
+}
+
+impl<I: Instruments<L>, L: Listener> Clone for ReadOnlyInstruments<I, L> {
+    fn clone(&self) -> Self {
+        ReadOnlyInstruments { instruments: self.instruments.clone(), listener: PhantomData }
+    }
+}
+
+impl<I: Instruments<L>, L: Listener> ReadOnlyInstruments<I, L> {
+    /// Creates a read-only view of the instrument board
+    pub fn new(instruments: Arc<I>) -> Self {
+        ReadOnlyInstruments { instruments, listener: PhantomData }
+    }
+
+    /// See [`Instruments#serialize_reading`](trait.Instruments.html#tymethod.serialize_reading)
+    pub fn serialize_reading<K : AsRef<str>, S: Serializer>(&self, key: K, serializer: S) -> Result<S::Ok, ReadError<S::Error>> {
+        self.instruments.serialize_reading(key, serializer)
+    }
+
+    /// See [`Instruments#serialize_reading_with_context`](trait.Instruments.html#method.serialize_reading_with_context)
+    pub fn serialize_reading_with_context<K : AsRef<str>, S: Serializer>(&self, key: K, context: &HashMap<&str, String>,
+                                                                          serializer: S) -> Result<S::Ok, ReadError<S::Error>> {
+        self.instruments.serialize_reading_with_context(key, context, serializer)
+    }
+
+    /// See [`Instruments#serialize_reading_at`](trait.Instruments.html#method.serialize_reading_at)
+    pub fn serialize_reading_at<S: Serializer>(&self, index: usize, serializer: S) -> Result<S::Ok, ReadError<S::Error>> {
+        self.instruments.serialize_reading_at(index, serializer)
+    }
+
+    /// See [`Instruments#instrument_names`](trait.Instruments.html#tymethod.instrument_names)
+    pub fn instrument_names(&self) -> Vec<&'static str> {
+        self.instruments.instrument_names()
+    }
+
+    /// See [`Instruments#instrument_count`](trait.Instruments.html#method.instrument_count)
+    pub fn instrument_count(&self) -> usize {
+        self.instruments.instrument_count()
+    }
+
+    /// See [`Instruments#bulk_instrument_names`](trait.Instruments.html#method.bulk_instrument_names)
+    pub fn bulk_instrument_names(&self) -> Vec<&'static str> {
+        self.instruments.bulk_instrument_names()
+    }
+
+    /// See [`Instruments#instrument_unit`](trait.Instruments.html#method.instrument_unit)
+    pub fn instrument_unit<K : AsRef<str>>(&self, key: K) -> Option<&'static str> {
+        self.instruments.instrument_unit(key)
+    }
+
+    /// See [`Instruments#instrument_description`](trait.Instruments.html#method.instrument_description)
+    pub fn instrument_description<K : AsRef<str>>(&self, key: K) -> Option<&'static str> {
+        self.instruments.instrument_description(key)
+    }
+
+    /// See [`Instruments#instrument_has_been_updated`](trait.Instruments.html#method.instrument_has_been_updated)
+    pub fn instrument_has_been_updated<K : AsRef<str>>(&self, key: K) -> bool {
+        self.instruments.instrument_has_been_updated(key)
+    }
+
+    /// See [`Instruments#updated_instrument_names`](trait.Instruments.html#method.updated_instrument_names)
+    pub fn updated_instrument_names(&self) -> Vec<&'static str> {
+        self.instruments.updated_instrument_names()
+    }
+
+    /// See [`Instruments#instrument_is_poisoned`](trait.Instruments.html#method.instrument_is_poisoned)
+    pub fn instrument_is_poisoned<K : AsRef<str>>(&self, key: K) -> bool {
+        self.instruments.instrument_is_poisoned(key)
+    }
+
+    /// See [`Instruments#poisoned_instruments`](trait.Instruments.html#method.poisoned_instruments)
+    pub fn poisoned_instruments(&self) -> Vec<&'static str> {
+        self.instruments.poisoned_instruments()
+    }
+
+    /// See [`Instruments#grouped_instrument_names`](trait.Instruments.html#method.grouped_instrument_names)
+    pub fn grouped_instrument_names(&self, separator: &str) -> NameGroup {
+        self.instruments.grouped_instrument_names(separator)
+    }
+
+    /// See [`Instruments#to_json_value`](trait.Instruments.html#method.to_json_value)
+    ///
+    /// _Only available if `serde_json` feature is enabled._
+    #[cfg(feature = "serde_json")]
+    pub fn to_json_value(&self) -> Result<serde_json::Value, ReadError<serde_json::Error>> {
+        self.instruments.to_json_value()
+    }
+
+    /// See [`Instruments#collect_readings`](trait.Instruments.html#method.collect_readings)
+    ///
+    /// _Only available if `serde_json` feature is enabled._
+    #[cfg(feature = "serde_json")]
+    pub fn collect_readings<T>(&self) -> Result<HashMap<&'static str, T>, ReadError<serde_json::Error>>
+        where T: serde::de::DeserializeOwned {
+        self.instruments.collect_readings()
+    }
+
+    /// See [`Instruments#serialized_size`](trait.Instruments.html#method.serialized_size)
+    pub fn serialized_size<K: AsRef<str>, IS, S>(&self, key: K, is: &IS) -> Result<usize, ReadError<String>>
+        where for<'a> IS: ser::InstantiateSerializer<'a, ser::ByteCounter, Target=S>,
+              S: ser::IntoWriter<ser::ByteCounter>, for<'a> &'a mut S: Serializer {
+        self.instruments.serialized_size(key, is)
+    }
+
+    /// See [`Instruments#serialized_sizes`](trait.Instruments.html#method.serialized_sizes)
+    pub fn serialized_sizes<IS, S>(&self, is: &IS) -> Result<HashMap<&'static str, usize>, ReadError<String>>
+        where for<'a> IS: ser::InstantiateSerializer<'a, ser::ByteCounter, Target=S>,
+              S: ser::IntoWriter<ser::ByteCounter>, for<'a> &'a mut S: Serializer {
+        self.instruments.serialized_sizes(is)
+    }
+}
+
+/// Instrument names grouped by prefixes
+///
+/// This is a tree of name segments. Every group contains its subgroups (in the order of
+/// their first appearance) and, if there is an instrument named after the group's path,
+/// that instrument's name.
+///
+/// ```rust
+/// extern crate rapt;
+///
+/// use rapt::NameGroup;
+///
+/// fn main() {
+///     let names = NameGroup::new(vec!["value/main", "value/supplemental", "status"], "/");
+///     let value = names.group("value").unwrap();
+///     assert_eq!(value.groups().map(|(segment, _)| segment).collect::<Vec<_>>(),
+///                vec!["main", "supplemental"]);
+///     assert_eq!(value.group("main").unwrap().instrument(), Some("value/main"));
+///     assert_eq!(names.group("status").unwrap().instrument(), Some("status"));
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct NameGroup {
+    instrument: Option<&'static str>,
+    groups: Vec<(&'static str, NameGroup)>,
+}
+
+impl NameGroup {
+    /// Groups names by `separator`, which must not be empty
+    pub fn new<I: IntoIterator<Item = &'static str>>(names: I, separator: &str) -> Self {
+        assert!(!separator.is_empty(), "separator must not be empty");
+        let mut root = NameGroup::default();
+        for name in names {
+            let group = name.split(separator).fold(&mut root, |group, segment| group.group_mut(segment));
+            group.instrument = Some(name);
+        }
+        root
+    }
+
+    fn group_mut(&mut self, segment: &'static str) -> &mut NameGroup {
+        let index = match self.groups.iter().position(|&(s, _)| s == segment) {
+            Some(index) => index,
+            None => {
+                self.groups.push((segment, NameGroup::default()));
+                self.groups.len() - 1
+            },
+        };
+        &mut self.groups[index].1
+    }
+
+    /// Returns the full name of the instrument named after this group's path, if any
+    pub fn instrument(&self) -> Option<&'static str> {
+        self.instrument
+    }
+
+    /// Returns a particular subgroup
+    pub fn group(&self, segment: &str) -> Option<&NameGroup> {
+        self.groups.iter().find(|&&(s, _)| s == segment).map(|(_, group)| group)
+    }
+
+    /// Iterates over subgroups and their name segments
+    pub fn groups(&self) -> impl Iterator<Item = (&'static str, &NameGroup)> {
+        self.groups.iter().map(|&(segment, ref group)| (segment, group))
+    }
 }
 
 /// Trait that allows instruments to notify interested parties about updates
 pub trait Listener : Clone {
     /// When invoked, an instrument with a `name` has been updated.
+    ///
+    /// It is invoked once the instrument's value is no longer locked for writing, so the
+    /// listener may read the value, or block until another thread has read it.
     fn instrument_updated(&self, name: &'static str);
+    /// When invoked, the listener is being wired into a board with instruments named `names`
+    ///
+    /// Called once by [`Instruments#wire_listener`], before the listener is wired into
+    /// instruments, so that it can prepare for them (for example, pre-allocate state for
+    /// every instrument). Does nothing by default.
+    ///
+    /// [`Instruments#wire_listener`]: trait.Instruments.html#tymethod.wire_listener
+    #[allow(unused_variables)]
+    fn on_wired(&self, names: &[&'static str]) {}
 }
 
 /// `()` implements [`Listener`] and silently discards updates. It essentially means
@@ -308,20 +1727,269 @@ use std::sync::mpsc;
 impl Listener for mpsc::Sender<&'static str> {
     #[allow(unused_variables)]
     fn instrument_updated(&self, name: &'static str) {
-        let _ = self.send(name).unwrap();
+        self.send(name).unwrap();
+    }
+}
+
+/// [`Listener`] that delivers notifications over an `mpsc` channel carrying
+/// arbitrary messages
+///
+/// Messages are constructed from instrument names, either using `From<&'static str>`
+/// or a conversion function, which allows channels to carry structured events. Plain
+/// `mpsc::Sender<&'static str>` can be used as a listener directly.
+///
+/// Just like a plain `Sender`, it panics if the receiver has been dropped, unless an error
+/// handler is set with [`on_error`].
+///
+/// [`Listener`]: trait.Listener.html
+/// [`on_error`]: #method.on_error
+pub struct ChannelListener<M> {
+    sender: mpsc::Sender<M>,
+    convert: fn(&'static str) -> M,
+    on_error: Option<Arc<dyn Fn(&'static str) + Send + Sync>>,
+}
+
+impl<M: From<&'static str>> ChannelListener<M> {
+    /// Creates a listener constructing messages with `From<&'static str>`
+    pub fn new(sender: mpsc::Sender<M>) -> Self {
+        ChannelListener::with_conversion(sender, M::from)
+    }
+}
+
+impl<M> ChannelListener<M> {
+    /// Creates a listener constructing messages with `convert`
+    pub fn with_conversion(sender: mpsc::Sender<M>, convert: fn(&'static str) -> M) -> Self {
+        ChannelListener { sender, convert, on_error: None }
+    }
+
+    /// Sets a handler invoked with the instrument name when a notification can't be
+    /// sent (because the receiver has been dropped), instead of panicking
+    ///
+    /// ```rust
+    /// extern crate rapt;
+    ///
+    /// use rapt::{ChannelListener, Listener};
+    /// use std::sync::Arc;
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::mpsc;
+    ///
+    /// fn main() {
+    ///     let dropped = Arc::new(AtomicUsize::new(0));
+    ///     let counter = dropped.clone();
+    ///     let (tx, rx) = mpsc::channel::<&'static str>();
+    ///     let listener = ChannelListener::new(tx).on_error(move |_| {
+    ///         counter.fetch_add(1, Ordering::Relaxed);
+    ///     });
+    ///     drop(rx);
+    ///     listener.instrument_updated("requests");
+    ///     assert_eq!(dropped.load(Ordering::Relaxed), 1);
+    /// }
+    /// ```
+    pub fn on_error<F>(mut self, on_error: F) -> Self where F: Fn(&'static str) + Send + Sync + 'static {
+        self.on_error = Some(Arc::new(on_error));
+        self
+    }
+}
+
+impl<M> Clone for ChannelListener<M> {
+    fn clone(&self) -> Self {
+        ChannelListener { sender: self.sender.clone(), convert: self.convert, on_error: self.on_error.clone() }
+    }
+}
+
+impl<M> Listener for ChannelListener<M> {
+    fn instrument_updated(&self, name: &'static str) {
+        if self.sender.send((self.convert)(name)).is_err() {
+            match self.on_error {
+                Some(ref on_error) => on_error(name),
+                None => panic!("instrument {} can't notify its listener, the receiver has been dropped", name),
+            }
+        }
+    }
+}
+
+/// [`Listener`] that tags instrument names with their source before forwarding them
+///
+/// When boards of several subsystems notify a single listener, their instrument names
+/// might collide. This listener forwards `tag` and the instrument name joined with a
+/// separator (`/` by default) to the inner listener, for both updates and wiring.
+///
+/// Since listeners take `&'static str` names, tagged names are interned: each of them is
+/// allocated (and leaked) once, when the listener is wired or first notified, and reused
+/// afterwards. Boards have a fixed set of names, so is the memory spent on them. Clones
+/// share interned names, but separately created listeners don't, so a listener should be
+/// created once per source rather than per wiring.
+///
+/// ```rust
+/// extern crate rapt;
+///
+/// use rapt::{Listener, TaggedListener};
+/// use std::sync::mpsc;
+///
+/// fn main() {
+///     let (tx, rx) = mpsc::channel();
+///     let listener = TaggedListener::new("storage", tx);
+///     listener.instrument_updated("requests");
+///     assert_eq!(rx.recv().unwrap(), "storage/requests");
+/// }
+/// ```
+///
+/// [`Listener`]: trait.Listener.html
+#[derive(Clone)]
+pub struct TaggedListener<L: Listener> {
+    inner: L,
+    tag: &'static str,
+    separator: &'static str,
+    names: Arc<RwLock<HashMap<&'static str, &'static str>>>,
+}
+
+impl<L: Listener> TaggedListener<L> {
+    /// Creates a listener tagging names with `tag` for `inner` listener
+    pub fn new(tag: &'static str, inner: L) -> Self {
+        TaggedListener { inner, tag, separator: "/", names: Default::default() }
+    }
+
+    /// Joins the tag and instrument names with `separator`
+    pub fn with_separator(mut self, separator: &'static str) -> Self {
+        self.separator = separator;
+        self.names = Default::default();
+        self
+    }
+
+    /// Returns the tag
+    pub fn tag(&self) -> &'static str {
+        self.tag
+    }
+
+    /// Returns the inner listener
+    pub fn inner(&self) -> &L {
+        &self.inner
+    }
+
+    /// Returns the tagged instrument name, interning it if necessary
+    pub fn tagged(&self, name: &'static str) -> &'static str {
+        if let Some(tagged) = self.names.read().unwrap_or_else(|poisoned| poisoned.into_inner()).get(name) {
+            return tagged;
+        }
+        let mut names = self.names.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let (tag, separator) = (self.tag, self.separator);
+        names.entry(name).or_insert_with(|| Box::leak(format!("{}{}{}", tag, separator, name).into_boxed_str()))
+    }
+}
+
+impl<L: Listener> Listener for TaggedListener<L> {
+    fn instrument_updated(&self, name: &'static str) {
+        self.inner.instrument_updated(self.tagged(name))
+    }
+
+    fn on_wired(&self, names: &[&'static str]) {
+        let tagged: Vec<_> = names.iter().map(|name| self.tagged(name)).collect();
+        self.inner.on_wired(&tagged)
     }
 }
 
 /// Declare and re-export optional mqttc crate
 #[cfg(feature = "mqtt_publisher")]
 pub extern crate mqttc;
+/// Declare and re-export optional netopt crate (used to configure MQTT connections)
+#[cfg(feature = "mqtt_publisher")]
+pub extern crate netopt;
 /// Optional mqtt module
 #[cfg(feature = "mqtt_publisher")]
 pub mod mqtt;
 
+#[cfg(all(feature = "eventfd_listener", target_os = "linux"))]
+extern crate libc;
+/// Optional eventfd listener module
+#[cfg(all(feature = "eventfd_listener", target_os = "linux"))]
+pub mod eventfd;
+
+/// Optional Server-Sent Events publisher module
+#[cfg(feature = "sse_publisher")]
+pub mod sse;
+
+/// Declare and re-export optional sysinfo crate
+#[cfg(feature = "sysinfo")]
+pub extern crate sysinfo;
+/// Optional system instruments module
+#[cfg(feature = "sysinfo")]
+pub mod system;
+
+#[cfg(feature = "dynamic_instruments")]
+extern crate erased_serde;
+/// Optional dynamic instrument boards module
+#[cfg(feature = "dynamic_instruments")]
+pub mod dynamic;
+
 /// Declare and re-export optional serde_json crate
 #[cfg(feature = "serde_json")]
 pub extern crate serde_json;
 
+/// Declare and re-export optional schemars crate
+#[cfg(feature = "json_schema")]
+pub extern crate schemars;
+/// Optional JSON Schema module
+#[cfg(feature = "json_schema")]
+pub mod schema;
+
+/// Optional InfluxDB line protocol module
+#[cfg(feature = "influx")]
+pub mod influx;
+
+/// Optional JSON patches module
+#[cfg(feature = "serde_json")]
+pub mod patch;
+
 /// Serialization utilities
-pub mod ser;
\ No newline at end of file
+pub mod ser;
+
+/// Detecting repeated readings
+pub mod dedup;
+
+/// Flat numeric readings
+pub mod flat;
+
+/// Read-only instruments over atomics
+pub mod atomic;
+pub use atomic::AtomicView;
+
+/// Instruments computing their values on demand
+pub mod lazy;
+pub use lazy::LazyInstrument;
+
+/// Instruments computed out of other instruments
+pub mod computed;
+
+/// Instruments applying only some of their updates
+pub mod sampled;
+
+/// Instruments measuring the rate of events
+pub mod rate;
+
+/// Polling values changed outside of rapt
+pub mod poll;
+
+/// Lock contention statistics
+#[cfg(feature = "lock_contention")]
+pub mod contention;
+
+/// Lock order debugging
+#[cfg(feature = "lock_order")]
+pub mod lock_order;
+
+/// Uptime instrument
+pub mod uptime;
+
+/// Notifying about significant changes only
+pub mod threshold;
+
+mod throttle;
+pub use threshold::Threshold;
+
+/// Bounded log of recent errors
+#[cfg(feature = "timestamp_instruments")]
+pub mod error_log;
+
+/// Optional test utilities module
+#[cfg(feature = "test_util")]
+pub mod test_util;
\ No newline at end of file
@@ -0,0 +1,173 @@
+// Copyright 2017 All Contributors (see CONTRIBUTORS file)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+//
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! # Lock order debugging
+//!
+//! _This module is only present if `lock_order` feature is enabled.
+//! It is disabled by default, as it adds some overhead to every instrument operation._
+//!
+//! Updating an instrument from within another instrument's update (or reading one) holds
+//! both of their locks at once. If two threads do that to the same pair of instruments in
+//! different orders, they can deadlock. When enabled, every thread keeps track of the
+//! instrument locks it holds for writing, and every acquisition while holding other locks
+//! is recorded. Acquiring a pair of locks in the order opposite to the one recorded before
+//! is reported as a [`LockOrderInversion`], even if no deadlock actually happened.
+//!
+//! ```
+//! extern crate rapt;
+//!
+//! use rapt::Instrument;
+//! use rapt::lock_order;
+//!
+//! fn main() {
+//!     let mut requests: Instrument<u64, ()> = Instrument::new(0);
+//!     requests.set_name("requests");
+//!     let mut errors: Instrument<u64, ()> = Instrument::new(0);
+//!     errors.set_name("errors");
+//!
+//!     requests.update(|v| *v += *errors.read().unwrap()).unwrap();
+//!     errors.update(|v| *v = *requests.read().unwrap()).unwrap();
+//!
+//!     let inversion = lock_order::inversions().pop().unwrap();
+//!     assert_eq!((inversion.first, inversion.second), (Some("requests"), Some("errors")));
+//! }
+//! ```
+//!
+//! Locks are identified by the address of instrument's value, which can be reused
+//! once all clones of an instrument are dropped. Reads return the lock guard to the
+//! caller, so they are only recorded as acquisitions and are not tracked as held.
+//!
+//! [`LockOrderInversion`]: struct.LockOrderInversion.html
+
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+use std::sync::Mutex;
+use std::thread;
+
+/// A pair of instrument locks that has been acquired in both orders
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LockOrderInversion {
+    /// Name of the instrument locked first when the pair was first acquired
+    /// (`None` if it has no name)
+    pub first: Option<&'static str>,
+    /// Name of the instrument locked second when the pair was first acquired
+    /// (`None` if it has no name)
+    pub second: Option<&'static str>,
+    /// Name of the thread that acquired the pair in the opposite order, if any
+    pub thread: Option<String>,
+}
+
+struct State {
+    /// Pairs of locks acquired while holding the first one
+    acquired: BTreeSet<(usize, usize)>,
+    /// Pairs of locks already reported, so that every inversion is reported once
+    reported: BTreeSet<(usize, usize)>,
+    inversions: Vec<LockOrderInversion>,
+}
+
+static STATE: Mutex<State> = Mutex::new(State {
+    acquired: BTreeSet::new(),
+    reported: BTreeSet::new(),
+    inversions: Vec::new(),
+});
+
+thread_local! {
+    /// Locks held by the current thread, along with instrument names
+    static HELD: RefCell<Vec<(usize, Option<&'static str>)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Returns lock order inversions reported so far
+pub fn inversions() -> Vec<LockOrderInversion> {
+    state().inversions.clone()
+}
+
+/// Forgets recorded acquisitions and reported inversions
+pub fn clear() {
+    let mut state = state();
+    state.acquired.clear();
+    state.reported.clear();
+    state.inversions.clear();
+}
+
+/// Recording must go on even if a thread panicked while holding the state
+fn state() -> ::std::sync::MutexGuard<'static, State> {
+    STATE.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Records acquisition of a lock, reporting inversions against locks held by the current thread
+pub(crate) fn acquiring(lock: usize, name: Option<&'static str>) {
+    HELD.with(|held| {
+        let held = held.borrow();
+        if held.iter().all(|&(id, _)| id == lock) {
+            return;
+        }
+        let mut state = state();
+        for &(id, held_name) in held.iter().filter(|&&(id, _)| id != lock) {
+            state.acquired.insert((id, lock));
+            if state.acquired.contains(&(lock, id)) && state.reported.insert((lock.min(id), lock.max(id))) {
+                state.inversions.push(LockOrderInversion {
+                    first: name,
+                    second: held_name,
+                    thread: thread::current().name().map(String::from),
+                });
+            }
+        }
+    })
+}
+
+/// A lock held by the current thread, released when dropped
+pub(crate) struct Held(usize);
+
+/// Records acquisition of a lock and tracks it as held until the returned value is dropped
+pub(crate) fn hold(lock: usize, name: Option<&'static str>) -> Held {
+    acquiring(lock, name);
+    HELD.with(|held| held.borrow_mut().push((lock, name)));
+    Held(lock)
+}
+
+impl Drop for Held {
+    fn drop(&mut self) {
+        HELD.with(|held| {
+            let mut held = held.borrow_mut();
+            if let Some(index) = held.iter().rposition(|&(id, _)| id == self.0) {
+                held.remove(index);
+            }
+        })
+    }
+}
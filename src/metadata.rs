@@ -0,0 +1,167 @@
+// Copyright 2017 All Contributors (see CONTRIBUTORS file)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+//
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+
+//! Metadata shared by all instrument types
+//!
+//! Instrument boards attach the name, the unit, the description and the schema version
+//! to every instrument during listener wiring. The accessors and the wiring methods are
+//! generated by the macros below, so that every instrument type exposes the methods the
+//! derived code calls the same way.
+
+use serde::{Serialize, Serializer};
+use serde::ser::SerializeStruct;
+
+/// Name, unit, description and schema version of an instrument
+#[derive(Clone, Copy, Default)]
+pub(crate) struct Metadata {
+    pub(crate) name: Option<&'static str>,
+    pub(crate) unit: Option<&'static str>,
+    pub(crate) description: Option<&'static str>,
+    pub(crate) schema_version: Option<u32>,
+}
+
+impl Metadata {
+    /// Serializes a reading of an instrument computing its value on demand
+    ///
+    /// Such readings have no `last_update_at` field, as there are no updates to timestamp.
+    pub(crate) fn serialize_computed<S: Serializer, V: Serialize>(&self, serializer: S, value: V)
+                                                                  -> Result<S::Ok, S::Error> {
+        let count = if self.schema_version.is_some() { 2 } else { 1 };
+        let mut ss = serializer.serialize_struct("Instrument", count)?;
+        ss.serialize_field("value", &Some(value))?;
+        if let Some(version) = self.schema_version {
+            ss.serialize_field("schema_version", &version)?;
+        }
+        ss.end()
+    }
+}
+
+/// Generates metadata accessors backed by the `Metadata` at `self.$field`
+macro_rules! metadata_accessors {
+    ($($field: ident).+) => {
+        /// Sets the name of the instrument. FOR INTERNAL USE ONLY.
+        pub fn set_name(&mut self, name: &'static str) {
+            self.$($field).+.name = Some(name)
+        }
+
+        /// Sets the unit of the instrument value (such as `bytes` or `milliseconds`)
+        ///
+        /// When derived, instrument boards set it from the `#[rapt(unit = "...")]` attribute
+        /// during listener wiring.
+        pub fn set_unit(&mut self, unit: &'static str) {
+            self.$($field).+.unit = Some(unit)
+        }
+
+        /// Returns the unit of the instrument value, if any
+        pub fn unit(&self) -> Option<&'static str> {
+            self.$($field).+.unit
+        }
+
+        /// Sets a human-readable description of the instrument
+        ///
+        /// When derived, instrument boards set it from the `#[rapt(description = "...")]` attribute
+        /// during listener wiring.
+        pub fn set_description(&mut self, description: &'static str) {
+            self.$($field).+.description = Some(description)
+        }
+
+        /// Returns the description of the instrument, if any
+        pub fn description(&self) -> Option<&'static str> {
+            self.$($field).+.description
+        }
+
+        /// Sets the version of the value's schema, serialized as `schema_version` field
+        ///
+        /// This allows consumers to tell apart readings of evolving value types. When derived,
+        /// instrument boards set it from the `#[rapt(version = N)]` attribute during listener wiring.
+        pub fn set_schema_version(&mut self, version: u32) {
+            self.$($field).+.schema_version = Some(version)
+        }
+
+        /// Returns the version of the value's schema, if any
+        pub fn schema_version(&self) -> Option<u32> {
+            self.$($field).+.schema_version
+        }
+    };
+}
+
+/// Generates listener wiring and status accessors for instruments computing their value
+/// on demand, parametrized over listener type `$listener`
+///
+/// There are no updates to notify listeners about, so they are discarded.
+macro_rules! on_demand_wiring {
+    ($listener: ident) => {
+        /// Sets the name of the instrument. FOR INTERNAL USE ONLY.
+        ///
+        /// The listener is discarded as there are no updates to notify it about.
+        pub fn set_name_and_listener(&mut self, name: &'static str, _listener: $listener) {
+            self.set_name(name)
+        }
+
+        /// Discards the listener. FOR INTERNAL USE ONLY.
+        pub fn set_listener(&mut self, _listener: $listener) {}
+
+        /// Always returns `true`, as the value is computed on demand
+        pub fn has_been_updated(&self) -> bool {
+            true
+        }
+
+        /// Always returns `false`, as there is no lock to poison
+        pub fn is_poisoned(&self) -> bool {
+            false
+        }
+    };
+}
+
+/// Generates listener wiring for instruments wrapping an `Instrument` at `self.$field`,
+/// parametrized over listener type `$listener`
+macro_rules! wrapped_wiring {
+    ($field: ident, $listener: ident) => {
+        /// Sets the name of the instrument and the listener. FOR INTERNAL USE ONLY.
+        pub fn set_name_and_listener(&mut self, name: &'static str, listener: $listener) {
+            self.$field.set_name_and_listener(name, listener)
+        }
+
+        /// Sets the listener without notifying it. FOR INTERNAL USE ONLY.
+        pub fn set_listener(&mut self, listener: $listener) {
+            self.$field.set_listener(listener)
+        }
+    };
+}
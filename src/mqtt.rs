@@ -70,20 +70,260 @@
 
 /// Re-exports mqtcc crate
 pub use mqttc as client;
+/// Re-exports netopt crate
+pub use netopt;
+use netopt::NetworkOptions;
 use self::client::{PubSub, PubOpt};
 
 use super::{Listener, Instruments};
 use super::ser::{InstantiateSerializer, IntoWriter};
+use super::dedup::{Dedup, FlapDamping};
 use serde::Serializer;
 
-use std::sync::mpsc;
+use std::sync::{mpsc, Arc, Mutex, Condvar};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+use std::thread;
+use std::mem;
+use std::net::ToSocketAddrs;
 
 /// Publisher control messages
 enum Message {
     /// An instrument has been updated
     Update(&'static str),
-    /// Shutdown requested
-    Shutdown,
+    /// Shutdown requested, acknowledgement is sent over the enclosed channel
+    Shutdown(mpsc::Sender<()>),
+    /// Instrument's topic has been changed
+    Rename(&'static str, String),
+    /// Instrument's retained message should be cleared
+    Clear(&'static str),
+    /// Flush requested, acknowledgement is sent over the enclosed channel once
+    /// all messages queued before it have been processed
+    Flush(mpsc::Sender<()>),
+}
+
+/// Backpressure strategy for publisher's update queue
+///
+/// Every instrument update is queued for the publisher. If the broker can't keep up,
+/// an unbounded queue will keep growing. Since [`Publisher`] only publishes current
+/// values of instruments, it is usually safe to drop some of the queued updates.
+///
+/// With a bounded queue, an update of an instrument that already has one queued is merged
+/// into it, as the publisher will publish the latest value anyway. Updates are only dropped
+/// or blocked on when the queue is full of updates of other instruments.
+///
+/// The capacity is only enforced once [`Publisher#run`] has started. Until then, there's no
+/// one to make room in the queue, so updates (such as the initial readings announced when
+/// the publisher is wired into instruments) are queued regardless, merging as described above.
+///
+/// Shutdown and flush requests are never dropped and do not count towards the capacity.
+///
+/// [`Publisher`]: struct.Publisher.html
+/// [`Publisher#run`]: struct.Publisher.html#method.run
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Backpressure {
+    /// The queue is unbounded (default)
+    #[default]
+    Unbounded,
+    /// The queue holds up to a given number of updates, dropping the oldest one when full
+    /// of updates of other instruments
+    DropOldest(usize),
+    /// The queue holds up to a given number of updates, dropping the new one when full
+    DropNewest(usize),
+    /// The queue holds up to a given number of updates, blocking the updating thread
+    /// until there is room
+    ///
+    /// The updating thread blocks once it has released the instrument's value, so the
+    /// publisher can still read it to make room.
+    Block(usize),
+}
+
+/// What the publisher does when an instrument's reading can't be serialized
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum SerializationErrorPolicy {
+    /// The reading is skipped and counted (see [`Handle#skipped_readings`]), other
+    /// instruments are published as usual (default)
+    ///
+    /// [`Handle#skipped_readings`]: struct.Handle.html#method.skipped_readings
+    #[default]
+    Skip,
+    /// The publisher panics
+    FailFast,
+}
+
+/// Publisher's message queue
+///
+/// It is shared between the publisher and its handles, so it also keeps publisher's
+/// statistics.
+struct Queue {
+    state: Mutex<QueueState>,
+    backpressure: Backpressure,
+    /// Number of readings skipped because they couldn't be serialized
+    skipped_readings: AtomicUsize,
+    /// Number of messages the client has failed to publish
+    failed_publishes: AtomicUsize,
+    /// Signalled when a message has been queued
+    available: Condvar,
+    /// Signalled when a message has been taken out of the queue
+    taken: Condvar,
+}
+
+struct QueueState {
+    messages: VecDeque<Message>,
+    /// Number of `Message::Update` in `messages`
+    updates: usize,
+    /// Number of `Message::Shutdown` in `messages`
+    shutdowns: usize,
+    /// Set when the publisher is not running anymore
+    closed: bool,
+    /// Set while the publisher is running
+    running: bool,
+}
+
+impl QueueState {
+    /// Returns `true` if an update of the instrument is queued after the last control message
+    ///
+    /// Updates queued before a control message (such as a topic change) can't be merged with,
+    /// as the publisher has to handle them before it.
+    fn update_pending(&self, name: &'static str) -> bool {
+        self.messages.iter().rev()
+            .take_while(|m| matches!(**m, Message::Update(_)))
+            .any(|m| matches!(*m, Message::Update(n) if n == name))
+    }
+}
+
+impl Queue {
+    fn new(backpressure: Backpressure) -> Self {
+        Queue {
+            state: Mutex::new(QueueState {
+                messages: VecDeque::new(), updates: 0, shutdowns: 0, closed: false, running: false,
+            }),
+            backpressure,
+            skipped_readings: AtomicUsize::new(0),
+            failed_publishes: AtomicUsize::new(0),
+            available: Condvar::new(),
+            taken: Condvar::new(),
+        }
+    }
+
+    /// Queues a message, returns it back if the publisher is not running
+    fn send(&self, message: Message) -> Result<(), Message> {
+        let mut state = self.state.lock().unwrap();
+        if let Message::Update(name) = message {
+            if self.backpressure != Backpressure::Unbounded && !state.closed && state.update_pending(name) {
+                // The queued update will publish the latest value
+                return Ok(());
+            }
+            if state.running {
+                match self.backpressure {
+                    Backpressure::Unbounded => (),
+                    Backpressure::DropOldest(capacity) => {
+                        if state.updates >= capacity {
+                            if let Some(index) = state.messages.iter().position(|m| matches!(*m, Message::Update(_))) {
+                                state.messages.remove(index);
+                                state.updates -= 1;
+                            }
+                        }
+                    },
+                    Backpressure::DropNewest(capacity) => {
+                        if state.updates >= capacity {
+                            return Ok(());
+                        }
+                    },
+                    Backpressure::Block(capacity) => {
+                        while state.updates >= capacity && state.running {
+                            state = self.taken.wait(state).unwrap();
+                        }
+                    },
+                }
+            }
+        }
+        if state.closed {
+            return Err(message);
+        }
+        match message {
+            Message::Update(_) => state.updates += 1,
+            Message::Shutdown(_) => state.shutdowns += 1,
+            Message::Rename(..) | Message::Clear(_) | Message::Flush(_) => (),
+        }
+        state.messages.push_back(message);
+        self.available.notify_one();
+        Ok(())
+    }
+
+    /// Takes the next message out of the queue, blocks until there is one or until
+    /// `deadline` (if any) has passed
+    ///
+    /// Shutdown requests take priority over updates.
+    fn recv(&self, deadline: Option<Instant>) -> Option<Message> {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            let index = if state.shutdowns > 0 {
+                state.messages.iter().position(|m| matches!(*m, Message::Shutdown(_)))
+            } else {
+                None
+            };
+            if let Some(message) = state.messages.remove(index.unwrap_or(0)) {
+                match message {
+                    Message::Update(_) => state.updates -= 1,
+                    Message::Shutdown(_) => state.shutdowns -= 1,
+                    Message::Rename(..) | Message::Clear(_) | Message::Flush(_) => (),
+                }
+                self.taken.notify_all();
+                return Some(message);
+            }
+            state = match deadline {
+                None => self.available.wait(state).unwrap(),
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return None;
+                    }
+                    self.available.wait_timeout(state, deadline - now).unwrap().0
+                },
+            };
+        }
+    }
+
+    /// Returns the number of queued messages
+    fn len(&self) -> usize {
+        self.state.lock().unwrap().messages.len()
+    }
+
+    /// Returns `true` if there is a pending shutdown request
+    fn shutdown_requested(&self) -> bool {
+        self.state.lock().unwrap().shutdowns > 0
+    }
+
+    /// Marks the queue as open (the publisher is running) or closed
+    ///
+    /// When closing, discards all queued messages, acknowledging pending shutdown and flush requests.
+    fn set_closed(&self, closed: bool) {
+        let mut state = self.state.lock().unwrap();
+        state.closed = closed;
+        state.running = !closed;
+        if closed {
+            for message in state.messages.drain(..) {
+                match message {
+                    Message::Shutdown(ack) | Message::Flush(ack) => { let _ = ack.send(()); },
+                    Message::Update(_) | Message::Rename(..) | Message::Clear(_) => (),
+                }
+            }
+            state.updates = 0;
+            state.shutdowns = 0;
+            self.taken.notify_all();
+        }
+    }
+}
+
+/// Closes the queue when the publisher is dropped
+struct Receiver(Arc<Queue>);
+
+impl Drop for Receiver {
+    fn drop(&mut self) {
+        self.0.set_closed(true);
+    }
 }
 
 /// A trait for formatting instrument name into a full MQTT topic name
@@ -100,6 +340,77 @@ impl TopicFormatter for () {
     }
 }
 
+/// [`TopicFormatter`] mapping instrument names to explicit topics
+///
+/// Names that aren't mapped are used as topics, just like with `()`. This allows remapping
+/// topics of a board without changing its definition.
+///
+/// ```rust
+/// extern crate rapt;
+///
+/// use rapt::mqtt::{MapFormatter, TopicFormatter};
+///
+/// fn main() {
+///     let mut topics = std::collections::HashMap::new();
+///     topics.insert("requests", "site/web/requests".to_string());
+///     let formatter = MapFormatter(topics);
+///     assert_eq!(formatter.format_topic("requests"), "site/web/requests");
+///     assert_eq!(formatter.format_topic("errors"), "errors");
+/// }
+/// ```
+///
+/// [`TopicFormatter`]: trait.TopicFormatter.html
+pub struct MapFormatter(pub HashMap<&'static str, String>);
+
+impl TopicFormatter for MapFormatter {
+    fn format_topic(&self, name: &'static str) -> String {
+        match self.0.get(name) {
+            Some(topic) => topic.clone(),
+            None => name.into(),
+        }
+    }
+}
+
+/// MQTT client publishing messages on behalf of [`Publisher`]
+///
+/// Implemented for mqttc's `Client`. Other implementations can route messages elsewhere,
+/// for example, record them in tests.
+///
+/// [`Publisher`]: struct.Publisher.html
+pub trait PublishClient {
+    /// Publishes `payload` to `topic`
+    fn publish(&mut self, topic: String, payload: Vec<u8>, pubopt: PubOpt) -> client::Result<()>;
+}
+
+impl PublishClient for client::Client {
+    fn publish(&mut self, topic: String, payload: Vec<u8>, pubopt: PubOpt) -> client::Result<()> {
+        PubSub::publish(self, topic, payload, pubopt)
+    }
+}
+
+/// [`PublishClient`] handing messages over to a callback instead of a broker
+///
+/// The publisher runs as usual (serializing readings, detecting repeated ones and
+/// formatting topics), only messages are passed to the callback with their topics and
+/// options. This allows validating a deployment's topic scheme without a broker:
+///
+/// ```norun
+/// let client = mqtt::DryRun(|topic: &str, payload: &[u8], _| {
+///     println!("{}: {}", topic, String::from_utf8_lossy(payload));
+/// });
+/// let publisher = mqtt::PublisherBuilder::new(client, instruments).build();
+/// ```
+///
+/// [`PublishClient`]: trait.PublishClient.html
+pub struct DryRun<F: FnMut(&str, &[u8], PubOpt)>(pub F);
+
+impl<F: FnMut(&str, &[u8], PubOpt)> PublishClient for DryRun<F> {
+    fn publish(&mut self, topic: String, payload: Vec<u8>, pubopt: PubOpt) -> client::Result<()> {
+        (self.0)(&topic, &payload, pubopt);
+        Ok(())
+    }
+}
+
 /// MQTT publisher
 /// 
 /// An important aspect of how Rapt and `Publisher` works is that it *will not*
@@ -107,16 +418,26 @@ impl TopicFormatter for () {
 /// to every event of an update but it will grab instrument's last value as opposed
 /// to the value that it had after that particular update. As a consequence, `Publisher`
 /// will filter out messages that simply repeat the previous message for the given instrument.
-pub struct Publisher<TF: TopicFormatter, I: Instruments<Handle>> {
+/// How repeated messages are detected can be configured with [`Publisher#set_dedup`].
+///
+/// [`Publisher#set_dedup`]: struct.Publisher.html#method.set_dedup
+pub struct Publisher<TF: TopicFormatter, I: Instruments<Handle>, C: PublishClient = client::Client> {
     topic_formatter: TF,
-    client: client::Client,
+    client: C,
     instruments: I,
-    retain: bool,
-    sender: mpsc::Sender<Message>,
-    receiver: mpsc::Receiver<Message>,
+    pubopt: fn(&'static str) -> PubOpt,
+    dedup: Dedup,
+    damping: Option<FlapDamping>,
+    serialization_errors: SerializationErrorPolicy,
+    /// Last published readings, if publishing diffs
+    #[cfg(feature = "serde_json")]
+    published: Option<HashMap<&'static str, serde_json::Value>>,
+    /// Topics set with `Handle#set_topic_name`
+    topics: HashMap<&'static str, String>,
+    receiver: Receiver,
 }
 
-impl<TF: TopicFormatter, I: Instruments<Handle>> Publisher<TF, I> {
+impl<TF: TopicFormatter, I: Instruments<Handle>, C: PublishClient> Publisher<TF, I, C> {
     /// Creates a new MQTT publisher
     ///
     /// Consumes following arguments:
@@ -126,18 +447,24 @@ impl<TF: TopicFormatter, I: Instruments<Handle>> Publisher<TF, I> {
     /// * instruments
     /// * retain (true if messages should be retained)
     ///
-    pub fn new(topic_formatter: TF, client: client::Client, mut instruments: I, retain: bool) -> Self {
-        let (sender, receiver) = mpsc::channel();
-        let handle = Handle { sender: sender.clone() };
-        instruments.wire_listener(handle);
-        Publisher {
-            topic_formatter,
-            client,
-            instruments,
-            retain,
-            sender,
-            receiver,
-        }
+    /// For other options, use [`PublisherBuilder`].
+    ///
+    /// [`PublisherBuilder`]: struct.PublisherBuilder.html
+    pub fn new(topic_formatter: TF, client: C, instruments: I, retain: bool) -> Self {
+        PublisherBuilder::new(client, instruments).topic_formatter(topic_formatter).retain(retain).build()
+    }
+
+    /// Creates a new MQTT publisher with a given backpressure strategy
+    ///
+    /// Takes the same arguments as [`Publisher#new`], followed by [`Backpressure`].
+    /// Panics if the capacity of a bounded queue is zero.
+    ///
+    /// [`Publisher#new`]: struct.Publisher.html#method.new
+    /// [`Backpressure`]: enum.Backpressure.html
+    pub fn with_backpressure(topic_formatter: TF, client: C, instruments: I, retain: bool,
+                             backpressure: Backpressure) -> Self {
+        PublisherBuilder::new(client, instruments).topic_formatter(topic_formatter).retain(retain)
+            .backpressure(backpressure).build()
     }
 
     /// Returns a reference to instruments
@@ -153,11 +480,20 @@ impl<TF: TopicFormatter, I: Instruments<Handle>> Publisher<TF, I> {
         &self.instruments
     }
 
+    /// Sets the way repeated messages are detected
+    ///
+    /// By default, only instrument values are compared, see [`Dedup`] for details.
+    ///
+    /// [`Dedup`]: ../dedup/struct.Dedup.html
+    pub fn set_dedup(&mut self, dedup: Dedup) {
+        self.dedup = dedup;
+    }
+
     /// Handle to the running `Publisher`
     ///
     /// Mainly used to gracefully shut it down.
     pub fn handle(&self) -> Handle {
-        Handle { sender: self.sender.clone() }
+        Handle { queue: self.receiver.0.clone() }
     }
 
     /// This method is typically used to run the publisher in a new thread:
@@ -165,92 +501,631 @@ impl<TF: TopicFormatter, I: Instruments<Handle>> Publisher<TF, I> {
     /// ```norun
     /// let publisher_thread = thread::spawn(move || publisher.run(rapt::ser::JsonSerializer));
     /// ```
+    ///
+    /// It returns once [`Handle#shutdown`] has been called. To take the client back
+    /// afterwards, return the publisher from the thread and call [`Publisher#into_inner`]:
+    ///
+    /// ```norun
+    /// let publisher_thread = thread::spawn(move || {
+    ///     publisher.run(rapt::ser::JsonSerializer);
+    ///     publisher
+    /// });
+    /// publisher_handle.shutdown();
+    /// let client = publisher_thread.join().unwrap().into_inner();
+    /// ```
+    ///
+    /// [`Handle#shutdown`]: struct.Handle.html#method.shutdown
+    /// [`Publisher#into_inner`]: struct.Publisher.html#method.into_inner
     pub fn run<IS, S>(&mut self, is: IS)
            where for<'a> IS: InstantiateSerializer<'a, Vec<u8>, Target=S>,
                  S: IntoWriter<Vec<u8>>, for<'a> &'a mut S: Serializer {
 
-        use std::hash::{Hash, Hasher};
-        use std::collections::hash_map::DefaultHasher;
-        use std::collections::HashMap;
-        use std::collections::hash_map::Entry;
-        // This allows us to filter out duplicate values, by storing
-        // `name => serialized_value_hash` we can relatively quickly
-        // and inexpensively check whether we're attempting to send
-        // a duplicate of the last message
-        let mut last_messages = HashMap::new();
-
-        let pubopt = if self.retain {
-            PubOpt::retain()
-        } else {
-            PubOpt::at_least_once()
-        };
-        loop {
-            match self.receiver.recv() {
-                Ok(Message::Shutdown) => break,
-                Ok(Message::Update(name)) => {
-                    let mut ser = is.instantiate_serializer(Vec::with_capacity(64));
-                    let _ = self.instruments.serialize_reading(name, &mut ser).unwrap();
-                    let vec : Vec<u8> = ser.into_writer();
-
-                    // Calculate message hash
-                    let mut hasher = DefaultHasher::new();
-                    vec.hash(&mut hasher);
-                    let hash = hasher.finish();
-
-                    if match last_messages.entry(name) {
-                        // This is the first message for this instrument
-                        Entry::Vacant(entry) => {
-                            entry.insert(hash);
-                            // send it
-                            true
-                        },
-                        // There was a message sent for this instrument
-                        Entry::Occupied(mut entry) => {
-                            if *entry.get() != hash {
-                                entry.insert(hash);
-                                // if it was a different message, send it
-                                true
-                            } else {
-                                // otherwise, don't
-                                false
-                            }
-                        }
-                    } {
-                        let _ = self.client.publish(self.topic_formatter.format_topic(name), vec, pubopt).unwrap();
+        // Serialization buffer is reused across updates: the serializer is
+        // instantiated over it for every update and gives it back once done.
+        // Readings that aren't published (repeated, held back or skipped
+        // ones) don't allocate once it has grown large enough. Published
+        // readings are handed over to the client, see `publish_reading`.
+        let mut buffer = Vec::with_capacity(64);
+
+        let queue = self.receiver.0.clone();
+        queue.set_closed(false);
+        let ack = loop {
+            let settlement = self.damping.as_ref().and_then(FlapDamping::next_settlement);
+            let message = match queue.recv(settlement) {
+                Some(message) => message,
+                None => {
+                    buffer = self.publish_settled(buffer, &is);
+                    continue;
+                },
+            };
+            match message {
+                Message::Shutdown(ack) => break ack,
+                Message::Update(name) => buffer = self.publish_reading(name, buffer, &is, None),
+                Message::Rename(name, topic) => {
+                    let old_topic = self.topic(name);
+                    if old_topic == topic || !self.instruments.instrument_names().contains(&name) {
+                        continue;
                     }
+                    self.topics.insert(name, topic);
+                    let pubopt = (self.pubopt)(name);
+                    if pubopt.is_retain() {
+                        // Clear the retained message
+                        self.publish(old_topic, Vec::new(), pubopt);
+                    }
+                    // Announce current value under the new topic
+                    self.forget(name);
+                    buffer = self.publish_reading(name, buffer, &is, None);
+                },
+                Message::Clear(name) => {
+                    let topic = self.topic(name);
+                    self.publish(topic, Vec::new(), PubOpt::retain());
+                    // Next update should be published in full even if the value is the same
+                    self.forget(name);
                 },
-                Err(err) => panic!(err),
+                // The handle might have been dropped by now
+                Message::Flush(ack) => { let _ = ack.send(()); },
+            }
+        };
+        queue.set_closed(true);
+        // The handle might have been dropped by now
+        let _ = ack.send(());
+    }
+
+    /// Runs the publisher in a new thread
+    ///
+    /// This wraps the typical lifecycle of spawning a thread to [`Publisher#run`] the
+    /// publisher, shutting it down and joining the thread:
+    ///
+    /// ```norun
+    /// let running = publisher.spawn(rapt::ser::JsonSerializer);
+    /// // ...
+    /// let client = running.stop_and_join().unwrap().into_inner();
+    /// ```
+    ///
+    /// [`Publisher#run`]: struct.Publisher.html#method.run
+    pub fn spawn<IS, S>(mut self, is: IS) -> RunningPublisher<TF, I, C>
+           where TF: Send + 'static, I: Send + 'static, C: Send + 'static, IS: Send + 'static,
+                 for<'a> IS: InstantiateSerializer<'a, Vec<u8>, Target=S>,
+                 S: IntoWriter<Vec<u8>>, for<'a> &'a mut S: Serializer {
+        let handle = self.handle();
+        let thread = thread::spawn(move || {
+            self.run(is);
+            self
+        });
+        RunningPublisher { handle, thread }
+    }
+
+    /// Returns instrument's topic
+    fn topic(&self, name: &'static str) -> String {
+        match self.topics.get(name) {
+            Some(topic) => topic.clone(),
+            None => self.topic_formatter.format_topic(name),
+        }
+    }
+
+    /// Publishes instrument's reading, returns the buffer back
+    ///
+    /// Repeated readings and readings held back by flap damping are not published. If the
+    /// reading with the `settled` checksum has been held back and is still current, it is.
+    fn publish_reading<IS, S>(&mut self, name: &'static str, mut buffer: Vec<u8>, is: &IS,
+                              settled: Option<u64>) -> Vec<u8>
+           where for<'a> IS: InstantiateSerializer<'a, Vec<u8>, Target=S>,
+                 S: IntoWriter<Vec<u8>>, for<'a> &'a mut S: Serializer {
+        buffer.clear();
+        let mut ser = is.instantiate_serializer(buffer);
+        // The error borrows the serializer, so it is formatted right away
+        let result = self.instruments.serialize_reading(name, &mut ser)
+            .map(|_| ()).map_err(|err| format!("{:?}", err));
+        buffer = ser.into_writer();
+        if let Err(err) = result {
+            match self.serialization_errors {
+                SerializationErrorPolicy::Skip => {
+                    self.receiver.0.skipped_readings.fetch_add(1, Ordering::Relaxed);
+                    return buffer;
+                },
+                SerializationErrorPolicy::FailFast => panic!("can't serialize {}: {}", name, err),
+            }
+        }
+
+        // Publishing might block for a long time if the broker is misbehaving,
+        // don't start it if the publisher is being shut down
+        if self.receiver.0.shutdown_requested() {
+            return buffer;
+        }
+        if !self.is_changed(name, &buffer, is, settled) {
+            return buffer;
+        }
+        let topic = self.topic(name);
+        let published = match self.patch(name, &buffer) {
+            // Patches only make sense following the previous message, so they are never retained
+            Some(patch) => self.publish(topic, patch, PubOpt::at_least_once()),
+            None => {
+                // The client takes the payload by value, so the buffer is handed over rather
+                // than copied, and replaced with a spare one large enough for a similar reading
+                let spare = Vec::with_capacity(buffer.capacity());
+                let payload = mem::replace(&mut buffer, spare);
+                let pubopt = (self.pubopt)(name);
+                self.publish(topic, payload, pubopt)
+            },
+        };
+        if !published {
+            // Consumers haven't got this reading, so the next one shouldn't be skipped
+            // as a repeated one or published as a patch from it
+            self.forget(name);
+        }
+        buffer
+    }
+
+    /// Hands a message over to the client, returns `false` if it has failed to publish it
+    ///
+    /// Failures are counted (see [`Handle#failed_publishes`]) rather than stopping the
+    /// publisher, as the broker might be back by the next message.
+    ///
+    /// [`Handle#failed_publishes`]: struct.Handle.html#method.failed_publishes
+    fn publish(&mut self, topic: String, payload: Vec<u8>, pubopt: PubOpt) -> bool {
+        let published = self.client.publish(topic, payload, pubopt).is_ok();
+        if !published {
+            self.receiver.0.failed_publishes.fetch_add(1, Ordering::Relaxed);
+        }
+        published
+    }
+
+    /// Returns `true` if the reading is neither a repeated one nor held back by flap damping
+    ///
+    /// A reading held back with the `settled` checksum is published once it settles, unless
+    /// the value has changed since. A changed one is checked by flap damping as usual.
+    fn is_changed<IS, S>(&mut self, name: &'static str, payload: &[u8], is: &IS, settled: Option<u64>) -> bool
+           where for<'a> IS: InstantiateSerializer<'a, Vec<u8>, Target=S>,
+                 S: IntoWriter<Vec<u8>>, for<'a> &'a mut S: Serializer {
+        let changed = self.dedup.is_changed(&self.instruments, name, payload, is);
+        match (self.damping.as_mut(), self.dedup.last_checksum(name)) {
+            (Some(damping), Some(checksum)) if settled == Some(checksum) => {
+                damping.settle(name, checksum, Instant::now());
+                true
+            },
+            (Some(damping), Some(checksum)) => changed && damping.admit(name, checksum, Instant::now()),
+            _ => changed,
+        }
+    }
+
+    /// Publishes readings that have been held back by flap damping and have settled since,
+    /// returns the buffer back
+    fn publish_settled<IS, S>(&mut self, mut buffer: Vec<u8>, is: &IS) -> Vec<u8>
+           where for<'a> IS: InstantiateSerializer<'a, Vec<u8>, Target=S>,
+                 S: IntoWriter<Vec<u8>>, for<'a> &'a mut S: Serializer {
+        let settled = match self.damping {
+            Some(ref mut damping) => damping.settled(Instant::now()),
+            None => return buffer,
+        };
+        for (name, checksum) in settled {
+            buffer = self.publish_reading(name, buffer, is, Some(checksum));
+        }
+        buffer
+    }
+
+    /// Forgets what has been published for the instrument, so that its next reading
+    /// is published in full
+    fn forget(&mut self, name: &'static str) {
+        self.dedup.forget(name);
+        if let Some(ref mut damping) = self.damping {
+            damping.forget(name);
+        }
+        #[cfg(feature = "serde_json")]
+        {
+            if let Some(ref mut published) = self.published {
+                published.remove(name);
             }
         }
     }
 
+    /// Returns a patch from the last published reading to the current one, if
+    /// publishing diffs and there is a previous reading
+    ///
+    /// The patch is computed from `payload`, the reading checked for changes, so both
+    /// reflect the same value. Payloads that aren't JSON are published in full.
+    #[cfg(feature = "serde_json")]
+    fn patch(&mut self, name: &'static str, payload: &[u8]) -> Option<Vec<u8>> {
+        let published = self.published.as_mut()?;
+        let reading: serde_json::Value = match serde_json::from_slice(payload) {
+            Ok(reading) => reading,
+            Err(_) => {
+                published.remove(name);
+                return None;
+            },
+        };
+        let previous = published.insert(name, reading.clone())?;
+        serde_json::to_vec(&super::patch::diff(&previous, &reading)).ok()
+    }
+
+    #[cfg(not(feature = "serde_json"))]
+    fn patch(&mut self, _name: &'static str, _payload: &[u8]) -> Option<Vec<u8>> {
+        None
+    }
+
     /// Consumes `Publisher` and returns underlying `Client`
-    pub fn into_inner(self) -> client::Client {
+    pub fn into_inner(self) -> C {
         self.client
     }
 }
 
+impl<TF: TopicFormatter, I: Instruments<Handle>> Publisher<TF, I> {
+    /// Connects to an MQTT broker and creates a new MQTT publisher
+    ///
+    /// This is a convenience constructor for the cases when there's no need to
+    /// configure the client separately. Client `options` are used to connect to the
+    /// broker at `address` over the network configured with `network`. To connect to a
+    /// secured broker, configure TLS on `network`.
+    ///
+    /// The rest of the arguments are the same as for [`Publisher#new`]. Connection
+    /// errors (including TLS handshake and certificate verification failures) are
+    /// returned as they are.
+    ///
+    /// [`Publisher#new`]: struct.Publisher.html#method.new
+    pub fn connect<A: ToSocketAddrs>(address: A, options: client::ClientOptions, network: NetworkOptions,
+                                     topic_formatter: TF, instruments: I, retain: bool) -> client::Result<Self> {
+        let client = options.connect(address, network)?;
+        Ok(Publisher::new(topic_formatter, client, instruments, retain))
+    }
+}
+
+/// Publish options of retained messages
+fn retained(_name: &'static str) -> PubOpt {
+    PubOpt::retain()
+}
+
+/// Publish options of messages that aren't retained
+fn not_retained(_name: &'static str) -> PubOpt {
+    PubOpt::at_least_once()
+}
+
+/// [`Publisher`] configuration
+///
+/// Only the client and instruments are required, every other option has a default:
+///
+/// ```norun
+/// let publisher = mqtt::PublisherBuilder::new(client, instruments)
+///     .retain(true)
+///     .backpressure(mqtt::Backpressure::DropOldest(1024))
+///     .build();
+/// ```
+///
+/// [`Publisher`]: struct.Publisher.html
+pub struct PublisherBuilder<TF: TopicFormatter, I: Instruments<Handle>, C: PublishClient = client::Client> {
+    topic_formatter: TF,
+    client: C,
+    instruments: I,
+    pubopt: fn(&'static str) -> PubOpt,
+    backpressure: Backpressure,
+    dedup: Dedup,
+    damping: Option<FlapDamping>,
+    serialization_errors: SerializationErrorPolicy,
+    #[cfg(feature = "serde_json")]
+    diffs: bool,
+}
+
+impl<I: Instruments<Handle>, C: PublishClient> PublisherBuilder<(), I, C> {
+    /// Starts configuring a publisher of `instruments` over a *connected* client
+    ///
+    /// By default, instrument names are used as topics, messages are not retained,
+    /// the update queue is unbounded, only instrument values are compared to detect
+    /// repeated messages and readings that can't be serialized are skipped.
+    pub fn new(client: C, instruments: I) -> Self {
+        PublisherBuilder {
+            topic_formatter: (),
+            client,
+            instruments,
+            pubopt: not_retained,
+            backpressure: Backpressure::default(),
+            dedup: Dedup::default(),
+            damping: None,
+            serialization_errors: SerializationErrorPolicy::default(),
+            #[cfg(feature = "serde_json")]
+            diffs: false,
+        }
+    }
+}
+
+impl<TF: TopicFormatter, I: Instruments<Handle>, C: PublishClient> PublisherBuilder<TF, I, C> {
+    /// Sets the topic formatter
+    pub fn topic_formatter<T: TopicFormatter>(self, topic_formatter: T) -> PublisherBuilder<T, I, C> {
+        PublisherBuilder {
+            topic_formatter,
+            client: self.client,
+            instruments: self.instruments,
+            pubopt: self.pubopt,
+            backpressure: self.backpressure,
+            dedup: self.dedup,
+            damping: self.damping,
+            serialization_errors: self.serialization_errors,
+            #[cfg(feature = "serde_json")]
+            diffs: self.diffs,
+        }
+    }
+
+    /// Sets whether messages should be retained
+    ///
+    /// Retained messages are published with `PubOpt::retain()`, others with
+    /// `PubOpt::at_least_once()`. This replaces options set with [`publish_options`].
+    ///
+    /// [`publish_options`]: #method.publish_options
+    pub fn retain(mut self, retain: bool) -> Self {
+        self.pubopt = if retain { retained } else { not_retained };
+        self
+    }
+
+    /// Sets the options readings of every instrument are published with
+    ///
+    /// `pubopt` is called with instrument names, which allows configuring any option
+    /// supported by the client, such as QoS or retaining, per instrument. This replaces
+    /// the options set with [`retain`]. If the options of an instrument include retaining,
+    /// the retained message is cleared when the instrument's topic is changed.
+    ///
+    /// ```norun
+    /// let publisher = mqtt::PublisherBuilder::new(client, instruments)
+    ///     .publish_options(|name| if name == "status" { PubOpt::retain() } else { PubOpt::at_most_once() })
+    ///     .build();
+    /// ```
+    ///
+    /// [`retain`]: #method.retain
+    pub fn publish_options(mut self, pubopt: fn(&'static str) -> PubOpt) -> Self {
+        self.pubopt = pubopt;
+        self
+    }
+
+    /// Sets the backpressure strategy, see [`Backpressure`]
+    ///
+    /// Panics if the capacity of a bounded queue is zero.
+    ///
+    /// [`Backpressure`]: enum.Backpressure.html
+    pub fn backpressure(mut self, backpressure: Backpressure) -> Self {
+        match backpressure {
+            Backpressure::DropOldest(0) | Backpressure::DropNewest(0) | Backpressure::Block(0) =>
+                panic!("backpressure capacity must be positive"),
+            _ => (),
+        }
+        self.backpressure = backpressure;
+        self
+    }
+
+    /// Sets the way repeated messages are detected, see [`Publisher#set_dedup`]
+    ///
+    /// [`Publisher#set_dedup`]: struct.Publisher.html#method.set_dedup
+    pub fn dedup(mut self, dedup: Dedup) -> Self {
+        self.dedup = dedup;
+        self
+    }
+
+    /// Enables flap damping, see [`FlapDamping`]
+    ///
+    /// Readings held back are published once they settle, even if there are no further
+    /// updates. [`Handle#flush`] doesn't wait for them. Disabled by default.
+    ///
+    /// ```norun
+    /// let publisher = mqtt::PublisherBuilder::new(client, instruments)
+    ///     .flap_damping(FlapDamping::new(Duration::from_secs(60), Duration::from_secs(10)))
+    ///     .build();
+    /// ```
+    ///
+    /// [`FlapDamping`]: ../dedup/struct.FlapDamping.html
+    /// [`Handle#flush`]: struct.Handle.html#method.flush
+    pub fn flap_damping(mut self, damping: FlapDamping) -> Self {
+        self.damping = Some(damping);
+        self
+    }
+
+    /// Sets what happens when a reading can't be serialized, see [`SerializationErrorPolicy`]
+    ///
+    /// [`SerializationErrorPolicy`]: enum.SerializationErrorPolicy.html
+    pub fn serialization_error_policy(mut self, policy: SerializationErrorPolicy) -> Self {
+        self.serialization_errors = policy;
+        self
+    }
+
+    /// Sets whether changes should be published as JSON patches
+    ///
+    /// _Only available if `serde_json` feature is enabled._
+    ///
+    /// When enabled, the first reading of every instrument is published in full, and
+    /// every next one as a [JSON Patch] (RFC 6902) from the previous one, which is a JSON
+    /// array, so consumers can tell patches from full readings (JSON objects). Patches are
+    /// computed between published payloads, so they require a JSON serializer (readings
+    /// serialized otherwise are always published in full), and are never retained.
+    /// Disabled by default.
+    ///
+    /// [JSON Patch]: https://tools.ietf.org/html/rfc6902
+    #[cfg(feature = "serde_json")]
+    pub fn publish_diffs(mut self, diffs: bool) -> Self {
+        self.diffs = diffs;
+        self
+    }
+
+    /// Creates the publisher, wiring it into instruments
+    pub fn build(self) -> Publisher<TF, I, C> {
+        let mut instruments = self.instruments;
+        let queue = Arc::new(Queue::new(self.backpressure));
+        instruments.wire_listener(Handle { queue: queue.clone() });
+        Publisher {
+            topic_formatter: self.topic_formatter,
+            client: self.client,
+            instruments,
+            pubopt: self.pubopt,
+            dedup: self.dedup,
+            damping: self.damping,
+            serialization_errors: self.serialization_errors,
+            #[cfg(feature = "serde_json")]
+            published: if self.diffs { Some(HashMap::new()) } else { None },
+            topics: HashMap::new(),
+            receiver: Receiver(queue),
+        }
+    }
+}
+
+/// [`Publisher`] running in its own thread
+///
+/// Created by [`Publisher#spawn`].
+///
+/// [`Publisher`]: struct.Publisher.html
+/// [`Publisher#spawn`]: struct.Publisher.html#method.spawn
+pub struct RunningPublisher<TF: TopicFormatter, I: Instruments<Handle>, C: PublishClient = client::Client> {
+    handle: Handle,
+    thread: thread::JoinHandle<Publisher<TF, I, C>>,
+}
+
+impl<TF: TopicFormatter, I: Instruments<Handle>, C: PublishClient> RunningPublisher<TF, I, C> {
+    /// Handle to the running `Publisher`
+    pub fn handle(&self) -> Handle {
+        self.handle.clone()
+    }
+
+    /// Shuts the publisher down and waits for its thread to finish
+    ///
+    /// Returns the publisher back (so that the client can be taken out of it with
+    /// [`Publisher#into_inner`]), or an error if the thread has panicked.
+    ///
+    /// [`Publisher#into_inner`]: struct.Publisher.html#method.into_inner
+    pub fn stop_and_join(self) -> thread::Result<Publisher<TF, I, C>> {
+        self.handle.shutdown();
+        self.thread.join()
+    }
+}
+
 /// Running [`Publisher`] handle
 ///
 /// [`Publisher`]: struct.Publisher.html
 #[derive(Clone)]
 pub struct Handle {
-    sender: mpsc::Sender<Message>,
+    queue: Arc<Queue>,
 }
 
 impl Handle {
+    /// Returns the number of messages (mostly updates) waiting for the publisher
+    ///
+    /// A growing backlog means the publisher (or the broker) can't keep up with updates.
+    /// See [`Backpressure`] for ways to bound it.
+    ///
+    /// [`Backpressure`]: enum.Backpressure.html
+    pub fn queue_len(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Returns the number of readings skipped because they couldn't be serialized
+    ///
+    /// See [`SerializationErrorPolicy`].
+    ///
+    /// [`SerializationErrorPolicy`]: enum.SerializationErrorPolicy.html
+    pub fn skipped_readings(&self) -> usize {
+        self.queue.skipped_readings.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of messages the client has failed to publish
+    ///
+    /// The publisher keeps running when publishing fails, and publishes the next reading
+    /// of the instrument in full even if it is the same.
+    pub fn failed_publishes(&self) -> usize {
+        self.queue.failed_publishes.load(Ordering::Relaxed)
+    }
+
     /// Shutdown the publisher
+    ///
+    /// Shutdown requests take priority over queued updates, which are discarded.
+    /// Blocks until [`Publisher#run`] has returned. If the publisher has already been shut
+    /// down or dropped, returns immediately.
+    ///
+    /// If [`Publisher#run`] hasn't started yet, this waits for it to start (it then returns
+    /// right away) or for the publisher to be dropped. So this blocks forever if the publisher
+    /// is kept around but never run; use [`Handle#shutdown_timeout`] if that is possible.
+    ///
+    /// Please note that a publish that is already in progress can't be interrupted,
+    /// so if the broker is misbehaving, this might block for a long time. Consider using
+    /// [`Handle#shutdown_timeout`] if that's a concern.
+    ///
+    /// [`Publisher#run`]: struct.Publisher.html#method.run
+    /// [`Handle#shutdown_timeout`]: struct.Handle.html#method.shutdown_timeout
     pub fn shutdown(&self) {
-        let _ = self.sender.send(Message::Shutdown).unwrap();
+        let (ack_sender, ack_receiver) = mpsc::channel();
+        if self.queue.send(Message::Shutdown(ack_sender)).is_ok() {
+            let _ = ack_receiver.recv();
+        }
+    }
+
+    /// Shutdown the publisher, waiting for no longer than `timeout`
+    ///
+    /// Same as [`Handle#shutdown`], but returns `false` if [`Publisher#run`] hasn't
+    /// returned in time (most likely, because it is blocked publishing a message).
+    /// It will still return as soon as the publish completes.
+    ///
+    /// [`Handle#shutdown`]: struct.Handle.html#method.shutdown
+    /// [`Publisher#run`]: struct.Publisher.html#method.run
+    pub fn shutdown_timeout(&self, timeout: Duration) -> bool {
+        let (ack_sender, ack_receiver) = mpsc::channel();
+        match self.queue.send(Message::Shutdown(ack_sender)) {
+            Ok(()) => ack_receiver.recv_timeout(timeout).is_ok(),
+            Err(_) => true,
+        }
+    }
+
+    /// Waits until the publisher has processed all messages queued before this call
+    ///
+    /// This is a synchronization point for "publish, then confirm" workflows: once it
+    /// returns, readings of all updates made before it have been handed over to the
+    /// client (or skipped as repeated ones). Unlike [`Handle#shutdown`], the publisher
+    /// keeps running afterwards.
+    ///
+    /// If the publisher has already been shut down or dropped, returns immediately. If
+    /// [`Publisher#run`] hasn't started yet, waits for it to start. Updates dropped according
+    /// to [`Backpressure`] strategy are not waited for, and a pending shutdown request discards
+    /// queued messages, releasing the wait.
+    ///
+    /// [`Handle#shutdown`]: struct.Handle.html#method.shutdown
+    /// [`Publisher#run`]: struct.Publisher.html#method.run
+    /// [`Backpressure`]: enum.Backpressure.html
+    pub fn flush(&self) {
+        let (ack_sender, ack_receiver) = mpsc::channel();
+        if self.queue.send(Message::Flush(ack_sender)).is_ok() {
+            let _ = ack_receiver.recv();
+        }
+    }
+
+    /// Clears instrument's retained message
+    ///
+    /// Publishes an empty retained message to instrument's topic, which makes the broker
+    /// delete the retained message, so that a decommissioned instrument doesn't linger
+    /// with stale data. If the instrument is updated later on, its reading is published
+    /// again.
+    ///
+    /// This is processed in order with updates, and is discarded if the publisher
+    /// is not running anymore.
+    pub fn clear(&self, name: &'static str) {
+        let _ = self.queue.send(Message::Clear(name));
+    }
+
+    /// Changes instrument's topic
+    ///
+    /// Instrument's topic is determined by [`TopicFormatter`] and can be changed later,
+    /// for example when the instrument identity is only known after startup. The current
+    /// reading is published to the new topic right away. If the instrument's messages
+    /// are retained, the retained message on the old topic is cleared (an empty retained
+    /// message is published to it).
+    ///
+    /// The change is processed in order with updates, and is discarded if the publisher
+    /// is not running anymore or if there's no instrument with such name.
+    ///
+    /// [`TopicFormatter`]: trait.TopicFormatter.html
+    pub fn set_topic_name<T: Into<String>>(&self, name: &'static str, topic: T) {
+        let _ = self.queue.send(Message::Rename(name, topic.into()));
     }
 }
 
 /// Very importantly, [`Handle`] is a [`Listener`],
 ///
+/// Updates are queued according to publisher's [`Backpressure`] strategy, and are
+/// discarded if the publisher is not running anymore.
+///
 /// [`Handle`]: struct.Handle.html
 /// [`Listener`]: ../trait.Listener.html
+/// [`Backpressure`]: enum.Backpressure.html
 impl Listener for Handle {
     fn instrument_updated(&self, name: &'static str) {
-        let _ = self.sender.send(Message::Update(name)).unwrap();
+        let _ = self.queue.send(Message::Update(name));
     }
 }
\ No newline at end of file
@@ -0,0 +1,116 @@
+// Copyright 2017 All Contributors (see CONTRIBUTORS file)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+//
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+
+//! # JSON patches
+//!
+//! _This module is only present if `serde_json` feature is enabled._
+//!
+//! When a large reading changes only slightly, sending the whole reading again is
+//! wasteful. [`diff`] computes a [JSON Patch] (RFC 6902) that turns one reading into
+//! another, so that consumers holding the previous reading can apply it instead.
+//!
+//! [`diff`]: fn.diff.html
+//! [JSON Patch]: https://tools.ietf.org/html/rfc6902
+
+use serde_json::{Map, Value};
+
+/// Computes a JSON Patch turning `from` into `to`
+///
+/// Objects are compared key by key, recursively. Any other values (including arrays)
+/// are replaced as a whole if they differ. Returns an empty array if the values are equal.
+///
+/// ```rust
+/// extern crate rapt;
+/// #[macro_use]
+/// extern crate serde_json;
+///
+/// use rapt::patch::diff;
+///
+/// fn main() {
+///     let from = json!({"value": {"status": 0, "name": "http"}});
+///     let to = json!({"value": {"status": 1, "name": "http"}});
+///     assert_eq!(diff(&from, &to), json!([{"op": "replace", "path": "/value/status", "value": 1}]));
+/// }
+/// ```
+pub fn diff(from: &Value, to: &Value) -> Value {
+    let mut operations = vec![];
+    diff_at(&mut String::new(), from, to, &mut operations);
+    Value::Array(operations)
+}
+
+fn diff_at(path: &mut String, from: &Value, to: &Value, operations: &mut Vec<Value>) {
+    match (from, to) {
+        (Value::Object(from), Value::Object(to)) => {
+            for (key, from_value) in from {
+                let len = push_segment(path, key);
+                match to.get(key) {
+                    Some(to_value) => diff_at(path, from_value, to_value, operations),
+                    None => operations.push(operation("remove", path, None)),
+                }
+                path.truncate(len);
+            }
+            for (key, to_value) in to.iter().filter(|&(key, _)| !from.contains_key(key)) {
+                let len = push_segment(path, key);
+                operations.push(operation("add", path, Some(to_value)));
+                path.truncate(len);
+            }
+        },
+        (from, to) if from != to => operations.push(operation("replace", path, Some(to))),
+        _ => (),
+    }
+}
+
+/// Appends an escaped JSON Pointer segment to `path`, returns the previous length
+fn push_segment(path: &mut String, key: &str) -> usize {
+    let len = path.len();
+    path.push('/');
+    path.push_str(&key.replace('~', "~0").replace('/', "~1"));
+    len
+}
+
+fn operation(op: &str, path: &str, value: Option<&Value>) -> Value {
+    let mut operation = Map::new();
+    operation.insert("op".into(), op.into());
+    operation.insert("path".into(), path.into());
+    if let Some(value) = value {
+        operation.insert("value".into(), value.clone());
+    }
+    Value::Object(operation)
+}
@@ -0,0 +1,174 @@
+// Copyright 2017 All Contributors (see CONTRIBUTORS file)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+//
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+
+//! # Polling
+//!
+//! Values that change outside of rapt (such as a counter maintained by a C library)
+//! don't produce update events, so listeners can't be notified about their changes.
+//! [`Poller`] bridges them into rapt's notification model: it periodically reads such
+//! values and notifies the listener whenever one of them has changed.
+//!
+//! Polled values are typically exposed on the board with [`LazyInstrument`] or
+//! [`AtomicView`], under the same names as they are watched by the poller.
+//!
+//! [`Poller`]: struct.Poller.html
+//! [`LazyInstrument`]: ../lazy/struct.LazyInstrument.html
+//! [`AtomicView`]: ../atomic/struct.AtomicView.html
+
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use super::Listener;
+
+/// A watched value, returns `true` if it has changed since the last poll
+type Watch = Box<dyn FnMut() -> bool + Send>;
+
+/// Periodically polls values and notifies the listener about their changes
+///
+/// ```rust
+/// extern crate rapt;
+///
+/// use std::sync::Arc;
+/// use std::sync::atomic::{AtomicU64, Ordering};
+/// use std::sync::mpsc;
+/// use std::time::Duration;
+/// use rapt::poll::Poller;
+///
+/// fn main() {
+///     let (tx, rx) = mpsc::channel();
+///     let counter = Arc::new(AtomicU64::new(0));
+///     let counter_ = counter.clone();
+///
+///     let mut poller = Poller::new(Duration::from_millis(100), tx);
+///     poller.watch("counter", move || counter_.load(Ordering::Relaxed));
+///
+///     poller.poll();
+///     counter.fetch_add(1, Ordering::Relaxed);
+///     poller.poll();
+///     assert_eq!(rx.try_recv(), Ok("counter"));
+/// }
+/// ```
+pub struct Poller<L: Listener> {
+    interval: Duration,
+    listener: L,
+    watches: Vec<(&'static str, Watch)>,
+}
+
+impl<L: Listener> Poller<L> {
+    /// Creates a new poller notifying `listener`, polling every `interval` once spawned
+    pub fn new(interval: Duration, listener: L) -> Self {
+        Poller { interval, listener, watches: vec![] }
+    }
+
+    /// Watches a value, notifying the listener with `name` whenever it is not equal
+    /// to the value observed during the previous poll
+    ///
+    /// The first observed value is not considered a change.
+    pub fn watch<T, F>(&mut self, name: &'static str, f: F) -> &mut Self
+        where T: PartialEq + Send + 'static, F: Fn() -> T + Send + 'static {
+        self.watch_with(name, f, |previous: &T, current: &T| previous != current)
+    }
+
+    /// Watches a value, notifying the listener with `name` whenever `changed` returns `true`
+    /// for the values observed during the previous and the current polls
+    ///
+    /// This allows to ignore insignificant changes (for example, floating point noise).
+    /// The first observed value is not considered a change.
+    pub fn watch_with<T, F, C>(&mut self, name: &'static str, f: F, changed: C) -> &mut Self
+        where T: Send + 'static, F: Fn() -> T + Send + 'static, C: Fn(&T, &T) -> bool + Send + 'static {
+        let mut last: Option<T> = None;
+        self.watches.push((name, Box::new(move || {
+            let current = f();
+            let result = match last {
+                Some(ref previous) => changed(previous, &current),
+                None => false,
+            };
+            // Only move the reference point on changes, so that accumulated
+            // insignificant changes are eventually noticed
+            if result || last.is_none() {
+                last = Some(current);
+            }
+            result
+        })));
+        self
+    }
+
+    /// Polls all watched values once, notifying the listener about changed ones
+    pub fn poll(&mut self) {
+        for &mut (name, ref mut watch) in self.watches.iter_mut() {
+            if watch() {
+                self.listener.instrument_updated(name);
+            }
+        }
+    }
+
+    /// Polls in a new thread, every `interval`, until stopped
+    pub fn spawn(mut self) -> PollerHandle where L: Send + 'static {
+        let (stop, stopped) = mpsc::channel();
+        let thread = thread::spawn(move || {
+            while let Err(mpsc::RecvTimeoutError::Timeout) = stopped.recv_timeout(self.interval) {
+                self.poll();
+            }
+        });
+        PollerHandle { stop, thread }
+    }
+}
+
+/// Handle to a [`Poller`] running in its own thread
+///
+/// Created by [`Poller#spawn`].
+///
+/// [`Poller`]: struct.Poller.html
+/// [`Poller#spawn`]: struct.Poller.html#method.spawn
+pub struct PollerHandle {
+    stop: mpsc::Sender<()>,
+    thread: thread::JoinHandle<()>,
+}
+
+impl PollerHandle {
+    /// Stops polling and waits for the thread to finish
+    ///
+    /// Returns an error if the thread has panicked.
+    pub fn stop(self) -> thread::Result<()> {
+        let _ = self.stop.send(());
+        self.thread.join()
+    }
+}
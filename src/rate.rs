@@ -0,0 +1,205 @@
+// Copyright 2017 All Contributors (see CONTRIBUTORS file)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+//
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! # Update rate instruments
+//!
+//! Sometimes the cadence of updates is an observation on its own: a subsystem that suddenly
+//! reports too often, or has gone quiet. [`RateInstrument`] counts events and keeps the
+//! instants of recent ones, reporting how many of them per second occurred within a
+//! sliding window.
+//!
+//! [`RateInstrument`]: struct.RateInstrument.html
+
+use serde::{Serialize, Serializer, Deserializer};
+#[cfg(feature = "timestamp_instruments")]
+use chrono::prelude::*;
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, RwLockReadGuard, LockResult};
+use std::time::{Duration, Instant};
+
+use super::{Instrument, LastUpdated, Listener, Reset, Restore, Timestamp, UpdateError};
+#[cfg(feature = "timestamp_instruments")]
+use super::UpdatedSince;
+
+/// An instrument counting events and their rate over a sliding window
+///
+/// Every [`record`] is a regular update of the counter: the timestamp is bumped and the
+/// listener is notified. Its instant is kept until it falls out of the window, so memory
+/// use is proportional to the number of events within the window.
+///
+/// Serializes the same way as [`Instrument`] does, with the count as `value` and an
+/// additional `rate` field (events per second within the window).
+///
+/// ```rust
+/// extern crate rapt;
+///
+/// use std::time::Duration;
+/// use rapt::rate::RateInstrument;
+///
+/// fn main() {
+///     let reconnects = RateInstrument::<()>::new(Duration::from_secs(60));
+///     reconnects.record().unwrap();
+///     assert_eq!(*reconnects.read().unwrap(), 1);
+///     assert!(reconnects.rate() > 0.0);
+/// }
+/// ```
+///
+/// [`record`]: struct.RateInstrument.html#method.record
+/// [`Instrument`]: ../struct.Instrument.html
+#[derive(Clone)]
+pub struct RateInstrument<L: Listener> {
+    instrument: Instrument<u64, L>,
+    window: Duration,
+    instants: Arc<Mutex<VecDeque<Instant>>>,
+}
+
+impl<L: Listener> RateInstrument<L> {
+    /// Creates a new instrument computing the rate over a given window
+    ///
+    /// Panics if `window` is zero.
+    pub fn new(window: Duration) -> Self {
+        assert!(window > Duration::from_secs(0), "window must be positive");
+        RateInstrument {
+            instrument: Instrument::new(0),
+            window,
+            instants: Arc::new(Mutex::new(VecDeque::new())),
+        }
+    }
+
+    metadata_accessors!(instrument.metadata);
+    wrapped_wiring!(instrument, L);
+
+    /// Returns the window the rate is computed over
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+
+    /// Returns `true` if an event has ever been recorded
+    pub fn has_been_updated(&self) -> bool {
+        self.instrument.has_been_updated()
+    }
+
+    /// Returns `true` if a thread has panicked while updating the instrument
+    pub fn is_poisoned(&self) -> bool {
+        self.instrument.is_poisoned()
+    }
+
+    /// Thread-safe reader of the total number of recorded events
+    pub fn read(&self) -> LockResult<RwLockReadGuard<'_, u64>> {
+        self.instrument.read()
+    }
+
+    /// Records an event
+    pub fn record(&self) -> Result<(), UpdateError> {
+        self.record_at(Instant::now())
+    }
+
+    /// Records an event that happened at a given instant
+    ///
+    /// Instants are expected to be monotonic; an instant preceding already recorded
+    /// ones is counted, but may be dropped from the window earlier than it should be.
+    pub fn record_at(&self, at: Instant) -> Result<(), UpdateError> {
+        {
+            let mut instants = self.instants.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            instants.push_back(at);
+            self.expire(&mut instants, at);
+        }
+        self.instrument.update(|v| *v += 1)
+    }
+
+    /// Returns the number of events per second within the window ending now
+    pub fn rate(&self) -> f64 {
+        self.rate_at(Instant::now())
+    }
+
+    /// Returns the number of events per second within the window ending at a given instant
+    pub fn rate_at(&self, at: Instant) -> f64 {
+        let mut instants = self.instants.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        self.expire(&mut instants, at);
+        let window = self.window.as_secs() as f64 + f64::from(self.window.subsec_nanos()) / 1e9;
+        instants.len() as f64 / window
+    }
+
+    fn expire(&self, instants: &mut VecDeque<Instant>, at: Instant) {
+        while let Some(&oldest) = instants.front() {
+            if at.duration_since(oldest) < self.window {
+                break;
+            }
+            instants.pop_front();
+        }
+    }
+}
+
+/// Resets the count to zero and forgets recent events
+impl<L: Listener> Reset for RateInstrument<L> {
+    fn reset(&self) -> Result<(), UpdateError> {
+        self.instants.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).clear();
+        self.instrument.reset()
+    }
+}
+
+/// Restores the count, see [`Restore`]. Recent events are not restored, so the rate starts from zero.
+///
+/// [`Restore`]: ../trait.Restore.html
+impl<L: Listener> Restore for RateInstrument<L> {
+    fn restore<'de, D: Deserializer<'de>>(&self, reading: D) -> Result<(), D::Error> {
+        self.instrument.restore(reading)
+    }
+}
+
+impl<L: Listener> Serialize for RateInstrument<L> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where
+        S: Serializer {
+        self.instrument.serialize_with_field(serializer, Some(("rate", self.rate())))
+    }
+}
+
+#[cfg(feature = "timestamp_instruments")]
+impl<L: Listener> UpdatedSince<DateTime<Utc>> for RateInstrument<L> {
+    fn updated_since(&self, since: &DateTime<Utc>) -> bool {
+        self.instrument.updated_since(since)
+    }
+}
+
+impl<L: Listener> LastUpdated for RateInstrument<L> {
+    fn last_update_at(&self) -> Option<Timestamp> {
+        self.instrument.last_update_at()
+    }
+}
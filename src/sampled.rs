@@ -0,0 +1,189 @@
+// Copyright 2017 All Contributors (see CONTRIBUTORS file)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+//
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+
+//! # Sampled instruments
+//!
+//! For extremely frequent events, even taking the instrument's lock on every occurrence
+//! might be too much. [`SampledInstrument`] trades precision for throughput by only
+//! applying every Nth update, counting them with an atomic counter.
+//!
+//! This is count-based sampling, unlike [`Instrument#with_min_notify_interval`] which
+//! applies every update but limits notifications in time.
+//!
+//! [`SampledInstrument`]: struct.SampledInstrument.html
+//! [`Instrument#with_min_notify_interval`]: ../struct.Instrument.html#method.with_min_notify_interval
+
+use serde::{Serialize, Serializer, Deserializer};
+use serde::de::DeserializeOwned;
+#[cfg(feature = "timestamp_instruments")]
+use chrono::prelude::*;
+
+use std::sync::{Arc, RwLockReadGuard, LockResult};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use super::{Instrument, LastUpdated, Listener, Reset, Restore, Timestamp, UpdateError};
+#[cfg(feature = "timestamp_instruments")]
+use super::UpdatedSince;
+
+/// An instrument applying only one in every `sample_rate` updates
+///
+/// Applied updates are regular updates: the timestamp is bumped and the listener is
+/// notified. Skipped ones don't even lock the value. The first update is always applied.
+///
+/// Serializes the same way as [`Instrument`] does, with an additional `sample_rate`
+/// field, so that consumers can scale counters accordingly.
+///
+/// ```rust
+/// extern crate rapt;
+///
+/// use rapt::sampled::SampledInstrument;
+///
+/// fn main() {
+///     let packets = SampledInstrument::<u64, ()>::new(0, 100);
+///     for _ in 0..1000 {
+///         packets.update(|v| *v += 1).unwrap();
+///     }
+///     // Estimated number of packets
+///     assert_eq!(*packets.read().unwrap() * packets.sample_rate(), 1000);
+/// }
+/// ```
+///
+/// [`Instrument`]: ../struct.Instrument.html
+pub struct SampledInstrument<T: Serialize, L: Listener> {
+    instrument: Instrument<T, L>,
+    sample_rate: u64,
+    updates: Arc<AtomicU64>,
+}
+
+impl<T: Serialize, L: Listener> Clone for SampledInstrument<T, L> {
+    fn clone(&self) -> Self {
+        SampledInstrument {
+            instrument: self.instrument.clone(),
+            sample_rate: self.sample_rate,
+            updates: self.updates.clone(),
+        }
+    }
+}
+
+impl<T: Serialize, L: Listener> SampledInstrument<T, L> {
+    /// Creates a new instrument applying one in every `sample_rate` updates
+    ///
+    /// Panics if `sample_rate` is zero.
+    pub fn new(data: T, sample_rate: u64) -> Self {
+        assert!(sample_rate > 0, "sample rate must be positive");
+        SampledInstrument {
+            instrument: Instrument::new(data),
+            sample_rate,
+            updates: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    metadata_accessors!(instrument.metadata);
+    wrapped_wiring!(instrument, L);
+
+    /// Returns the sample rate
+    pub fn sample_rate(&self) -> u64 {
+        self.sample_rate
+    }
+
+    /// Returns `true` if an update has ever been applied
+    pub fn has_been_updated(&self) -> bool {
+        self.instrument.has_been_updated()
+    }
+
+    /// Returns `true` if a thread has panicked while updating the instrument
+    pub fn is_poisoned(&self) -> bool {
+        self.instrument.is_poisoned()
+    }
+
+    /// Thread-safe value reader
+    pub fn read(&self) -> LockResult<RwLockReadGuard<'_, T>> {
+        self.instrument.read()
+    }
+
+    /// Thread-safe value writer, applying one in every `sample_rate` calls
+    ///
+    /// Takes the same closures as [`Instrument#update`] does. Returns `Ok(Some(_))` with the
+    /// closure's result if the update has been applied, `Ok(None)` if it has been skipped.
+    ///
+    /// [`Instrument#update`]: ../struct.Instrument.html#method.update
+    pub fn update<F, R>(&self, f: F) -> Result<Option<R>, UpdateError> where F: FnOnce(&mut T) -> R {
+        if self.updates.fetch_add(1, Ordering::Relaxed) % self.sample_rate != 0 {
+            return Ok(None);
+        }
+        self.instrument.update(f).map(Some)
+    }
+}
+
+/// Resets the value to its default, regardless of the sample rate
+impl<T: Serialize + Default, L: Listener> Reset for SampledInstrument<T, L> {
+    fn reset(&self) -> Result<(), UpdateError> {
+        self.instrument.reset()
+    }
+}
+
+/// Restores the value, see [`Restore`].
+///
+/// [`Restore`]: ../trait.Restore.html
+impl<T: Serialize + DeserializeOwned, L: Listener> Restore for SampledInstrument<T, L> {
+    fn restore<'de, D: Deserializer<'de>>(&self, reading: D) -> Result<(), D::Error> {
+        self.instrument.restore(reading)
+    }
+}
+
+impl<T: Serialize, L: Listener> Serialize for SampledInstrument<T, L> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where
+        S: Serializer {
+        self.instrument.serialize_with_field(serializer, Some(("sample_rate", self.sample_rate)))
+    }
+}
+
+#[cfg(feature = "timestamp_instruments")]
+impl<T: Serialize, L: Listener> UpdatedSince<DateTime<Utc>> for SampledInstrument<T, L> {
+    fn updated_since(&self, since: &DateTime<Utc>) -> bool {
+        self.instrument.updated_since(since)
+    }
+}
+
+impl<T: Serialize, L: Listener> LastUpdated for SampledInstrument<T, L> {
+    fn last_update_at(&self) -> Option<Timestamp> {
+        self.instrument.last_update_at()
+    }
+}
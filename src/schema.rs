@@ -0,0 +1,277 @@
+// Copyright 2017 All Contributors (see CONTRIBUTORS file)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+//
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! # JSON Schema
+//!
+//! _This module is only present if `json_schema` feature is enabled.
+//! It is disabled by default._
+//!
+//! Consumers of instrument readings often need to know their structure upfront, for example
+//! to validate them or to generate client code. This module describes readings with
+//! [JSON Schema], using [schemars]: [`reading_schema`] describes the reading of a single
+//! instrument type, [`board_schema`] describes the readings of an entire board (as serialized
+//! with [`Readings`]), keyed by instrument names.
+//!
+//! Instrument values must implement `JsonSchema`. Derived boards provide a `describe_readings`
+//! function that [`board_schema`] accepts:
+//!
+//! ```rust
+//! extern crate rapt;
+//! #[macro_use]
+//! extern crate rapt_derive;
+//! extern crate serde;
+//!
+//! use rapt::{Instrument, Listener};
+//! use rapt::schema::board_schema;
+//!
+//! #[derive(Instruments)]
+//! struct AppInstruments<L: Listener> {
+//!     requests: Instrument<u64, L>,
+//!     status: Instrument<String, L>,
+//! }
+//!
+//! fn main() {
+//!     let schema = board_schema(AppInstruments::<()>::describe_readings);
+//!     let properties = &schema.schema.object.as_ref().unwrap().properties;
+//!     assert!(properties.contains_key("requests"));
+//!     assert!(properties.contains_key("status"));
+//! }
+//! ```
+//!
+//! Fields that depend on instrument's configuration (`schema_version` and `error`)
+//! are described as optional.
+//!
+//! [JSON Schema]: https://json-schema.org
+//! [schemars]: https://docs.rs/schemars
+//! [`reading_schema`]: fn.reading_schema.html
+//! [`board_schema`]: fn.board_schema.html
+//! [`Readings`]: ../ser/struct.Readings.html
+
+use schemars::JsonSchema;
+use schemars::gen::SchemaGenerator;
+#[cfg(feature = "timestamp_instruments")]
+use schemars::schema::ArrayValidation;
+use schemars::schema::{InstanceType, ObjectValidation, RootSchema, Schema, SchemaObject,
+                        SubschemaValidation};
+use serde::Serialize;
+use serde_json::Value;
+
+use super::{DescribeReading, Instrument, Listener};
+use super::atomic::{AtomicValue, AtomicView};
+use super::computed::ComputedInstrument;
+use super::lazy::LazyInstrument;
+use super::rate::RateInstrument;
+use super::sampled::SampledInstrument;
+use super::uptime::Uptime;
+#[cfg(feature = "timestamp_instruments")]
+use super::error_log::ErrorLogInstrument;
+
+/// Describes the reading of a single instrument type
+pub fn reading_schema<I: DescribeReading<SchemaGenerator, Description = Schema>>() -> RootSchema {
+    let mut generator = SchemaGenerator::default();
+    let schema = I::describe_reading(&mut generator);
+    root_schema(generator, schema)
+}
+
+/// Describes readings of an entire board, keyed by instrument names
+///
+/// `describe` lists readings of board's instruments, typically this is the `describe_readings`
+/// function of a derived board. Readings are not required to be present, as not every export
+/// includes every instrument.
+pub fn board_schema<F>(describe: F) -> RootSchema
+    where F: FnOnce(&mut SchemaGenerator) -> Vec<(&'static str, Schema)> {
+    let mut generator = SchemaGenerator::default();
+    let properties = describe(&mut generator).into_iter()
+        .map(|(name, schema)| (name.to_string(), schema))
+        .collect();
+    let schema = object_schema(properties, vec![]);
+    root_schema(generator, schema)
+}
+
+fn root_schema(mut generator: SchemaGenerator, schema: Schema) -> RootSchema {
+    let schema = match schema {
+        Schema::Object(object) => object,
+        schema => SchemaObject { subschemas: Some(Box::new(SubschemaValidation {
+            all_of: Some(vec![schema]),
+            ..Default::default()
+        })), ..Default::default() },
+    };
+    RootSchema {
+        meta_schema: generator.settings().meta_schema.clone(),
+        schema,
+        definitions: generator.take_definitions(),
+    }
+}
+
+fn object_schema(properties: Vec<(String, Schema)>, required: Vec<&str>) -> Schema {
+    SchemaObject {
+        instance_type: Some(InstanceType::Object.into()),
+        object: Some(Box::new(ObjectValidation {
+            required: required.into_iter().map(String::from).collect(),
+            properties: properties.into_iter().collect(),
+            ..Default::default()
+        })),
+        ..Default::default()
+    }.into()
+}
+
+fn timestamp_schema() -> Schema {
+    SchemaObject {
+        instance_type: Some(InstanceType::String.into()),
+        format: Some("date-time".to_string()),
+        ..Default::default()
+    }.into()
+}
+
+/// Describes the reading of an [`Instrument`] with the given value schema and an extra field
+///
+/// [`Instrument`]: ../struct.Instrument.html
+fn instrument_reading(generator: &mut SchemaGenerator, value: Schema, extra: Option<(&str, Schema)>) -> Schema {
+    let error = SchemaObject { const_value: Some(Value::from("poisoned")), ..Default::default() }.into();
+    let mut properties = vec![("value".to_string(), value), ("error".to_string(), error)];
+    if let Some((key, schema)) = extra {
+        properties.push((key.to_string(), schema));
+    }
+    properties.push(("schema_version".to_string(), generator.subschema_for::<u32>()));
+    let mut required = vec!["value"];
+    if cfg!(feature = "timestamp_instruments") {
+        properties.push(("last_update_at".to_string(), timestamp_schema()));
+        required.push("last_update_at");
+    }
+    object_schema(properties, required)
+}
+
+/// Describes the reading of an instrument computing its value on demand
+fn computed_reading(generator: &mut SchemaGenerator, value: Schema) -> Schema {
+    let properties = vec![("value".to_string(), value),
+                          ("schema_version".to_string(), generator.subschema_for::<u32>())];
+    object_schema(properties, vec!["value"])
+}
+
+impl<T: Serialize + JsonSchema, L: Listener> DescribeReading<SchemaGenerator> for Instrument<T, L> {
+    type Description = Schema;
+    fn describe_reading(generator: &mut SchemaGenerator) -> Schema {
+        let value = generator.subschema_for::<T>();
+        instrument_reading(generator, value, None)
+    }
+}
+
+impl<T: Serialize + JsonSchema, L: Listener> DescribeReading<SchemaGenerator> for SampledInstrument<T, L> {
+    type Description = Schema;
+    fn describe_reading(generator: &mut SchemaGenerator) -> Schema {
+        let value = generator.subschema_for::<T>();
+        let sample_rate = generator.subschema_for::<u64>();
+        instrument_reading(generator, value, Some(("sample_rate", sample_rate)))
+    }
+}
+
+impl<L: Listener> DescribeReading<SchemaGenerator> for RateInstrument<L> {
+    type Description = Schema;
+    fn describe_reading(generator: &mut SchemaGenerator) -> Schema {
+        let value = generator.subschema_for::<u64>();
+        let rate = generator.subschema_for::<f64>();
+        instrument_reading(generator, value, Some(("rate", rate)))
+    }
+}
+
+/// Entries are `[timestamp, message]` pairs
+#[cfg(feature = "timestamp_instruments")]
+impl<L: Listener> DescribeReading<SchemaGenerator> for ErrorLogInstrument<L> {
+    type Description = Schema;
+    fn describe_reading(generator: &mut SchemaGenerator) -> Schema {
+        let message = generator.subschema_for::<String>();
+        let entry = SchemaObject {
+            instance_type: Some(InstanceType::Array.into()),
+            array: Some(Box::new(ArrayValidation {
+                items: Some(vec![timestamp_schema(), message].into()),
+                min_items: Some(2),
+                max_items: Some(2),
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+        let value = SchemaObject {
+            instance_type: Some(InstanceType::Array.into()),
+            array: Some(Box::new(ArrayValidation {
+                items: Some(Schema::from(entry).into()),
+                ..Default::default()
+            })),
+            ..Default::default()
+        }.into();
+        instrument_reading(generator, value, None)
+    }
+}
+
+impl<A: AtomicValue, L: Listener> DescribeReading<SchemaGenerator> for AtomicView<A, L>
+    where A::Value: JsonSchema {
+    type Description = Schema;
+    fn describe_reading(generator: &mut SchemaGenerator) -> Schema {
+        let value = generator.subschema_for::<A::Value>();
+        computed_reading(generator, value)
+    }
+}
+
+impl<T: Serialize + JsonSchema, F: Fn() -> T, L: Listener> DescribeReading<SchemaGenerator> for LazyInstrument<T, F, L> {
+    type Description = Schema;
+    fn describe_reading(generator: &mut SchemaGenerator) -> Schema {
+        let value = generator.subschema_for::<T>();
+        computed_reading(generator, value)
+    }
+}
+
+impl<S, T: Serialize + JsonSchema, L: Listener> DescribeReading<SchemaGenerator> for ComputedInstrument<S, T, L> {
+    type Description = Schema;
+    fn describe_reading(generator: &mut SchemaGenerator) -> Schema {
+        let value = generator.subschema_for::<T>();
+        computed_reading(generator, value)
+    }
+}
+
+impl<L: Listener> DescribeReading<SchemaGenerator> for Uptime<L> {
+    type Description = Schema;
+    fn describe_reading(generator: &mut SchemaGenerator) -> Schema {
+        let mut properties = vec![("uptime".to_string(), generator.subschema_for::<f64>())];
+        let mut required = vec!["uptime"];
+        if cfg!(feature = "timestamp_instruments") {
+            properties.push(("started_at".to_string(), timestamp_schema()));
+            required.push("started_at");
+        }
+        computed_reading(generator, object_schema(properties, required))
+    }
+}
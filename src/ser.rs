@@ -53,6 +53,12 @@
 //!
 //! * [`JsonSerializer`] — requires `serde_json` feature to be enabled; disabled by default
 //!
+//! It also provides [`FieldFilter`], a serializer wrapper that only emits selected
+//! struct fields, which is useful when only a few fields of a large instrument value
+//! are of interest, and [`ContextSerializer`] that merges labels known only at
+//! serialization time (such as a tenant ID) into instrument's fields. [`Readings`]
+//! serializes all instruments of a board at once, optionally renaming them.
+//!
 //! The technique employed in this module depends on a common
 //! pattern used in Serde ecosystem: actual serializers do not
 //! implement [`Serializer`], but their mutable references (`&mut`) do.
@@ -86,16 +92,28 @@
 //!
 //! [`Serializer`]: https://docs.serde.rs/serde/trait.Serializer.html
 //! [`JsonSerializer`]: struct.JsonSerializer.html
+//! [`FieldFilter`]: struct.FieldFilter.html
+//! [`ContextSerializer`]: struct.ContextSerializer.html
+//! [`Readings`]: struct.Readings.html
 //! [`Write`]: https://doc.rust-lang.org/std/io/trait.Write.html
 //!
-use std::io::Write;
+use std::io::{self, Write};
+use std::collections::HashMap;
+
+use std::marker::PhantomData;
+
+use serde::ser::{Error, Serialize, Serializer, SerializeSeq, SerializeTuple, SerializeTupleStruct,
+                 SerializeTupleVariant, SerializeMap, SerializeStruct, SerializeStructVariant};
+
+use super::{Instruments, Listener, ReadError};
 
 #[cfg(feature = "serde_json")]
 use serde_json;
 
 /// This trait instantiates a serializer over a given [`Write`]
 ///
-/// Requires `Target` to be convertible back into the writer.
+/// Requires `Target` to be convertible back into the writer. This also allows to reuse
+/// the writer (such as a cleared `Vec<u8>`) for the next serializer instantiation.
 ///
 /// [`Write`]: https://doc.rust-lang.org/std/io/trait.Write.html
 pub trait InstantiateSerializer<'a, W: Write> {
@@ -109,7 +127,7 @@ pub trait InstantiateSerializer<'a, W: Write> {
     fn instantiate_serializer(&self, over: W) -> Self::Target;
 }
 
-//// JSON Serializer (enabled in `serde_json` feature is enabled; disabled by default)
+/// JSON Serializer (enabled in `serde_json` feature is enabled; disabled by default)
 #[cfg(feature = "serde_json")]
 pub struct JsonSerializer;
 
@@ -134,3 +152,737 @@ impl<W: Write> IntoWriter<W> for serde_json::Serializer<W> {
         self.into_inner()
     }
 }
+
+/// A writer that only counts bytes written into it
+///
+/// Serializers instantiated over it measure serialized sizes without keeping the
+/// serialized bytes, see [`Instruments#serialized_size`].
+///
+/// [`Instruments#serialized_size`]: ../trait.Instruments.html#method.serialized_size
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ByteCounter {
+    count: usize,
+}
+
+impl ByteCounter {
+    /// Returns the number of bytes written so far
+    pub fn count(&self) -> usize {
+        self.count
+    }
+}
+
+impl Write for ByteCounter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.count += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A set of field paths to include into serialized output
+///
+/// Paths are dot-separated struct field names, relative to the value being
+/// serialized. For an [`Instrument`], `value.status` selects the `status` field
+/// of the instrument's value (note that `last_update_at` has to be listed explicitly
+/// if it is needed). Listing a path includes everything underneath it.
+///
+/// Only struct fields are filtered. Sequences, tuples and maps are emitted as is,
+/// with the filter applied to each of their elements.
+///
+/// Since the number of fields to be emitted can't be known upfront, the length
+/// hint passed to the underlying serializer is an upper bound. Formats that write
+/// out struct lengths (as opposed to JSON) require all paths to name existing fields.
+///
+/// ```rust
+/// extern crate rapt;
+/// extern crate serde;
+/// extern crate serde_json;
+///
+/// use serde::Serialize;
+/// use rapt::Instrument;
+/// use rapt::ser::FieldFilter;
+///
+/// fn main() {
+///     let i = Instrument::<(u8, u8), ()>::new((1, 2));
+///     let filter = FieldFilter::new(vec!["value"]);
+///     let mut ser = serde_json::Serializer::new(Vec::new());
+///     i.serialize(filter.serializer(&mut ser)).unwrap();
+///     assert_eq!(ser.into_inner(), b"{\"value\":[1,2]}");
+/// }
+/// ```
+///
+/// [`Instrument`]: ../struct.Instrument.html
+#[derive(Debug, Clone, Default)]
+pub struct FieldFilter {
+    fields: Option<HashMap<String, FieldFilter>>,
+}
+
+impl FieldFilter {
+    /// Creates a new filter including given field paths
+    pub fn new<I, P>(paths: I) -> Self where I: IntoIterator<Item = P>, P: AsRef<str> {
+        let mut filter = FieldFilter { fields: Some(HashMap::new()) };
+        for path in paths {
+            filter.include(path.as_ref().split('.'));
+        }
+        filter
+    }
+
+    fn include<'a, I: Iterator<Item = &'a str>>(&mut self, mut path: I) {
+        match path.next() {
+            // Everything underneath has been requested
+            None => self.fields = None,
+            Some(name) => {
+                if let Some(ref mut fields) = self.fields {
+                    fields.entry(name.into())
+                        .or_insert_with(|| FieldFilter { fields: Some(HashMap::new()) })
+                        .include(path)
+                }
+            },
+        }
+    }
+
+    fn field(&self, name: &str) -> Option<&FieldFilter> {
+        match self.fields {
+            None => Some(self),
+            Some(ref fields) => fields.get(name),
+        }
+    }
+
+    fn len_hint(&self, len: usize) -> usize {
+        match self.fields {
+            None => len,
+            Some(ref fields) => ::std::cmp::min(len, fields.len()),
+        }
+    }
+
+    /// Wraps a serializer so that it only emits included fields
+    pub fn serializer<S: Serializer>(&self, serializer: S) -> FilteredSerializer<'_, S> {
+        FilteredSerializer { inner: serializer, filter: self }
+    }
+}
+
+/// Serializer emitting only the fields included by a [`FieldFilter`]
+///
+/// Created by [`FieldFilter#serializer`].
+///
+/// [`FieldFilter`]: struct.FieldFilter.html
+/// [`FieldFilter#serializer`]: struct.FieldFilter.html#method.serializer
+pub struct FilteredSerializer<'f, S> {
+    inner: S,
+    filter: &'f FieldFilter,
+}
+
+/// A value serialized through a [`FilteredSerializer`]
+///
+/// [`FilteredSerializer`]: struct.FilteredSerializer.html
+struct Filtered<'a, 'f, T: ?Sized + 'a> {
+    value: &'a T,
+    filter: &'f FieldFilter,
+}
+
+impl<'a, 'f, T: ?Sized + Serialize> Serialize for Filtered<'a, 'f, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.value.serialize(self.filter.serializer(serializer))
+    }
+}
+
+/// Compound serializer counterpart of [`FilteredSerializer`]
+///
+/// [`FilteredSerializer`]: struct.FilteredSerializer.html
+pub struct FilteredCompound<'f, C> {
+    inner: C,
+    filter: &'f FieldFilter,
+}
+
+impl<'f, S: Serializer> Serializer for FilteredSerializer<'f, S> {
+    type Ok = S::Ok;
+    type Error = S::Error;
+    type SerializeSeq = FilteredCompound<'f, S::SerializeSeq>;
+    type SerializeTuple = FilteredCompound<'f, S::SerializeTuple>;
+    type SerializeTupleStruct = FilteredCompound<'f, S::SerializeTupleStruct>;
+    type SerializeTupleVariant = FilteredCompound<'f, S::SerializeTupleVariant>;
+    type SerializeMap = FilteredCompound<'f, S::SerializeMap>;
+    type SerializeStruct = FilteredCompound<'f, S::SerializeStruct>;
+    type SerializeStructVariant = FilteredCompound<'f, S::SerializeStructVariant>;
+
+    fn serialize_bool(self, v: bool) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_bool(v)
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_i8(v)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_i16(v)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_i32(v)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_i64(v)
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_i128(v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_u8(v)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_u16(v)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_u32(v)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_u64(v)
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_u128(v)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_f32(v)
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_f64(v)
+    }
+
+    fn serialize_char(self, v: char) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_char(v)
+    }
+
+    fn serialize_str(self, v: &str) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_str(v)
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_bytes(v)
+    }
+
+    fn serialize_none(self) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_none()
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_some(&Filtered { value, filter: self.filter })
+    }
+
+    fn serialize_unit(self) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_unit()
+    }
+
+    fn serialize_unit_struct(self, name: &'static str) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_unit_struct(name)
+    }
+
+    fn serialize_unit_variant(self, name: &'static str, variant_index: u32,
+                              variant: &'static str) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_unit_variant(name, variant_index, variant)
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(self, name: &'static str, value: &T)
+                                                      -> Result<S::Ok, S::Error> {
+        self.inner.serialize_newtype_struct(name, &Filtered { value, filter: self.filter })
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(self, name: &'static str, variant_index: u32,
+                                                       variant: &'static str, value: &T)
+                                                       -> Result<S::Ok, S::Error> {
+        self.inner.serialize_newtype_variant(name, variant_index, variant,
+                                             &Filtered { value, filter: self.filter })
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, S::Error> {
+        let filter = self.filter;
+        self.inner.serialize_seq(len).map(|inner| FilteredCompound { inner, filter })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, S::Error> {
+        let filter = self.filter;
+        self.inner.serialize_tuple(len).map(|inner| FilteredCompound { inner, filter })
+    }
+
+    fn serialize_tuple_struct(self, name: &'static str, len: usize)
+                              -> Result<Self::SerializeTupleStruct, S::Error> {
+        let filter = self.filter;
+        self.inner.serialize_tuple_struct(name, len).map(|inner| FilteredCompound { inner, filter })
+    }
+
+    fn serialize_tuple_variant(self, name: &'static str, variant_index: u32,
+                               variant: &'static str, len: usize)
+                               -> Result<Self::SerializeTupleVariant, S::Error> {
+        let filter = self.filter;
+        self.inner.serialize_tuple_variant(name, variant_index, variant, len)
+            .map(|inner| FilteredCompound { inner, filter })
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap, S::Error> {
+        let filter = self.filter;
+        self.inner.serialize_map(len).map(|inner| FilteredCompound { inner, filter })
+    }
+
+    fn serialize_struct(self, name: &'static str, len: usize)
+                        -> Result<Self::SerializeStruct, S::Error> {
+        let filter = self.filter;
+        self.inner.serialize_struct(name, filter.len_hint(len))
+            .map(|inner| FilteredCompound { inner, filter })
+    }
+
+    fn serialize_struct_variant(self, name: &'static str, variant_index: u32,
+                                variant: &'static str, len: usize)
+                                -> Result<Self::SerializeStructVariant, S::Error> {
+        let filter = self.filter;
+        self.inner.serialize_struct_variant(name, variant_index, variant, filter.len_hint(len))
+            .map(|inner| FilteredCompound { inner, filter })
+    }
+
+    fn is_human_readable(&self) -> bool {
+        self.inner.is_human_readable()
+    }
+}
+
+impl<'f, C: SerializeSeq> SerializeSeq for FilteredCompound<'f, C> {
+    type Ok = C::Ok;
+    type Error = C::Error;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), C::Error> {
+        self.inner.serialize_element(&Filtered { value, filter: self.filter })
+    }
+
+    fn end(self) -> Result<C::Ok, C::Error> {
+        self.inner.end()
+    }
+}
+
+impl<'f, C: SerializeTuple> SerializeTuple for FilteredCompound<'f, C> {
+    type Ok = C::Ok;
+    type Error = C::Error;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), C::Error> {
+        self.inner.serialize_element(&Filtered { value, filter: self.filter })
+    }
+
+    fn end(self) -> Result<C::Ok, C::Error> {
+        self.inner.end()
+    }
+}
+
+impl<'f, C: SerializeTupleStruct> SerializeTupleStruct for FilteredCompound<'f, C> {
+    type Ok = C::Ok;
+    type Error = C::Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), C::Error> {
+        self.inner.serialize_field(&Filtered { value, filter: self.filter })
+    }
+
+    fn end(self) -> Result<C::Ok, C::Error> {
+        self.inner.end()
+    }
+}
+
+impl<'f, C: SerializeTupleVariant> SerializeTupleVariant for FilteredCompound<'f, C> {
+    type Ok = C::Ok;
+    type Error = C::Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), C::Error> {
+        self.inner.serialize_field(&Filtered { value, filter: self.filter })
+    }
+
+    fn end(self) -> Result<C::Ok, C::Error> {
+        self.inner.end()
+    }
+}
+
+impl<'f, C: SerializeMap> SerializeMap for FilteredCompound<'f, C> {
+    type Ok = C::Ok;
+    type Error = C::Error;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<(), C::Error> {
+        self.inner.serialize_key(key)
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), C::Error> {
+        self.inner.serialize_value(&Filtered { value, filter: self.filter })
+    }
+
+    fn end(self) -> Result<C::Ok, C::Error> {
+        self.inner.end()
+    }
+}
+
+impl<'f, C: SerializeStruct> SerializeStruct for FilteredCompound<'f, C> {
+    type Ok = C::Ok;
+    type Error = C::Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, key: &'static str, value: &T)
+                                             -> Result<(), C::Error> {
+        match self.filter.field(key) {
+            Some(filter) => self.inner.serialize_field(key, &Filtered { value, filter }),
+            None => self.inner.skip_field(key),
+        }
+    }
+
+    fn end(self) -> Result<C::Ok, C::Error> {
+        self.inner.end()
+    }
+}
+
+impl<'f, C: SerializeStructVariant> SerializeStructVariant for FilteredCompound<'f, C> {
+    type Ok = C::Ok;
+    type Error = C::Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, key: &'static str, value: &T)
+                                             -> Result<(), C::Error> {
+        match self.filter.field(key) {
+            Some(filter) => self.inner.serialize_field(key, &Filtered { value, filter }),
+            None => self.inner.skip_field(key),
+        }
+    }
+
+    fn end(self) -> Result<C::Ok, C::Error> {
+        self.inner.end()
+    }
+}
+
+/// Fields serialized by instruments, context entries can't override them
+///
+/// ```rust
+/// extern crate rapt;
+///
+/// fn main() {
+///     assert_eq!(rapt::ser::INSTRUMENT_FIELDS,
+///                &["value", "error", "sample_rate", "rate", "schema_version", "last_update_at"]);
+/// }
+/// ```
+pub const INSTRUMENT_FIELDS: &[&str] = &["value", "error", "sample_rate", "rate", "schema_version", "last_update_at"];
+
+/// Serializer merging external context into an instrument's fields
+///
+/// Context entries are emitted after instrument's own fields, turning it into a map.
+/// Entries named after any field an instrument might serialize (see [`INSTRUMENT_FIELDS`]:
+/// `value`, `error`, `sample_rate`, `rate`, `schema_version` and `last_update_at`) are ignored,
+/// so that the context can never alter the reading itself.
+///
+/// Only the top-level struct receives the context, anything else is serialized as is.
+///
+/// ```rust
+/// extern crate rapt;
+/// extern crate serde;
+/// extern crate serde_json;
+///
+/// use std::collections::HashMap;
+/// use serde::Serialize;
+/// use rapt::Instrument;
+/// use rapt::ser::ContextSerializer;
+///
+/// fn main() {
+///     let i = Instrument::<u8, ()>::new(1);
+///     let mut context = HashMap::new();
+///     context.insert("tenant", "acme".to_string());
+///     let mut ser = serde_json::Serializer::new(Vec::new());
+///     i.serialize(ContextSerializer::new(&context, &mut ser)).unwrap();
+///     let v: serde_json::Value = serde_json::from_slice(&ser.into_inner()).unwrap();
+///     assert_eq!(v["tenant"], "acme");
+/// }
+/// ```
+///
+/// [`INSTRUMENT_FIELDS`]: constant.INSTRUMENT_FIELDS.html
+pub struct ContextSerializer<'c, S> {
+    inner: S,
+    context: &'c HashMap<&'c str, String>,
+}
+
+impl<'c, S: Serializer> ContextSerializer<'c, S> {
+    /// Wraps a serializer so that it merges `context` into the serialized instrument
+    pub fn new(context: &'c HashMap<&'c str, String>, serializer: S) -> Self {
+        ContextSerializer { inner: serializer, context }
+    }
+}
+
+/// Context entries that don't clash with instrument fields
+fn context_entries<'c>(context: &'c HashMap<&'c str, String>) -> impl Iterator<Item = (&'c &'c str, &'c String)> {
+    context.iter().filter(|&(key, _)| !INSTRUMENT_FIELDS.contains(key))
+}
+
+/// Struct serializer counterpart of [`ContextSerializer`]
+///
+/// [`ContextSerializer`]: struct.ContextSerializer.html
+pub struct ContextStruct<'c, M> {
+    inner: M,
+    context: &'c HashMap<&'c str, String>,
+}
+
+impl<'c, S: Serializer> Serializer for ContextSerializer<'c, S> {
+    type Ok = S::Ok;
+    type Error = S::Error;
+    type SerializeSeq = S::SerializeSeq;
+    type SerializeTuple = S::SerializeTuple;
+    type SerializeTupleStruct = S::SerializeTupleStruct;
+    type SerializeTupleVariant = S::SerializeTupleVariant;
+    type SerializeMap = S::SerializeMap;
+    type SerializeStruct = ContextStruct<'c, S::SerializeMap>;
+    type SerializeStructVariant = S::SerializeStructVariant;
+
+    fn serialize_bool(self, v: bool) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_bool(v)
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_i8(v)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_i16(v)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_i32(v)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_i64(v)
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_i128(v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_u8(v)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_u16(v)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_u32(v)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_u64(v)
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_u128(v)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_f32(v)
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_f64(v)
+    }
+
+    fn serialize_char(self, v: char) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_char(v)
+    }
+
+    fn serialize_str(self, v: &str) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_str(v)
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_bytes(v)
+    }
+
+    fn serialize_none(self) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_none()
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_some(value)
+    }
+
+    fn serialize_unit(self) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_unit()
+    }
+
+    fn serialize_unit_struct(self, name: &'static str) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_unit_struct(name)
+    }
+
+    fn serialize_unit_variant(self, name: &'static str, variant_index: u32,
+                              variant: &'static str) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_unit_variant(name, variant_index, variant)
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(self, name: &'static str, value: &T)
+                                                      -> Result<S::Ok, S::Error> {
+        self.inner.serialize_newtype_struct(name, value)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(self, name: &'static str, variant_index: u32,
+                                                       variant: &'static str, value: &T)
+                                                       -> Result<S::Ok, S::Error> {
+        self.inner.serialize_newtype_variant(name, variant_index, variant, value)
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, S::Error> {
+        self.inner.serialize_seq(len)
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, S::Error> {
+        self.inner.serialize_tuple(len)
+    }
+
+    fn serialize_tuple_struct(self, name: &'static str, len: usize)
+                              -> Result<Self::SerializeTupleStruct, S::Error> {
+        self.inner.serialize_tuple_struct(name, len)
+    }
+
+    fn serialize_tuple_variant(self, name: &'static str, variant_index: u32,
+                               variant: &'static str, len: usize)
+                               -> Result<Self::SerializeTupleVariant, S::Error> {
+        self.inner.serialize_tuple_variant(name, variant_index, variant, len)
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap, S::Error> {
+        self.inner.serialize_map(len)
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize)
+                        -> Result<Self::SerializeStruct, S::Error> {
+        let context = self.context;
+        let len = len + context_entries(context).count();
+        self.inner.serialize_map(Some(len)).map(|inner| ContextStruct { inner, context })
+    }
+
+    fn serialize_struct_variant(self, name: &'static str, variant_index: u32,
+                                variant: &'static str, len: usize)
+                                -> Result<Self::SerializeStructVariant, S::Error> {
+        self.inner.serialize_struct_variant(name, variant_index, variant, len)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        self.inner.is_human_readable()
+    }
+}
+
+impl<'c, M: SerializeMap> SerializeStruct for ContextStruct<'c, M> {
+    type Ok = M::Ok;
+    type Error = M::Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, key: &'static str, value: &T)
+                                             -> Result<(), M::Error> {
+        self.inner.serialize_entry(key, value)
+    }
+
+    fn end(mut self) -> Result<M::Ok, M::Error> {
+        for (key, value) in context_entries(self.context) {
+            self.inner.serialize_entry(key, value)?;
+        }
+        self.inner.end()
+    }
+}
+
+/// Readings of all instruments of a board, as a map keyed by instrument names
+///
+/// Instrument names can be mapped to different keys with [`with_key_mapping`], which
+/// decouples internal names from the key space expected by consumers (such as a legacy
+/// API), while [`Instruments#serialize_reading`] keeps matching internal names.
+///
+/// Instruments excluded from bulk reads (see [`Instruments#bulk_instrument_names`]) are skipped.
+///
+/// ```rust
+/// extern crate rapt;
+/// extern crate serde;
+/// extern crate serde_json;
+/// #[macro_use]
+/// extern crate rapt_derive;
+///
+/// use rapt::{Instrument, Listener};
+/// use rapt::ser::Readings;
+///
+/// #[derive(Instruments)]
+/// struct AppInstruments<L: Listener> {
+///     requests: Instrument<u64, L>,
+/// }
+///
+/// fn main() {
+///     let instruments = AppInstruments::<()> { requests: Instrument::new(42) };
+///     let readings = Readings::new(&instruments).with_key_mapping(|name| format!("app.{}", name));
+///     let json = serde_json::to_value(&readings).unwrap();
+///     assert_eq!(json["app.requests"]["value"], 42);
+/// }
+/// ```
+///
+/// [`with_key_mapping`]: #method.with_key_mapping
+/// [`Instruments#serialize_reading`]: ../trait.Instruments.html#tymethod.serialize_reading
+/// [`Instruments#bulk_instrument_names`]: ../trait.Instruments.html#method.bulk_instrument_names
+pub struct Readings<'a, I: Instruments<L> + 'a, L: Listener, F = fn(&'static str) -> String> {
+    instruments: &'a I,
+    key: F,
+    sorted: bool,
+    listener: PhantomData<L>,
+}
+
+impl<'a, I: Instruments<L> + 'a, L: Listener> Readings<'a, I, L> {
+    /// Creates readings of the instrument board, keyed by instrument names
+    pub fn new(instruments: &'a I) -> Self {
+        Readings { instruments, key: String::from, sorted: false, listener: PhantomData }
+    }
+}
+
+impl<'a, I: Instruments<L> + 'a, L: Listener, F: Fn(&'static str) -> String> Readings<'a, I, L, F> {
+    /// Maps instrument names to output keys with `key`
+    pub fn with_key_mapping<K: Fn(&'static str) -> String>(self, key: K) -> Readings<'a, I, L, K> {
+        Readings { instruments: self.instruments, key, sorted: self.sorted, listener: PhantomData }
+    }
+
+    /// Orders readings by their output keys, instead of the order of instrument names
+    ///
+    /// This makes the output independent of the order instruments are declared in, which
+    /// is useful for comparing snapshots byte for byte.
+    pub fn sorted(mut self) -> Self {
+        self.sorted = true;
+        self
+    }
+}
+
+impl<'a, I: Instruments<L> + 'a, L: Listener, F: Fn(&'static str) -> String> Serialize for Readings<'a, I, L, F> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where
+        S: Serializer {
+        let mut entries: Vec<_> = self.instruments.bulk_instrument_names().into_iter()
+            .map(|name| ((self.key)(name), name)).collect();
+        if self.sorted {
+            entries.sort();
+        }
+        let mut map = serializer.serialize_map(Some(entries.len()))?;
+        for (key, name) in entries {
+            map.serialize_entry(&key, &Reading { instruments: self.instruments, name, listener: PhantomData })?;
+        }
+        map.end()
+    }
+}
+
+/// Reading of a single instrument
+struct Reading<'a, I: Instruments<L> + 'a, L: Listener> {
+    instruments: &'a I,
+    name: &'static str,
+    listener: PhantomData<L>,
+}
+
+impl<'a, I: Instruments<L> + 'a, L: Listener> Serialize for Reading<'a, I, L> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where
+        S: Serializer {
+        self.instruments.serialize_reading(self.name, serializer).map_err(|e| match e {
+            ReadError::SerializationError(e) => e,
+            ReadError::NotFound => S::Error::custom(format!("instrument {} not found", self.name)),
+        })
+    }
+}
@@ -0,0 +1,300 @@
+// Copyright 2017 All Contributors (see CONTRIBUTORS file)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+//
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+
+//! # Server-Sent Events Publisher
+//!
+//! _This module is only present if `sse_publisher` feature is enabled.
+//! It is disabled by default._
+//!
+//! [Server-Sent Events] are the simplest way to stream live instrument updates into
+//! a web page: browsers connect with `EventSource` and receive every update as an
+//! `update` event, whose data is the serialized instrument name and reading:
+//!
+//! ```text
+//! event: update
+//! data: {"name":"requests","reading":{"value":42}}
+//! ```
+//!
+//! The publisher embeds a minimal HTTP server (it serves the stream on any path). Newly
+//! connected clients receive current readings of all instruments first. A comment is
+//! sent periodically to keep idle connections alive, and clients that have disconnected
+//! (or can't keep up) are dropped.
+//!
+//! [Server-Sent Events]: https://html.spec.whatwg.org/multipage/server-sent-events.html
+
+use super::{Listener, Instruments, ReadError};
+use super::dedup::Dedup;
+use super::ser::{InstantiateSerializer, IntoWriter};
+use serde::{Serialize, Serializer};
+use serde::ser::{Error, SerializeStruct};
+
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+/// Publisher control messages
+enum Message {
+    /// An instrument has been updated
+    Update(&'static str),
+    /// Shutdown requested, acknowledgement is sent over the enclosed channel
+    Shutdown(mpsc::Sender<()>),
+}
+
+/// How often new connections are accepted when there are no updates
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(100);
+/// How long a client has to send its request or to receive an event
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(5);
+/// Beginning of every update event
+const EVENT_PREFIX: &[u8] = b"event: update\ndata: ";
+/// Maximum size of request headers
+const MAX_REQUEST_SIZE: usize = 8192;
+
+/// Server-Sent Events publisher
+///
+/// Just like MQTT publisher, it reacts to every update but sends instrument's last value,
+/// filtering out repeated ones (see [`Dedup`]).
+///
+/// [`Dedup`]: ../dedup/struct.Dedup.html
+pub struct Publisher<I: Instruments<Handle>> {
+    listener: TcpListener,
+    instruments: I,
+    heartbeat: Duration,
+    dedup: Dedup,
+    clients: Vec<TcpStream>,
+    sender: mpsc::Sender<Message>,
+    receiver: mpsc::Receiver<Message>,
+}
+
+impl<I: Instruments<Handle>> Publisher<I> {
+    /// Creates a new publisher accepting connections on `listener`
+    ///
+    /// Heartbeat comments are sent every 15 seconds by default.
+    pub fn new(listener: TcpListener, mut instruments: I) -> io::Result<Self> {
+        listener.set_nonblocking(true)?;
+        let (sender, receiver) = mpsc::channel();
+        instruments.wire_listener(Handle { sender: sender.clone() });
+        Ok(Publisher {
+            listener,
+            instruments,
+            heartbeat: Duration::from_secs(15),
+            dedup: Dedup::default(),
+            clients: vec![],
+            sender,
+            receiver,
+        })
+    }
+
+    /// Sets the interval between heartbeat comments
+    pub fn set_heartbeat(&mut self, heartbeat: Duration) {
+        self.heartbeat = heartbeat;
+    }
+
+    /// Sets the tracker used to skip repeated readings
+    ///
+    /// Newly connected clients receive all current readings regardless.
+    pub fn set_dedup(&mut self, dedup: Dedup) {
+        self.dedup = dedup;
+    }
+
+    /// Returns a reference to instruments
+    pub fn instruments(&self) -> &I {
+        &self.instruments
+    }
+
+    /// Handle to the running `Publisher`
+    ///
+    /// Mainly used to gracefully shut it down.
+    pub fn handle(&self) -> Handle {
+        Handle { sender: self.sender.clone() }
+    }
+
+    /// Runs the publisher until [`Handle#shutdown`] is called
+    ///
+    /// `is` should typically produce JSON, as that's what web pages can parse.
+    ///
+    /// [`Handle#shutdown`]: struct.Handle.html#method.shutdown
+    pub fn run<IS, S>(&mut self, is: IS)
+           where for<'a> IS: InstantiateSerializer<'a, Vec<u8>, Target=S>,
+                 S: IntoWriter<Vec<u8>>, for<'a> &'a mut S: Serializer {
+        let mut last_sent = Instant::now();
+        let ack = loop {
+            match self.receiver.recv_timeout(ACCEPT_INTERVAL) {
+                Ok(Message::Shutdown(ack)) => break ack,
+                Ok(Message::Update(name)) => {
+                    if let Some(event) = self.event(name, &is) {
+                        if self.dedup.is_changed(&self.instruments, name, &event, &is) {
+                            broadcast(&mut self.clients, &event);
+                            last_sent = Instant::now();
+                        }
+                    }
+                },
+                Err(_) => (),
+            }
+            self.accept(&is);
+            if last_sent.elapsed() >= self.heartbeat {
+                broadcast(&mut self.clients, b": heartbeat\n\n");
+                last_sent = Instant::now();
+            }
+        };
+        self.clients.clear();
+        let _ = ack.send(());
+    }
+
+    /// Serializes an `update` event
+    fn event<IS, S>(&self, name: &'static str, is: &IS) -> Option<Vec<u8>>
+           where for<'a> IS: InstantiateSerializer<'a, Vec<u8>, Target=S>,
+                 S: IntoWriter<Vec<u8>>, for<'a> &'a mut S: Serializer {
+        let mut ser = is.instantiate_serializer(EVENT_PREFIX.to_vec());
+        Update { name, instruments: &self.instruments }.serialize(&mut ser).ok()?;
+        let mut event = ser.into_writer();
+        // Data spanning multiple lines would have to be split into multiple `data` fields
+        if event[EVENT_PREFIX.len()..].contains(&b'\n') {
+            return None;
+        }
+        event.extend_from_slice(b"\n\n");
+        Some(event)
+    }
+
+    /// Accepts pending connections, sending them current readings
+    fn accept<IS, S>(&mut self, is: &IS)
+           where for<'a> IS: InstantiateSerializer<'a, Vec<u8>, Target=S>,
+                 S: IntoWriter<Vec<u8>>, for<'a> &'a mut S: Serializer {
+        while let Ok((mut stream, _)) = self.listener.accept() {
+            if handshake(&mut stream).is_err() {
+                continue;
+            }
+            let mut events = self.instruments.bulk_instrument_names().into_iter()
+                .filter_map(|name| self.event(name, is));
+            if events.try_for_each(|event| stream.write_all(&event)).is_ok() {
+                self.clients.push(stream);
+            }
+        }
+    }
+}
+
+/// Reads client's request and responds with event stream headers
+fn handshake(stream: &mut TcpStream) -> io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+    stream.set_write_timeout(Some(CLIENT_TIMEOUT))?;
+    let mut request = Vec::new();
+    let mut buf = [0; 1024];
+    while !request.ends_with(b"\r\n\r\n") {
+        let read = stream.read(&mut buf)?;
+        if read == 0 || request.len() + read > MAX_REQUEST_SIZE {
+            return Err(io::ErrorKind::InvalidData.into());
+        }
+        request.extend_from_slice(&buf[..read]);
+    }
+    stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\n\
+                       Cache-Control: no-cache\r\nConnection: keep-alive\r\n\r\n")
+}
+
+/// Sends data to all clients, dropping the ones that failed to receive it
+fn broadcast(clients: &mut Vec<TcpStream>, data: &[u8]) {
+    clients.retain(|mut client| client.write_all(data).is_ok());
+}
+
+/// Instrument update, as sent in the event data
+struct Update<'a, I: Instruments<Handle> + 'a> {
+    name: &'static str,
+    instruments: &'a I,
+}
+
+/// Instrument reading
+struct Reading<'a, I: Instruments<Handle> + 'a> {
+    name: &'static str,
+    instruments: &'a I,
+}
+
+impl<'a, I: Instruments<Handle> + 'a> Serialize for Update<'a, I> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where
+        S: Serializer {
+        let mut ss = serializer.serialize_struct("Update", 2)?;
+        ss.serialize_field("name", self.name)?;
+        ss.serialize_field("reading", &Reading { name: self.name, instruments: self.instruments })?;
+        ss.end()
+    }
+}
+
+impl<'a, I: Instruments<Handle> + 'a> Serialize for Reading<'a, I> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where
+        S: Serializer {
+        self.instruments.serialize_reading(self.name, serializer).map_err(|e| match e {
+            ReadError::SerializationError(e) => e,
+            ReadError::NotFound => S::Error::custom(format!("instrument {} not found", self.name)),
+        })
+    }
+}
+
+/// Running [`Publisher`] handle
+///
+/// [`Publisher`]: struct.Publisher.html
+#[derive(Clone)]
+pub struct Handle {
+    sender: mpsc::Sender<Message>,
+}
+
+impl Handle {
+    /// Shutdown the publisher
+    ///
+    /// Blocks until the publisher has processed all updates queued before this call,
+    /// disconnected all clients and [`Publisher#run`] has returned. If the publisher
+    /// has been dropped, returns immediately.
+    ///
+    /// [`Publisher#run`]: struct.Publisher.html#method.run
+    pub fn shutdown(&self) {
+        let (ack_sender, ack_receiver) = mpsc::channel();
+        if self.sender.send(Message::Shutdown(ack_sender)).is_ok() {
+            let _ = ack_receiver.recv();
+        }
+    }
+}
+
+/// [`Handle`] is a [`Listener`] that queues updates for the publisher
+///
+/// [`Handle`]: struct.Handle.html
+/// [`Listener`]: ../trait.Listener.html
+impl Listener for Handle {
+    fn instrument_updated(&self, name: &'static str) {
+        let _ = self.sender.send(Message::Update(name));
+    }
+}
@@ -0,0 +1,281 @@
+// Copyright 2017 All Contributors (see CONTRIBUTORS file)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+//
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+
+//! # System instruments
+//!
+//! _This module is only present if `sysinfo` feature is enabled.
+//! It is disabled by default._
+//!
+//! Provides [lazy instruments] reporting host and process statistics (memory, CPU and
+//! disk usage) using the [`sysinfo`] crate, either individually (through [`SystemStats`])
+//! or as a ready-made [`SystemInstruments`] board.
+//!
+//! Every reading queries the operating system, refreshing only the statistics it needs.
+//! This is fairly cheap for memory and CPU usage, but refreshing disk usage involves a
+//! `statvfs` call per disk which may block, so readings should not be taken in a tight
+//! loop. CPU usage is computed between two consecutive readings, so the first one is
+//! always zero, and readings taken less than [`sysinfo::MINIMUM_CPU_UPDATE_INTERVAL`]
+//! apart are inaccurate.
+//!
+//! ```rust
+//! extern crate rapt;
+//! extern crate serde;
+//! #[macro_use]
+//! extern crate rapt_derive;
+//!
+//! use rapt::{Instrument, Listener};
+//! use rapt::system::{SystemStats, SystemStat};
+//!
+//! #[derive(Instruments)]
+//! struct AppInstruments<L: Listener> {
+//!     #[rapt(unit = "bytes")]
+//!     memory: SystemStat<u64, L>,
+//!     requests: Instrument<u64, L>,
+//! }
+//!
+//! fn main() {
+//!     let stats = SystemStats::new();
+//!     let instruments = AppInstruments::<()> {
+//!         memory: stats.process_memory(),
+//!         requests: Instrument::default(),
+//!     };
+//!     assert!(instruments.memory.read() > 0);
+//! }
+//! ```
+//!
+//! [lazy instruments]: ../lazy/index.html
+//! [`sysinfo`]: https://docs.rs/sysinfo
+//! [`SystemStats`]: struct.SystemStats.html
+//! [`SystemInstruments`]: struct.SystemInstruments.html
+//! [`sysinfo::MINIMUM_CPU_UPDATE_INTERVAL`]: https://docs.rs/sysinfo/0.30/sysinfo/constant.MINIMUM_CPU_UPDATE_INTERVAL.html
+
+use super::{Instruments, LazyInstrument, Listener, ReadError};
+use serde::{Serialize, Serializer};
+use sysinfo::{Disks, Pid, System};
+
+use std::sync::{Arc, Mutex};
+
+/// A lazy instrument reporting a system statistic
+pub type SystemStat<T, L> = LazyInstrument<T, Box<dyn Fn() -> T + Send + Sync>, L>;
+
+/// Sources of statistics, shared by all instruments created by the same [`SystemStats`]
+///
+/// [`SystemStats`]: struct.SystemStats.html
+struct Sources {
+    system: System,
+    disks: Disks,
+    pid: Option<Pid>,
+}
+
+/// Factory of system instruments
+///
+/// Instruments created by the same factory share `sysinfo` state.
+#[derive(Clone)]
+pub struct SystemStats {
+    sources: Arc<Mutex<Sources>>,
+}
+
+impl Default for SystemStats {
+    fn default() -> Self {
+        SystemStats::new()
+    }
+}
+
+impl SystemStats {
+    /// Creates a new factory
+    ///
+    /// Lists disks, which can take a while.
+    pub fn new() -> Self {
+        SystemStats {
+            sources: Arc::new(Mutex::new(Sources {
+                system: System::new(),
+                disks: Disks::new_with_refreshed_list(),
+                pid: sysinfo::get_current_pid().ok(),
+            })),
+        }
+    }
+
+    /// Creates an instrument computing its value from refreshed sources
+    fn stat<T: Serialize, F, L: Listener>(&self, unit: &'static str, f: F) -> SystemStat<T, L>
+        where F: Fn(&mut Sources) -> T + Send + Sync + 'static {
+        let sources = self.sources.clone();
+        let mut instrument = LazyInstrument::new(Box::new(move || {
+            let mut sources = sources.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            f(&mut sources)
+        }) as Box<dyn Fn() -> T + Send + Sync>);
+        instrument.set_unit(unit);
+        instrument
+    }
+
+    /// Total memory of the host, in bytes
+    pub fn memory_total<L: Listener>(&self) -> SystemStat<u64, L> {
+        self.stat("bytes", |sources| {
+            sources.system.refresh_memory();
+            sources.system.total_memory()
+        })
+    }
+
+    /// Used memory of the host, in bytes
+    pub fn memory_used<L: Listener>(&self) -> SystemStat<u64, L> {
+        self.stat("bytes", |sources| {
+            sources.system.refresh_memory();
+            sources.system.used_memory()
+        })
+    }
+
+    /// CPU usage of the host, in percent (of all CPUs)
+    pub fn cpu_usage<L: Listener>(&self) -> SystemStat<f32, L> {
+        self.stat("percent", |sources| {
+            sources.system.refresh_cpu_usage();
+            sources.system.global_cpu_info().cpu_usage()
+        })
+    }
+
+    /// Total space of all disks, in bytes
+    pub fn disk_total<L: Listener>(&self) -> SystemStat<u64, L> {
+        self.stat("bytes", |sources| {
+            sources.disks.refresh();
+            sources.disks.list().iter().map(|disk| disk.total_space()).sum()
+        })
+    }
+
+    /// Available space of all disks, in bytes
+    pub fn disk_available<L: Listener>(&self) -> SystemStat<u64, L> {
+        self.stat("bytes", |sources| {
+            sources.disks.refresh();
+            sources.disks.list().iter().map(|disk| disk.available_space()).sum()
+        })
+    }
+
+    /// Resident memory of the current process, in bytes
+    ///
+    /// Zero if the process can't be inspected.
+    pub fn process_memory<L: Listener>(&self) -> SystemStat<u64, L> {
+        self.stat("bytes", |sources| match sources.pid {
+            Some(pid) if sources.system.refresh_process(pid) =>
+                sources.system.process(pid).map(|process| process.memory()).unwrap_or(0),
+            _ => 0,
+        })
+    }
+
+    /// CPU usage of the current process, in percent (of a single CPU)
+    ///
+    /// Zero if the process can't be inspected.
+    pub fn process_cpu_usage<L: Listener>(&self) -> SystemStat<f32, L> {
+        self.stat("percent", |sources| match sources.pid {
+            Some(pid) if sources.system.refresh_process(pid) =>
+                sources.system.process(pid).map(|process| process.cpu_usage()).unwrap_or(0.0),
+            _ => 0.0,
+        })
+    }
+}
+
+/// Ready-made board of all system instruments
+///
+/// Can be used with a publisher of its own to get host metrics alongside application ones.
+pub struct SystemInstruments<L: Listener> {
+    pub memory_total: SystemStat<u64, L>,
+    pub memory_used: SystemStat<u64, L>,
+    pub cpu_usage: SystemStat<f32, L>,
+    pub disk_total: SystemStat<u64, L>,
+    pub disk_available: SystemStat<u64, L>,
+    pub process_memory: SystemStat<u64, L>,
+    pub process_cpu_usage: SystemStat<f32, L>,
+}
+
+impl<L: Listener> Default for SystemInstruments<L> {
+    fn default() -> Self {
+        SystemInstruments::new(&SystemStats::new())
+    }
+}
+
+impl<L: Listener> SystemInstruments<L> {
+    /// Creates a board of instruments sharing `stats`
+    pub fn new(stats: &SystemStats) -> Self {
+        SystemInstruments {
+            memory_total: stats.memory_total(),
+            memory_used: stats.memory_used(),
+            cpu_usage: stats.cpu_usage(),
+            disk_total: stats.disk_total(),
+            disk_available: stats.disk_available(),
+            process_memory: stats.process_memory(),
+            process_cpu_usage: stats.process_cpu_usage(),
+        }
+    }
+}
+
+impl<L: Listener> Instruments<L> for SystemInstruments<L> {
+    fn serialize_reading<K : AsRef<str>, S: Serializer>(&self, key: K, serializer: S) -> Result<S::Ok, ReadError<S::Error>> {
+        match key.as_ref() {
+            "memory_total" => self.memory_total.serialize(serializer),
+            "memory_used" => self.memory_used.serialize(serializer),
+            "cpu_usage" => self.cpu_usage.serialize(serializer),
+            "disk_total" => self.disk_total.serialize(serializer),
+            "disk_available" => self.disk_available.serialize(serializer),
+            "process_memory" => self.process_memory.serialize(serializer),
+            "process_cpu_usage" => self.process_cpu_usage.serialize(serializer),
+            _ => return Err(ReadError::NotFound),
+        }.map_err(ReadError::SerializationError)
+    }
+
+    fn instrument_names(&self) -> Vec<&'static str> {
+        vec!["memory_total", "memory_used", "cpu_usage", "disk_total", "disk_available",
+             "process_memory", "process_cpu_usage"]
+    }
+
+    fn instrument_unit<K : AsRef<str>>(&self, key: K) -> Option<&'static str> {
+        match key.as_ref() {
+            "memory_total" | "memory_used" | "disk_total" | "disk_available" | "process_memory" => Some("bytes"),
+            "cpu_usage" | "process_cpu_usage" => Some("percent"),
+            _ => None,
+        }
+    }
+
+    fn wire_listener(&mut self, listener: L) {
+        listener.on_wired(&self.instrument_names());
+        self.memory_total.set_name_and_listener("memory_total", listener.clone());
+        self.memory_used.set_name_and_listener("memory_used", listener.clone());
+        self.cpu_usage.set_name_and_listener("cpu_usage", listener.clone());
+        self.disk_total.set_name_and_listener("disk_total", listener.clone());
+        self.disk_available.set_name_and_listener("disk_available", listener.clone());
+        self.process_memory.set_name_and_listener("process_memory", listener.clone());
+        self.process_cpu_usage.set_name_and_listener("process_cpu_usage", listener);
+    }
+}
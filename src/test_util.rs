@@ -0,0 +1,143 @@
+// Copyright 2017 All Contributors (see CONTRIBUTORS file)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+//
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+
+//! # Test utilities
+//!
+//! _This module is only present if `test_util` feature is enabled.
+//! It is disabled by default._
+//!
+//! Testing time-dependent behaviour (such as `last_update_at` timestamps) with real time
+//! requires sleeping and is prone to flakiness. [`ManualClock`] replaces the clock used
+//! for timestamps with one that only moves when told to.
+//!
+//! [`ManualClock`]: struct.ManualClock.html
+
+use chrono::prelude::*;
+
+use std::cell::RefCell;
+use std::marker::PhantomData;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+thread_local! {
+    static CLOCK: RefCell<Option<ManualClock>> = const { RefCell::new(None) };
+}
+
+/// Returns current time of the clock installed for the current thread, if any
+pub(crate) fn now() -> Option<DateTime<Utc>> {
+    CLOCK.with(|clock| clock.borrow().as_ref().map(ManualClock::now))
+}
+
+/// A clock that only moves when advanced
+///
+/// Once installed, it is used for timestamps taken by the current thread (such as instrument
+/// update times), until the returned guard is dropped. Clones share the time, so a clock
+/// can be installed into multiple threads and advanced from any of them.
+///
+/// Only timestamps are affected. Intervals (such as [`Instrument#with_min_notify_interval`])
+/// are measured with the monotonic clock.
+///
+/// ```rust
+/// extern crate rapt;
+/// extern crate chrono;
+///
+/// use std::time::Duration;
+/// use chrono::prelude::*;
+/// use rapt::test_util::ManualClock;
+///
+/// fn main() {
+///     let clock = ManualClock::new(Utc.timestamp_opt(1_500_000_000, 0).unwrap());
+///     let _guard = clock.install();
+///     clock.advance(Duration::from_secs(60));
+///     assert_eq!(clock.now().timestamp(), 1_500_000_060);
+/// }
+/// ```
+///
+/// [`Instrument#with_min_notify_interval`]: ../struct.Instrument.html#method.with_min_notify_interval
+#[derive(Debug, Clone)]
+pub struct ManualClock {
+    now: Arc<Mutex<DateTime<Utc>>>,
+}
+
+impl ManualClock {
+    /// Creates a clock starting at `start`
+    pub fn new(start: DateTime<Utc>) -> Self {
+        ManualClock { now: Arc::new(Mutex::new(start)) }
+    }
+
+    /// Returns current time
+    pub fn now(&self) -> DateTime<Utc> {
+        *self.now.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Moves the clock forward by `duration`
+    pub fn advance(&self, duration: Duration) {
+        let duration = chrono::Duration::from_std(duration).expect("duration is out of range");
+        let mut now = self.now.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        *now += duration;
+    }
+
+    /// Installs the clock for the current thread, until the guard is dropped
+    ///
+    /// The previously installed clock (if any) is restored afterwards.
+    pub fn install(&self) -> ClockGuard {
+        let previous = CLOCK.with(|clock| clock.borrow_mut().replace(self.clone()));
+        ClockGuard { previous, thread_bound: PhantomData }
+    }
+}
+
+/// Restores the previous clock when dropped
+///
+/// Created by [`ManualClock#install`].
+///
+/// [`ManualClock#install`]: struct.ManualClock.html#method.install
+#[must_use = "the clock is uninstalled as soon as the guard is dropped"]
+pub struct ClockGuard {
+    previous: Option<ManualClock>,
+    /// The guard must be dropped by the thread it was created by
+    thread_bound: PhantomData<*const ()>,
+}
+
+impl Drop for ClockGuard {
+    fn drop(&mut self) {
+        let previous = self.previous.take();
+        CLOCK.with(|clock| *clock.borrow_mut() = previous);
+    }
+}
@@ -0,0 +1,154 @@
+// Copyright 2017 All Contributors (see CONTRIBUTORS file)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+//
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! # Thresholds
+//!
+//! Numeric instruments often jitter within a range that isn't worth reporting. A
+//! [`Threshold`] set with [`Instrument#with_threshold`] makes such instrument notify its
+//! listener only once the value has moved far enough from the last significant one.
+//! Unlike deduplication of serialized readings, it compares the values themselves.
+//!
+//! [`Threshold`]: enum.Threshold.html
+//! [`Instrument#with_threshold`]: ../struct.Instrument.html#method.with_threshold
+
+use std::sync::{Arc, Mutex};
+
+/// Minimum change of a numeric value worth notifying about
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Threshold {
+    /// The value must change by more than the given amount
+    Absolute(f64),
+    /// The value must change by more than the given fraction of the last significant
+    /// value (such as `0.05` for 5%)
+    Relative(f64),
+}
+
+impl Threshold {
+    /// Returns `true` if the change from `from` to `to` exceeds the threshold
+    ///
+    /// Changes to or from NaN are always significant.
+    pub fn is_exceeded(&self, from: f64, to: f64) -> bool {
+        let limit = match *self {
+            Threshold::Absolute(delta) => delta,
+            Threshold::Relative(fraction) => fraction * from.abs(),
+        };
+        (to - from).abs() > limit || to.is_nan() || from.is_nan()
+    }
+}
+
+/// Numeric values thresholds can be applied to
+pub trait Numeric {
+    /// Converts the value to `f64`, possibly losing precision
+    fn to_f64(&self) -> f64;
+}
+
+macro_rules! numeric {
+    ($($ty: ty),*) => {
+        $(
+        impl Numeric for $ty {
+            fn to_f64(&self) -> f64 {
+                *self as f64
+            }
+        }
+        )*
+    };
+}
+
+numeric!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize, f32, f64);
+
+/// Threshold of an instrument, along with the last significant value
+///
+/// The value is shared by all clones of the instrument.
+pub(crate) struct InstrumentThreshold<T> {
+    threshold: Threshold,
+    bump_timestamp: bool,
+    value: fn(&T) -> f64,
+    significant: Arc<Mutex<f64>>,
+}
+
+impl<T> Clone for InstrumentThreshold<T> {
+    fn clone(&self) -> Self {
+        InstrumentThreshold {
+            threshold: self.threshold,
+            bump_timestamp: self.bump_timestamp,
+            value: self.value,
+            significant: self.significant.clone(),
+        }
+    }
+}
+
+impl<T: Numeric> InstrumentThreshold<T> {
+    pub(crate) fn new(threshold: Threshold, bump_timestamp: bool, current: &T) -> Self {
+        InstrumentThreshold {
+            threshold,
+            bump_timestamp,
+            value: T::to_f64,
+            significant: Arc::new(Mutex::new(current.to_f64())),
+        }
+    }
+}
+
+impl<T> InstrumentThreshold<T> {
+    /// Returns `true` if the change to `data` is significant, recording it as the last
+    /// significant value if so
+    pub(crate) fn is_significant(&self, data: &T) -> bool {
+        let value = (self.value)(data);
+        let mut significant = self.significant.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if self.threshold.is_exceeded(*significant, value) {
+            *significant = value;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Same threshold, starting over from the `data`
+    pub(crate) fn restart(&self, data: &T) -> Self {
+        InstrumentThreshold {
+            significant: Arc::new(Mutex::new((self.value)(data))),
+            ..self.clone()
+        }
+    }
+
+    /// Returns `true` if insignificant changes still bump the timestamp
+    #[cfg(feature = "timestamp_instruments")]
+    pub(crate) fn bumps_timestamp(&self) -> bool {
+        self.bump_timestamp
+    }
+}
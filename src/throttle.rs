@@ -0,0 +1,107 @@
+// Copyright 2017 All Contributors (see CONTRIBUTORS file)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+//
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Coalescing listener notifications, see [`Instrument#with_min_notify_interval`]
+//!
+//! [`Instrument#with_min_notify_interval`]: ../struct.Instrument.html#method.with_min_notify_interval
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Minimum notification interval of an instrument
+///
+/// The state is shared by all clones of the instrument.
+#[derive(Clone)]
+pub(crate) struct Throttle {
+    interval: Duration,
+    state: Arc<Mutex<State>>,
+}
+
+struct State {
+    last_notified_at: Option<Instant>,
+    /// Set when a notification has been held back and is yet to be sent
+    pending: bool,
+}
+
+impl Throttle {
+    pub(crate) fn new(interval: Duration) -> Self {
+        Throttle {
+            interval,
+            state: Arc::new(Mutex::new(State { last_notified_at: None, pending: false })),
+        }
+    }
+
+    /// Same interval, as if there have been no notifications yet
+    pub(crate) fn restart(&self) -> Self {
+        Throttle::new(self.interval)
+    }
+
+    /// Returns `true` if the listener has been notified less than the interval ago,
+    /// holding the notification back, records the notification otherwise
+    pub(crate) fn throttled(&self) -> bool {
+        let mut state = self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let now = Instant::now();
+        match state.last_notified_at {
+            Some(at) if now.duration_since(at) < self.interval => {
+                state.pending = true;
+                true
+            },
+            _ => {
+                state.last_notified_at = Some(now);
+                state.pending = false;
+                false
+            },
+        }
+    }
+
+    /// Returns `true` if a notification has been held back and the interval has passed
+    /// since the last one, recording the notification
+    pub(crate) fn take_pending(&self) -> bool {
+        let mut state = self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let now = Instant::now();
+        match state.last_notified_at {
+            Some(at) if state.pending && now.duration_since(at) >= self.interval => {
+                state.last_notified_at = Some(now);
+                state.pending = false;
+                true
+            },
+            _ => false,
+        }
+    }
+}
@@ -0,0 +1,189 @@
+// Copyright 2017 All Contributors (see CONTRIBUTORS file)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+//
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+
+//! # Uptime
+//!
+//! [`Uptime`] is an instrument capturing the time it was created at, typically
+//! along with the instrument board it belongs to. It provides a built-in uptime source,
+//! without the application having to keep track of its start time.
+//!
+//! [`Uptime`]: struct.Uptime.html
+
+use serde::{Serialize, Serializer, Deserializer};
+use serde::ser::SerializeStruct;
+
+#[cfg(feature = "timestamp_instruments")]
+use chrono::prelude::*;
+
+use std::marker::PhantomData;
+use std::time::{Duration, Instant};
+
+use super::{LastUpdated, Listener, Metadata, Restore, Timestamp};
+#[cfg(feature = "timestamp_instruments")]
+use super::UpdatedSince;
+
+/// An instrument reporting the time since its creation
+///
+/// Serializes the same way as [`Instrument`] does, except for the `last_update_at` field
+/// which is never present. The value contains `uptime` (in seconds) and, if
+/// `timestamp_instruments` feature is enabled, `started_at`.
+///
+/// Since uptime changes continuously, listeners are never notified about it.
+///
+/// ```rust
+/// extern crate rapt;
+/// extern crate serde;
+/// #[macro_use]
+/// extern crate rapt_derive;
+///
+/// use rapt::{Instrument, Listener};
+/// use rapt::uptime::Uptime;
+///
+/// #[derive(Instruments, Default)]
+/// struct AppInstruments<L: Listener> {
+///     uptime: Uptime<L>,
+///     requests: Instrument<u64, L>,
+/// }
+///
+/// fn main() {
+///     let instruments = AppInstruments::<()>::default();
+///     assert!(instruments.uptime.uptime().as_secs() < 60);
+/// }
+/// ```
+///
+/// [`Instrument`]: ../struct.Instrument.html
+pub struct Uptime<L: Listener> {
+    started: Instant,
+    #[cfg(feature = "timestamp_instruments")]
+    started_at: DateTime<Utc>,
+    metadata: Metadata,
+    listener: PhantomData<L>,
+}
+
+impl<L: Listener> Clone for Uptime<L> {
+    fn clone(&self) -> Self {
+        Uptime {
+            started: self.started,
+            #[cfg(feature = "timestamp_instruments")]
+            started_at: self.started_at,
+            metadata: self.metadata,
+            listener: PhantomData,
+        }
+    }
+}
+
+impl<L: Listener> Default for Uptime<L> {
+    fn default() -> Self {
+        Uptime::new()
+    }
+}
+
+impl<L: Listener> Uptime<L> {
+    /// Creates a new instrument, capturing current time as the start time
+    pub fn new() -> Self {
+        Uptime {
+            started: Instant::now(),
+            #[cfg(feature = "timestamp_instruments")]
+            started_at: super::now(),
+            metadata: Metadata::default(),
+            listener: PhantomData,
+        }
+    }
+
+    metadata_accessors!(metadata);
+    on_demand_wiring!(L);
+
+    /// Returns the start time
+    ///
+    /// _Only available if `timestamp_instruments` feature is enabled._
+    #[cfg(feature = "timestamp_instruments")]
+    pub fn started_at(&self) -> DateTime<Utc> {
+        self.started_at
+    }
+
+    /// Returns the time elapsed since the start
+    pub fn uptime(&self) -> Duration {
+        self.started.elapsed()
+    }
+}
+
+/// Serialized value of `Uptime`
+struct UptimeValue<'a, L: Listener + 'a>(&'a Uptime<L>);
+
+impl<'a, L: Listener + 'a> Serialize for UptimeValue<'a, L> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where
+        S: Serializer {
+        let len = if cfg!(feature = "timestamp_instruments") { 2 } else { 1 };
+        let mut ss = serializer.serialize_struct("Uptime", len)?;
+        #[cfg(feature = "timestamp_instruments")]
+        ss.serialize_field("started_at", &self.0.started_at)?;
+        ss.serialize_field("uptime", &self.0.uptime().as_secs_f64())?;
+        ss.end()
+    }
+}
+
+impl<L: Listener> Serialize for Uptime<L> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where
+        S: Serializer {
+        self.metadata.serialize_computed(serializer, UptimeValue(self))
+    }
+}
+
+/// Always `true`, as the value is computed on demand
+#[cfg(feature = "timestamp_instruments")]
+impl<L: Listener> UpdatedSince<DateTime<Utc>> for Uptime<L> {
+    fn updated_since(&self, _since: &DateTime<Utc>) -> bool {
+        true
+    }
+}
+
+/// Ignores the reading, as the value is computed on demand
+impl<L: Listener> Restore for Uptime<L> {
+    fn restore<'de, D: Deserializer<'de>>(&self, _reading: D) -> Result<(), D::Error> {
+        Ok(())
+    }
+}
+
+/// Never present, as the value is computed on demand
+impl<L: Listener> LastUpdated for Uptime<L> {
+    fn last_update_at(&self) -> Option<Timestamp> {
+        None
+    }
+}
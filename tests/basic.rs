@@ -40,12 +40,11 @@
 include!("includes/common.rs");
 
 use rapt::*;
-use serde::Serialize;
 
 use std::thread;
 use std::time::Duration;
 
-#[derive(Clone, Serialize, Default, Debug)]
+#[derive(Clone, Serialize, Default, Debug, PartialEq)]
 struct Datapoint {
     indicator: u32,
 }
@@ -108,8 +107,8 @@ fn multithread() {
         }
     });
 
-    let _ = t1.join().unwrap();
-    let _ = t2.join().unwrap();
+    t1.join().unwrap();
+    t2.join().unwrap();
 
     assert_eq!(i.datapoint.read().unwrap().indicator, 20000);
 }
@@ -121,8 +120,7 @@ use std::sync::mpsc;
 fn listener() {
     let (tx, rx) = mpsc::channel();
 
-    let mut i = TestInstruments::default();
-    i.wire_listener(tx);
+    let i = TestInstruments::with_listener(tx);
 
     // We should have the first notification already (from the wiring)
     let res = rx.recv_timeout(Duration::from_millis(100));
@@ -132,11 +130,890 @@ fn listener() {
     // No more notifications at this time
     assert!(rx.try_recv().is_err());
 
-    let _ = i.datapoint.update(|v| v.indicator = 100).unwrap();
+    i.datapoint.update(|v| v.indicator = 100).unwrap();
 
     // We should have a new notification
     let res = rx.recv_timeout(Duration::from_millis(100));
     assert!(res.is_ok());
     assert_eq!(res.unwrap(), "datapoint");
 
-}
\ No newline at end of file
+}
+
+#[derive(Debug, PartialEq)]
+struct Notification {
+    name: &'static str,
+}
+
+impl From<&'static str> for Notification {
+    fn from(name: &'static str) -> Self {
+        Notification { name }
+    }
+}
+
+#[test]
+// Tests delivering structured messages over a channel
+fn channel_listener() {
+    let (tx, rx) = mpsc::channel::<Notification>();
+
+    let mut i = TestInstruments::default();
+    i.wire_listener(ChannelListener::new(tx));
+    assert_eq!(rx.recv_timeout(Duration::from_millis(100)).unwrap(), Notification { name: "datapoint" });
+
+    let (tx, rx) = mpsc::channel();
+    let mut i = TestInstruments::default();
+    i.wire_listener(ChannelListener::with_conversion(tx, |name| name.len()));
+    assert_eq!(rx.recv_timeout(Duration::from_millis(100)).unwrap(), 9);
+}
+
+#[test]
+// Tests that the change callback is invoked with new values before the listener is notified
+fn on_change() {
+    use std::sync::Mutex;
+
+    let (tx, rx) = mpsc::channel();
+    let callback_tx = Mutex::new(tx.clone());
+    let mut i = Instrument::<u8, mpsc::Sender<&'static str>>::new(0)
+        .with_min_notify_interval(Duration::from_secs(60))
+        .on_change(move |v| callback_tx.lock().unwrap().send(if *v == 1 { "one" } else { "other" }).unwrap());
+    i.set_name("value");
+    i.set_listener(tx);
+
+    i.update(|v| *v = 1).unwrap();
+    assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec!["one", "value"]);
+    // The listener is throttled, but the callback isn't
+    i.clone().update(|v| *v = 2).unwrap();
+    assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec!["other"]);
+    assert!(i.deep_clone().update(|v| *v = 1).is_ok());
+    assert!(rx.try_recv().is_err());
+}
+
+#[test]
+// Tests that replacing the listener returns the previous one without notifying the new one
+fn replace_listener() {
+    let (tx, rx) = mpsc::channel();
+    let mut i = Instrument::<u8, mpsc::Sender<&'static str>>::new(0);
+    i.set_name("value");
+    assert!(i.replace_listener(tx.clone()).is_none());
+    assert!(rx.try_recv().is_err());
+
+    let (new_tx, new_rx) = mpsc::channel();
+    let old = i.replace_listener(new_tx).unwrap();
+    drop(tx);
+    drop(old);
+    // The previous listener has been handed over, so its channel is closed
+    assert_eq!(rx.recv(), Err(mpsc::RecvError));
+    assert!(new_rx.try_recv().is_err());
+
+    i.update(|v| *v = 1).unwrap();
+    assert_eq!(new_rx.try_recv().unwrap(), "value");
+}
+
+#[test]
+// Tests that notifications from different boards are told apart by their tags
+fn tagged_listener() {
+    let (tx, rx) = mpsc::channel();
+
+    let mut storage = TestInstruments::default();
+    storage.wire_listener(TaggedListener::new("storage", tx.clone()));
+    let mut network = TestInstruments::default();
+    network.wire_listener(TaggedListener::new("network", tx).with_separator("."));
+    assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec!["storage/datapoint", "network.datapoint"]);
+
+    network.datapoint.update(|v| v.indicator = 1).unwrap();
+    storage.datapoint.update(|v| v.indicator = 1).unwrap();
+    let names: Vec<_> = rx.try_iter().collect();
+    assert_eq!(names, vec!["network.datapoint", "storage/datapoint"]);
+    // Interned names are reused
+    storage.datapoint.update(|v| v.indicator = 2).unwrap();
+    assert!(std::ptr::eq(rx.try_recv().unwrap(), names[1]));
+}
+
+#[test]
+#[cfg(feature = "timestamp_instruments")]
+// Tests the structure of the serialized instrument
+fn serialization_structure() {
+    let i = TestInstruments::<()>::default();
+    i.datapoint.update(|v| v.indicator = 42).unwrap();
+
+    let mut ser = serde_json::Serializer::new(Vec::with_capacity(128));
+    assert!(i.serialize_reading("datapoint", &mut ser).is_ok());
+    let v: serde_json::Value = serde_json::from_slice(&ser.into_inner()).unwrap();
+
+    let obj = v.as_object().unwrap();
+    assert_eq!(obj.len(), 2);
+    assert_eq!(obj["value"], serde_json::json!({ "indicator": 42 }));
+    // RFC 3339 timestamp
+    assert!(obj["last_update_at"].as_str().unwrap().contains('T'));
+}
+
+#[test]
+#[cfg(not(feature = "timestamp_instruments"))]
+// Tests the structure of the serialized instrument
+fn serialization_structure() {
+    let i = TestInstruments::<()>::default();
+    i.datapoint.update(|v| v.indicator = 42).unwrap();
+
+    let mut ser = serde_json::Serializer::new(Vec::with_capacity(128));
+    assert!(i.serialize_reading("datapoint", &mut ser).is_ok());
+    let v: serde_json::Value = serde_json::from_slice(&ser.into_inner()).unwrap();
+
+    assert_eq!(v, serde_json::json!({ "value": { "indicator": 42 } }));
+}
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+#[derive(Instruments)]
+struct AtomicInstruments<L: Listener> {
+    requests: AtomicView<AtomicU64, L>,
+}
+
+#[test]
+// Tests that atomic views serialize current value of the atomic
+fn atomic_view() {
+    let requests = Arc::new(AtomicU64::new(0));
+    let mut i = AtomicInstruments::<()> { requests: AtomicView::new(requests.clone()) };
+    i.wire_listener(());
+
+    requests.fetch_add(42, Ordering::Relaxed);
+
+    let mut ser = serde_json::Serializer::new(Vec::with_capacity(128));
+    assert!(i.serialize_reading("requests", &mut ser).is_ok());
+    let v: serde_json::Value = serde_json::from_slice(&ser.into_inner()).unwrap();
+    assert_eq!(v, serde_json::json!({ "value": 42 }));
+}
+
+#[derive(Instruments)]
+struct SilentInstruments<L: Listener> {
+    datapoint: Instrument<Datapoint, L>,
+    #[rapt(initial = false)]
+    event: Instrument<Datapoint, L>,
+}
+
+#[test]
+// Tests that instruments with `initial = false` don't notify the listener when wired
+fn no_initial_notification() {
+    let (tx, rx) = mpsc::channel();
+
+    let mut i = SilentInstruments { datapoint: Instrument::default(), event: Instrument::default() };
+    i.wire_listener(tx);
+
+    assert_eq!(rx.recv_timeout(Duration::from_millis(100)).unwrap(), "datapoint");
+    assert!(rx.try_recv().is_err());
+
+    i.event.update(|v| v.indicator = 1).unwrap();
+    assert_eq!(rx.recv_timeout(Duration::from_millis(100)).unwrap(), "event");
+}
+
+#[cfg(feature = "timestamp_instruments")]
+#[derive(Instruments)]
+struct ErrorInstruments<L: Listener> {
+    errors: error_log::ErrorLogInstrument<L>,
+}
+
+#[test]
+#[cfg(feature = "timestamp_instruments")]
+// Tests that error log keeps recent errors only and notifies the listener about each of them
+fn error_log() {
+    let (tx, rx) = mpsc::channel();
+
+    let mut i = ErrorInstruments { errors: error_log::ErrorLogInstrument::new(2) };
+    i.wire_listener(tx);
+    assert_eq!(rx.recv_timeout(Duration::from_millis(100)).unwrap(), "errors");
+
+    for err in &["timeout", "refused", "reset"] {
+        i.errors.record(err).unwrap();
+        assert_eq!(rx.recv_timeout(Duration::from_millis(100)).unwrap(), "errors");
+    }
+
+    let mut ser = serde_json::Serializer::new(Vec::with_capacity(128));
+    assert!(i.serialize_reading("errors", &mut ser).is_ok());
+    let v: serde_json::Value = serde_json::from_slice(&ser.into_inner()).unwrap();
+    let log = v["value"].as_array().unwrap();
+    assert_eq!(log.len(), 2);
+    assert_eq!(log[0][1], "refused");
+    assert_eq!(log[1][1], "reset");
+}
+
+#[test]
+// Tests that updates tell whether the listener has been notified
+fn update_notified() {
+    let mut i = TestInstruments::<()>::default();
+    assert!(!i.datapoint.update_notified(|v| v.indicator = 1).unwrap());
+
+    i.wire_listener(());
+    assert!(i.datapoint.update_notified(|v| v.indicator = 2).unwrap());
+}
+
+#[test]
+// Tests that failed fallible updates don't notify the listener
+fn try_update() {
+    let (tx, rx) = mpsc::channel();
+
+    let mut i = TestInstruments::default();
+    i.wire_listener(tx);
+    assert_eq!(rx.recv_timeout(Duration::from_millis(100)).unwrap(), "datapoint");
+
+    assert!(matches!(i.datapoint.try_update(|v| { v.indicator = 1; Err("invalid") }), Err(UpdateError::Rejected("invalid"))));
+    assert!(rx.try_recv().is_err());
+    // Changes made before failing are kept
+    assert_eq!(i.datapoint.read().unwrap().indicator, 1);
+    assert!(!i.datapoint.has_been_updated());
+
+    i.datapoint.try_update(|v| { v.indicator = 2; Ok::<_, ()>(()) }).unwrap();
+    assert_eq!(rx.recv_timeout(Duration::from_millis(100)).unwrap(), "datapoint");
+    assert!(i.datapoint.has_been_updated());
+}
+
+#[test]
+// Tests that compare-and-set only replaces (and notifies about) the expected value
+fn compare_and_set() {
+    let (tx, rx) = mpsc::channel();
+
+    let mut i = TestInstruments::default();
+    i.wire_listener(tx);
+    assert_eq!(rx.recv_timeout(Duration::from_millis(100)).unwrap(), "datapoint");
+
+    assert!(!i.datapoint.compare_and_set(&Datapoint { indicator: 1 }, Datapoint { indicator: 2 }).unwrap());
+    assert!(rx.try_recv().is_err());
+    assert_eq!(i.datapoint.read().unwrap().indicator, 0);
+
+    assert!(i.datapoint.compare_and_set(&Datapoint { indicator: 0 }, Datapoint { indicator: 2 }).unwrap());
+    assert_eq!(rx.recv_timeout(Duration::from_millis(100)).unwrap(), "datapoint");
+    assert_eq!(i.datapoint.read().unwrap().indicator, 2);
+}
+
+#[test]
+// Tests that notifications are coalesced, but updates are not
+fn min_notify_interval() {
+    let (tx, rx) = mpsc::channel();
+
+    let mut i = TestInstruments {
+        datapoint: Instrument::default().with_min_notify_interval(Duration::from_millis(200)),
+    };
+    i.wire_listener(tx);
+    assert_eq!(rx.recv_timeout(Duration::from_millis(100)).unwrap(), "datapoint");
+
+    assert!(i.datapoint.update_notified(|v| v.indicator = 1).unwrap());
+    assert!(!i.datapoint.update_notified(|v| v.indicator = 2).unwrap());
+    assert!(!i.datapoint.update_notified(|v| v.indicator = 3).unwrap());
+    assert_eq!(rx.recv_timeout(Duration::from_millis(100)).unwrap(), "datapoint");
+    assert!(rx.try_recv().is_err());
+    assert_eq!(i.datapoint.read().unwrap().indicator, 3);
+
+    // The last update is held back until the interval has passed
+    assert!(!i.datapoint.notify_pending());
+    thread::sleep(Duration::from_millis(200));
+    assert!(rx.try_recv().is_err());
+    assert!(i.datapoint.notify_pending());
+    assert_eq!(rx.try_recv().unwrap(), "datapoint");
+    assert!(!i.datapoint.notify_pending());
+
+    // Held back notifications are also sent with the next update once the interval has passed
+    assert!(!i.datapoint.update_notified(|v| v.indicator = 4).unwrap());
+    thread::sleep(Duration::from_millis(200));
+    assert!(i.datapoint.update_notified(|v| v.indicator = 5).unwrap());
+    assert_eq!(rx.try_recv().unwrap(), "datapoint");
+    assert!(!i.datapoint.notify_pending());
+}
+
+#[test]
+// Tests that deep clones are detached from the original instrument
+fn deep_clone() {
+    let (tx, rx) = mpsc::channel();
+
+    let mut i = TestInstruments::default();
+    i.datapoint.set_unit("points");
+    i.datapoint.set_description("Test datapoint");
+    i.datapoint.set_schema_version(2);
+    i.wire_listener(tx);
+    assert_eq!(rx.recv_timeout(Duration::from_millis(100)).unwrap(), "datapoint");
+
+    let shared = i.datapoint.clone();
+    let copy = i.datapoint.deep_clone();
+    assert_eq!((copy.unit(), copy.description()), (Some("points"), Some("Test datapoint")));
+    // Serialized the same way, except for the timestamp
+    let reading = |i: &Instrument<Datapoint, mpsc::Sender<&'static str>>| {
+        let mut reading = serde_json::to_value(i).unwrap();
+        reading.as_object_mut().unwrap().remove("last_update_at");
+        reading
+    };
+    assert_eq!(reading(&copy), reading(&i.datapoint));
+    assert_eq!(reading(&copy)["schema_version"], 2);
+
+    i.datapoint.update(|v| v.indicator = 1).unwrap();
+    assert_eq!(rx.recv_timeout(Duration::from_millis(100)).unwrap(), "datapoint");
+    assert_eq!(shared.read().unwrap().indicator, 1);
+    assert_eq!(copy.read().unwrap().indicator, 0);
+
+    // The copy has no listener
+    copy.update(|v| v.indicator = 2).unwrap();
+    assert!(rx.try_recv().is_err());
+    assert_eq!(i.datapoint.read().unwrap().indicator, 1);
+}
+
+#[test]
+// Tests that scoped values are set back once the guard is dropped, even when panicking
+fn scoped() {
+    let i = Instrument::<u8, ()>::new(0);
+    {
+        let _guard = i.scoped(1, 2).unwrap();
+        assert_eq!(*i.read().unwrap(), 1);
+    }
+    assert_eq!(*i.read().unwrap(), 2);
+
+    let cloned = i.clone();
+    let res = thread::spawn(move || {
+        let _guard = cloned.scoped(3, 4).unwrap();
+        panic!("failed operation");
+    }).join();
+    assert!(res.is_err());
+    assert_eq!(*i.read().unwrap(), 4);
+}
+
+#[test]
+// Tests that only actual updates (not wiring) mark instruments as updated
+fn updated_instruments() {
+    let mut i = SilentInstruments::<()> { datapoint: Instrument::default(), event: Instrument::default() };
+    i.wire_listener(());
+
+    assert!(i.updated_instrument_names().is_empty());
+    assert!(!i.instrument_has_been_updated("event"));
+
+    i.event.update(|v| v.indicator = 1).unwrap();
+    assert_eq!(i.updated_instrument_names(), vec!["event"]);
+    assert!(i.instrument_has_been_updated("event"));
+    assert!(i.event.deep_clone().has_been_updated());
+    assert!(!i.instrument_has_been_updated("missing"));
+}
+
+#[test]
+// Tests that instruments poisoned by a panicking update are reported
+fn poisoned_instruments() {
+    let mut i = SilentInstruments::<()> { datapoint: Instrument::default(), event: Instrument::default() };
+    i.wire_listener(());
+    assert!(i.poisoned_instruments().is_empty());
+
+    let event = i.event.clone();
+    assert!(thread::spawn(move || event.update(|_| panic!("update failed"))).join().is_err());
+
+    assert!(i.event.is_poisoned());
+    assert!(i.instrument_is_poisoned("event"));
+    assert_eq!(i.poisoned_instruments(), vec!["event"]);
+}
+
+#[test]
+// Tests that poisoned values are told apart from null ones only if requested
+fn poison_reporting() {
+    let plain = Instrument::<Option<u8>, ()>::new(None);
+    let reporting = Instrument::<Option<u8>, ()>::new(None).with_poison_reporting();
+    let v = serde_json::to_value(&reporting).unwrap();
+    assert!(v["value"].is_null());
+    assert!(v.get("error").is_none());
+
+    for i in &[plain.clone(), reporting.clone()] {
+        let i = i.clone();
+        assert!(thread::spawn(move || i.update(|_| panic!("update failed"))).join().is_err());
+    }
+    assert!(serde_json::to_value(&plain).unwrap().get("error").is_none());
+    let v = serde_json::to_value(&reporting).unwrap();
+    assert!(v["value"].is_null());
+    assert_eq!(v["error"], "poisoned");
+    assert_eq!(serde_json::to_value(reporting.deep_clone()).unwrap().get("error"), None);
+}
+
+#[test]
+// Tests that poisoned instruments are still serialized with their last-known value
+fn poisoned_value() {
+    let progress = Instrument::<u8, ()>::new(0);
+    let updater = progress.clone();
+    assert!(thread::spawn(move || updater.update(|v| { *v = 50; panic!("update failed") })).join().is_err());
+    assert!(progress.read().is_err());
+    assert_eq!(*progress.read_poisoned(), 50);
+    assert_eq!(serde_json::to_value(&progress).unwrap()["value"], 50);
+    let v = progress.serialize_mapped(serde_json::value::Serializer, |v| v * 2).unwrap();
+    assert_eq!(v["value"], 100);
+}
+
+#[derive(Instruments)]
+struct LazyInstruments<L: Listener> {
+    computed: LazyInstrument<u64, fn() -> u64, L>,
+}
+
+static LAZY_SOURCE: AtomicU64 = AtomicU64::new(1);
+
+#[test]
+// Tests that lazy instruments compute their value when serialized
+fn lazy_instrument() {
+    let mut i = LazyInstruments::<()> {
+        computed: LazyInstrument::new(|| LAZY_SOURCE.load(Ordering::Relaxed) * 10),
+    };
+    i.wire_listener(());
+
+    LAZY_SOURCE.store(4, Ordering::Relaxed);
+
+    let mut ser = serde_json::Serializer::new(Vec::with_capacity(128));
+    assert!(i.serialize_reading("computed", &mut ser).is_ok());
+    let v: serde_json::Value = serde_json::from_slice(&ser.into_inner()).unwrap();
+    assert_eq!(v, serde_json::json!({ "value": 40 }));
+}
+
+#[test]
+// Tests that the poller notifies the listener about significant changes only
+fn poller() {
+    use rapt::poll::Poller;
+
+    let (tx, rx) = mpsc::channel();
+    let counter = Arc::new(AtomicU64::new(0));
+    let counter_ = counter.clone();
+    let load = Arc::new(AtomicU64::new(0));
+    let load_ = load.clone();
+
+    let mut poller = Poller::new(Duration::from_millis(10), tx);
+    poller.watch("counter", move || counter_.load(Ordering::Relaxed))
+          .watch_with("load", move || load_.load(Ordering::Relaxed), |previous, current| current - previous >= 10);
+
+    poller.poll();
+    assert!(rx.try_recv().is_err());
+
+    counter.store(1, Ordering::Relaxed);
+    load.store(5, Ordering::Relaxed);
+    poller.poll();
+    assert_eq!(rx.try_recv().unwrap(), "counter");
+    assert!(rx.try_recv().is_err());
+
+    load.store(10, Ordering::Relaxed);
+    poller.poll();
+    assert_eq!(rx.try_recv().unwrap(), "load");
+    assert!(rx.try_recv().is_err());
+
+    let handle = poller.spawn();
+    counter.store(2, Ordering::Relaxed);
+    assert_eq!(rx.recv_timeout(Duration::from_millis(1000)).unwrap(), "counter");
+    assert!(handle.stop().is_ok());
+}
+
+#[test]
+#[cfg(feature = "lock_contention")]
+// Tests that lock acquisitions waiting for another thread are counted
+fn lock_contention() {
+    let i: Instrument<u64, ()> = Instrument::new(0);
+    let guard = i.read().unwrap();
+
+    let writer = i.clone();
+    let t = thread::spawn(move || writer.update(|v| *v += 1).unwrap());
+    thread::sleep(Duration::from_millis(100));
+    drop(guard);
+    t.join().unwrap();
+
+    let contention = i.lock_contention();
+    assert_eq!(contention.acquisitions, 2);
+    assert_eq!(contention.blocked, 1);
+}
+
+#[test]
+#[cfg(all(feature = "lock_contention", feature = "serde_json"))]
+// Tests that restoring a value acquires the lock the same way updates do
+fn restore_lock_contention() {
+    let i: Instrument<u64, ()> = Instrument::new(0);
+    i.restore(serde_json::json!({"value": 42})).unwrap();
+    assert_eq!(*i.read().unwrap(), 42);
+    assert_eq!(i.lock_contention().acquisitions, 2);
+}
+
+#[derive(Instruments)]
+struct UptimeInstruments<L: Listener> {
+    uptime: uptime::Uptime<L>,
+}
+
+#[test]
+// Tests that uptime is reported since the instrument's creation
+fn uptime() {
+    let mut i = UptimeInstruments::<()> { uptime: uptime::Uptime::new() };
+    i.wire_listener(());
+    thread::sleep(Duration::from_millis(10));
+
+    assert!(i.uptime.uptime() >= Duration::from_millis(10));
+
+    let mut ser = serde_json::Serializer::new(Vec::with_capacity(128));
+    assert!(i.serialize_reading("uptime", &mut ser).is_ok());
+    let v: serde_json::Value = serde_json::from_slice(&ser.into_inner()).unwrap();
+    assert!(v["value"]["uptime"].as_f64().unwrap() >= 0.01);
+    assert_eq!(v["value"].get("started_at").is_some(), cfg!(feature = "timestamp_instruments"));
+}
+
+use rapt::sampled::SampledInstrument;
+
+#[derive(Instruments)]
+struct SampledInstruments<L: Listener> {
+    packets: SampledInstrument<u64, L>,
+}
+
+#[test]
+#[cfg(feature = "serde_json")]
+// Tests that only one in every N updates is applied and notified
+fn sampled_instrument() {
+    let (tx, rx) = mpsc::channel();
+
+    let mut i = SampledInstruments { packets: SampledInstrument::new(0, 3) };
+    i.wire_listener(tx);
+    assert_eq!(rx.recv_timeout(Duration::from_millis(100)).unwrap(), "packets");
+
+    let applied: Vec<Option<u64>> = (0..7).map(|_| i.packets.update(|v| { *v += 1; *v }).unwrap()).collect();
+    assert_eq!(applied, vec![Some(1), None, None, Some(2), None, None, Some(3)]);
+    assert_eq!(*i.packets.read().unwrap(), 3);
+    assert_eq!(rx.try_iter().count(), 3);
+
+    let v = i.to_json_value().unwrap();
+    assert_eq!(v["packets"]["value"], 3);
+    assert_eq!(v["packets"]["sample_rate"], 3);
+}
+
+use rapt::rate::RateInstrument;
+
+#[derive(Instruments)]
+struct RateInstruments<L: Listener> {
+    reconnects: RateInstrument<L>,
+}
+
+#[test]
+#[cfg(feature = "serde_json")]
+// Tests that events are counted and only recent ones contribute to the rate
+fn rate_instrument() {
+    use std::time::Instant;
+
+    let (tx, rx) = mpsc::channel();
+    let mut i = RateInstruments { reconnects: RateInstrument::new(Duration::from_secs(2)) };
+    i.wire_listener(tx);
+    assert_eq!(rx.recv_timeout(Duration::from_millis(100)).unwrap(), "reconnects");
+
+    let start = Instant::now();
+    for ms in &[0, 500, 1000, 1500] {
+        i.reconnects.record_at(start + Duration::from_millis(*ms)).unwrap();
+    }
+    assert_eq!(rx.try_iter().count(), 4);
+    assert_eq!(i.reconnects.rate_at(start + Duration::from_millis(1500)), 2.0);
+    assert_eq!(i.reconnects.rate_at(start + Duration::from_millis(2200)), 1.5);
+    assert_eq!(i.reconnects.rate_at(start + Duration::from_secs(10)), 0.0);
+    assert_eq!(*i.reconnects.read().unwrap(), 4);
+
+    i.reconnects.record().unwrap();
+    let v = i.to_json_value().unwrap();
+    assert_eq!(v["reconnects"]["value"], 5);
+    assert_eq!(v["reconnects"]["rate"], 0.5);
+}
+
+#[test]
+// Tests that a read-only view can be shared with another thread and reflects updates
+fn read_only_instruments() {
+    let mut i = TestInstruments::<()>::default();
+    i.wire_listener(());
+    let i = Arc::new(i);
+    let view = ReadOnlyInstruments::new(i.clone());
+
+    i.datapoint.update(|v| v.indicator = 7).unwrap();
+    let handle = {
+        let view = view.clone();
+        thread::spawn(move || {
+            let mut ser = serde_json::Serializer::new(Vec::with_capacity(128));
+            assert!(view.serialize_reading("datapoint", &mut ser).is_ok());
+            let v: serde_json::Value = serde_json::from_slice(&ser.into_inner()).unwrap();
+            v["value"]["indicator"].as_u64()
+        })
+    };
+    assert_eq!(handle.join().unwrap(), Some(7));
+    assert_eq!(view.updated_instrument_names(), vec!["datapoint"]);
+}
+
+#[derive(Instruments)]
+struct LatencyInstruments<L: Listener> {
+    latency: Instrument<Duration, L>,
+}
+
+#[test]
+// Tests that timing a closure records its duration and returns its result
+fn time() {
+    let (tx, rx) = mpsc::channel();
+
+    let mut i = LatencyInstruments { latency: Instrument::default() };
+    i.wire_listener(tx);
+    assert_eq!(rx.recv_timeout(Duration::from_millis(100)).unwrap(), "latency");
+
+    let result = i.latency.time(|| {
+        thread::sleep(Duration::from_millis(20));
+        "done"
+    });
+    assert_eq!(result, "done");
+    assert!(*i.latency.read().unwrap() >= Duration::from_millis(20));
+    assert_eq!(rx.recv_timeout(Duration::from_millis(100)).unwrap(), "latency");
+}
+
+#[cfg(feature = "serde_json")]
+use rapt::computed::ComputedInstrument;
+
+#[cfg(feature = "serde_json")]
+type Counters<L> = (Instrument<u64, L>, Instrument<u64, L>);
+
+#[cfg(feature = "serde_json")]
+#[derive(Instruments)]
+struct ComputedInstruments<L: Listener> {
+    requests: Instrument<u64, L>,
+    errors: Instrument<u64, L>,
+    error_rate: ComputedInstrument<Counters<L>, f64, L>,
+}
+
+#[cfg(feature = "serde_json")]
+fn error_rate<L: Listener>((requests, errors): &Counters<L>) -> f64 {
+    let requests = *requests.read().unwrap();
+    if requests == 0 { 0.0 } else { *errors.read().unwrap() as f64 / requests as f64 }
+}
+
+#[test]
+#[cfg(feature = "serde_json")]
+// Tests that computed instruments follow their sources
+fn computed_instrument() {
+    let (requests, errors) = (Instrument::new(0), Instrument::new(0));
+    let mut i = ComputedInstruments::<()> {
+        error_rate: ComputedInstrument::new((requests.clone(), errors.clone()), error_rate),
+        requests,
+        errors,
+    };
+    i.wire_listener(());
+
+    assert_eq!(i.to_json_value().unwrap()["error_rate"]["value"], 0.0);
+    i.requests.update(|v| *v = 10).unwrap();
+    i.errors.update(|v| *v = 5).unwrap();
+    assert_eq!(i.to_json_value().unwrap()["error_rate"]["value"], 0.5);
+}
+
+#[test]
+#[cfg(all(feature = "test_util", feature = "serde_json"))]
+// Tests that update timestamps follow the manual clock
+fn manual_clock() {
+    use rapt::test_util::ManualClock;
+
+    let start = serde_json::from_str("\"2017-07-14T02:40:00Z\"").unwrap();
+    let clock = ManualClock::new(start);
+    let _guard = clock.install();
+
+    let i = TestInstruments::<()>::default();
+    let reading = |i: &TestInstruments<()>| i.to_json_value().unwrap()["datapoint"]["last_update_at"].clone();
+    assert_eq!(reading(&i), "2017-07-14T02:40:00Z");
+
+    i.datapoint.update(|v| v.indicator = 1).unwrap();
+    assert_eq!(reading(&i), "2017-07-14T02:40:00Z");
+
+    clock.advance(Duration::from_millis(1500));
+    i.datapoint.update(|v| v.indicator = 2).unwrap();
+    assert_eq!(reading(&i), "2017-07-14T02:40:01.500Z");
+}
+
+#[test]
+#[cfg(all(feature = "test_util", feature = "serde_json"))]
+// Tests that only updates strictly after a given time count
+fn any_updated_since() {
+    use rapt::test_util::ManualClock;
+
+    let clock = ManualClock::new(serde_json::from_str("\"2017-07-14T02:40:00Z\"").unwrap());
+    let _guard = clock.install();
+    let start = clock.now();
+
+    let i = TestInstruments::<()>::default();
+    assert!(!i.any_updated_since(start));
+
+    i.datapoint.update(|v| v.indicator = 1).unwrap();
+    assert!(!i.any_updated_since(start));
+
+    clock.advance(Duration::from_secs(1));
+    i.datapoint.update(|v| v.indicator = 2).unwrap();
+    assert!(i.any_updated_since(start));
+    assert!(!i.any_updated_since(clock.now()));
+}
+
+#[test]
+// Tests that instruments and boards holding values that aren't `Clone` can be cloned
+fn clone_without_clone_value() {
+    use std::sync::mpsc;
+
+    #[derive(Serialize, Default)]
+    struct Connection {
+        id: u32,
+    }
+
+    #[derive(Instruments)]
+    #[rapt(accessors)]
+    struct Connections<L: Listener> {
+        connection: Instrument<Connection, L>,
+    }
+
+    let (tx, rx) = mpsc::channel();
+    let mut board = Connections { connection: Instrument::default() };
+    board.wire_listener(tx);
+    let connection = board.connection();
+    connection.update(|c| c.id = 1).unwrap();
+    assert_eq!(board.connection.read().unwrap().id, 1);
+    assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec!["connection", "connection"]);
+}
+
+#[test]
+#[cfg(feature = "lock_order")]
+// Tests that acquiring a pair of instrument locks in both orders is reported once
+fn lock_order_inversion() {
+    use rapt::lock_order::{self, LockOrderInversion};
+    use std::thread;
+
+    let mut outer = Instrument::<u64, ()>::new(0);
+    outer.set_name("lock_order_outer");
+    let mut inner = Instrument::<u64, ()>::new(0);
+    inner.set_name("lock_order_inner");
+    let reported = || lock_order::inversions().into_iter()
+        .filter(|inversion| inversion.first == Some("lock_order_outer"))
+        .collect::<Vec<_>>();
+
+    for _ in 0..2 {
+        outer.update(|_| inner.update(|v| *v += 1).unwrap()).unwrap();
+    }
+    assert!(reported().is_empty());
+
+    let (outer_clone, inner_clone) = (outer.clone(), inner.clone());
+    thread::Builder::new().name("reverse".into()).spawn(move || {
+        for _ in 0..2 {
+            inner_clone.update(|v| *v += *outer_clone.read().unwrap()).unwrap();
+        }
+    }).unwrap().join().unwrap();
+    assert_eq!(reported(), vec![LockOrderInversion {
+        first: Some("lock_order_outer"),
+        second: Some("lock_order_inner"),
+        thread: Some("reverse".into()),
+    }]);
+}
+
+#[test]
+// Tests that only changes exceeding the threshold notify the listener
+fn threshold() {
+    use rapt::Threshold;
+    use std::sync::mpsc;
+
+    let (tx, rx) = mpsc::channel();
+    let mut connections = Instrument::new(100u32).with_threshold(Threshold::Absolute(10.0), false);
+    connections.set_name_and_listener("connections", tx);
+    assert_eq!(rx.try_iter().count(), 1);
+
+    // Changes are compared against the last significant value, so slow drift is noticed
+    for _ in 0..5 {
+        connections.update(|v| *v += 3).unwrap();
+    }
+    assert_eq!(*connections.read().unwrap(), 115);
+    assert_eq!(rx.try_iter().count(), 1);
+    connections.update(|v| *v -= 5).unwrap();
+    assert_eq!(rx.try_iter().count(), 0);
+
+    // Clones share the last significant value, deep clones start over
+    let copy = connections.deep_clone();
+    connections.clone().update(|v| *v = 100).unwrap();
+    assert_eq!(rx.try_iter().count(), 1);
+    assert!(!copy.update_notified(|v| *v = 120).unwrap());
+
+    assert!(Threshold::Relative(0.1).is_exceeded(100.0, 111.0));
+    assert!(!Threshold::Relative(0.1).is_exceeded(100.0, 90.0));
+    assert!(Threshold::Absolute(1.0).is_exceeded(0.0, f64::NAN));
+}
+
+#[test]
+#[cfg(all(feature = "test_util", feature = "serde_json"))]
+// Tests that insignificant changes only bump the timestamp if asked to
+fn threshold_timestamp() {
+    use rapt::Threshold;
+    use rapt::test_util::ManualClock;
+
+    let clock = ManualClock::new(serde_json::from_str("\"2017-07-14T02:40:00Z\"").unwrap());
+    let _guard = clock.install();
+    let timestamp = |i: &Instrument<f64, ()>| serde_json::to_value(i).unwrap()["last_update_at"].clone();
+
+    let strict = Instrument::new(1.0).with_threshold(Threshold::Relative(0.05), false);
+    let bumped = Instrument::new(1.0).with_threshold(Threshold::Relative(0.05), true);
+    clock.advance(Duration::from_secs(1));
+    strict.update(|v| *v = 1.01).unwrap();
+    bumped.update(|v| *v = 1.01).unwrap();
+    assert_eq!(timestamp(&strict), "2017-07-14T02:40:00Z");
+    assert_eq!(timestamp(&bumped), "2017-07-14T02:40:01Z");
+
+    strict.update(|v| *v = 1.5).unwrap();
+    assert_eq!(timestamp(&strict), "2017-07-14T02:40:01Z");
+}
+
+#[test]
+// Tests that updates return the closure's result and still notify the listener
+fn update_result() {
+    let (tx, rx) = mpsc::channel();
+    let mut requests = Instrument::<u64, _>::new(1);
+    requests.set_name_and_listener("requests", tx);
+    assert_eq!(rx.try_iter().count(), 1);
+
+    let previous = requests.update(|v| std::mem::replace(v, 5)).unwrap();
+    assert_eq!((previous, *requests.read().unwrap()), (1, 5));
+    assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec!["requests"]);
+    requests.update(|v| *v += 1).unwrap();
+    assert_eq!(rx.try_iter().count(), 1);
+}
+
+#[test]
+// Tests that reads give up on a value locked for writing and tell it from a poisoned one
+fn read_timeout() {
+    use std::sync::Barrier;
+    use std::sync::Arc;
+    use std::thread;
+
+    let status = Instrument::<u8, ()>::new(1);
+    let (locked, release) = (Arc::new(Barrier::new(2)), Arc::new(Barrier::new(2)));
+    let writer = {
+        let (status, locked, release) = (status.clone(), locked.clone(), release.clone());
+        thread::spawn(move || status.update(|v| {
+            *v = 2;
+            locked.wait();
+            release.wait();
+        }).unwrap())
+    };
+    locked.wait();
+    assert!(status.try_read().is_none());
+    assert!(matches!(status.read_timeout(Duration::from_millis(20)), Err(ReadTimeoutError::TimedOut)));
+    release.wait();
+    writer.join().unwrap();
+    assert_eq!(*status.try_read().unwrap(), 2);
+
+    let poisoned = status.clone();
+    assert!(thread::spawn(move || poisoned.update(|_| panic!("poisoning"))).join().is_err());
+    assert!(status.try_read().is_none());
+    assert!(matches!(status.read_timeout(Duration::from_millis(20)), Err(ReadTimeoutError::Poisoned)));
+}
+
+#[test]
+// Tests that a snapshot is detached from the instrument and survives poisoning
+fn snapshot() {
+    use std::thread;
+
+    let peers = Instrument::<Vec<u8>, ()>::new(vec![1, 2]);
+    let snapshot = peers.snapshot();
+    peers.update(|v| v.push(3)).unwrap();
+    assert_eq!(snapshot, vec![1, 2]);
+
+    let poisoned = peers.clone();
+    assert!(thread::spawn(move || poisoned.update(|v| { v.push(4); panic!("poisoning") })).join().is_err());
+    assert!(peers.is_poisoned());
+    assert_eq!(peers.snapshot(), vec![1, 2, 3, 4]);
+}
+
+#[test]
+// Tests that updates leaving the value intact are not completed
+fn update_if_changed() {
+    let (tx, rx) = mpsc::channel();
+    let mut state = Instrument::new(vec![1u8]);
+    state.set_name("state");
+    state.set_listener(tx);
+    assert!(!state.update_if_changed(|v| v.sort()).unwrap());
+    assert!(!state.has_been_updated());
+    assert!(rx.try_recv().is_err());
+    assert!(state.update_if_changed(|v| v.push(2)).unwrap());
+    assert!(state.has_been_updated());
+    assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec!["state"]);
+}
@@ -0,0 +1,120 @@
+// Copyright 2017 All Contributors (see CONTRIBUTORS file)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+//
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+
+#![cfg(feature = "serde_json")]
+
+include!("includes/common.rs");
+
+use rapt::*;
+use rapt::dedup::Dedup;
+use rapt::ser::{FieldFilter, InstantiateSerializer, IntoWriter, JsonSerializer};
+
+#[derive(Serialize, Default)]
+struct Service {
+    status: u8,
+    requests: u64,
+}
+
+#[derive(Instruments)]
+struct TestInstruments<L: Listener> {
+    service: Instrument<Service, L>,
+}
+
+fn is_changed(dedup: &mut Dedup, i: &TestInstruments<()>) -> bool {
+    let mut ser = JsonSerializer.instantiate_serializer(Vec::new());
+    assert!(i.serialize_reading("service", &mut ser).is_ok());
+    dedup.is_changed(i, "service", &ser.into_writer(), &JsonSerializer)
+}
+
+#[test]
+// Tests that only filtered fields are considered when detecting changes
+fn dedup_filter() {
+    let i = TestInstruments::<()> { service: Instrument::default() };
+    let mut dedup = Dedup::with_filter(FieldFilter::new(vec!["value.status"]));
+
+    assert!(is_changed(&mut dedup, &i));
+    assert!(!is_changed(&mut dedup, &i));
+
+    i.service.update(|v| v.requests += 1).unwrap();
+    assert!(!is_changed(&mut dedup, &i));
+
+    i.service.update(|v| v.status = 1).unwrap();
+    assert!(is_changed(&mut dedup, &i));
+}
+
+#[test]
+#[cfg(feature = "timestamp_instruments")]
+// Tests that repeated values are detected even though the timestamp changes
+fn dedup_ignores_timestamp() {
+    use std::thread;
+    use std::time::Duration;
+
+    let i = TestInstruments::<()> { service: Instrument::default() };
+    let mut dedup = Dedup::default();
+    let mut entire = Dedup::entire();
+
+    assert!(is_changed(&mut dedup, &i));
+    assert!(is_changed(&mut entire, &i));
+
+    thread::sleep(Duration::from_millis(10));
+    i.service.update(|v| v.status = 0).unwrap();
+
+    assert!(!is_changed(&mut dedup, &i));
+    assert!(is_changed(&mut entire, &i));
+}
+
+#[test]
+// Tests that the checksum is stable and follows the serialized payload
+fn checksummed_reading() {
+    use rapt::dedup::{checksum, serialize_checksummed};
+
+    // FNV-1a test vectors
+    assert_eq!(checksum(b""), 0xcbf29ce484222325);
+    assert_eq!(checksum(b"a"), 0xaf63dc4c8601ec8c);
+
+    let i = TestInstruments::<()> { service: Instrument::default() };
+    let (payload, first) = serialize_checksummed(&i, "service", &JsonSerializer).unwrap();
+    assert_eq!(first, checksum(&payload));
+    assert_eq!(serialize_checksummed(&i, "service", &JsonSerializer).unwrap().1, first);
+
+    i.service.update(|v| v.status = 1).unwrap();
+    assert_ne!(serialize_checksummed(&i, "service", &JsonSerializer).unwrap().1, first);
+    assert!(matches!(serialize_checksummed(&i, "unknown", &JsonSerializer), Err(ReadError::NotFound)));
+}
@@ -0,0 +1,86 @@
+// Copyright 2017 All Contributors (see CONTRIBUTORS file)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+//
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+
+
+#![cfg(all(feature = "dynamic_instruments", feature = "serde_json"))]
+
+extern crate rapt;
+extern crate serde_json;
+
+use rapt::*;
+use rapt::dynamic::DynamicBoard;
+
+#[test]
+// Tests registering and unregistering instruments at runtime
+fn dynamic_board() {
+    let mut board = DynamicBoard::<()>::new();
+    board.wire_listener(());
+
+    let requests = board.register("requests", Instrument::new(1u64)).ok().unwrap();
+    board.register("status", Instrument::new("up")).ok().unwrap();
+    assert!(board.register("requests", Instrument::new(2u64)).is_err());
+
+    requests.update(|v| *v += 1).unwrap();
+    assert_eq!(board.instrument_names(), vec!["requests", "status"]);
+    assert!(board.instrument_has_been_updated("requests"));
+    let v = board.to_json_value().unwrap();
+    assert_eq!(v["requests"]["value"], 2);
+    assert_eq!(v["status"]["value"], "up");
+
+    assert!(board.unregister("requests").is_some());
+    assert!(board.unregister("requests").is_none());
+    assert_eq!(board.instrument_names(), vec!["status"]);
+}
+
+#[test]
+// Tests that registered instruments can be recovered with their concrete types
+fn downcast() {
+    let mut board = DynamicBoard::<()>::new();
+    let requests = board.register("requests", Instrument::new(1u64)).ok().unwrap();
+
+    let instrument = board.get("requests").unwrap();
+    assert!(instrument.downcast_ref::<u32>().is_none());
+    let typed = instrument.downcast_ref::<u64>().unwrap();
+    requests.update(|v| *v += 1).unwrap();
+    assert_eq!(*typed.read().unwrap(), 2);
+
+    assert!(board.unregister("requests").unwrap().downcast_ref::<u64>().is_some());
+    assert!(board.get("requests").is_none());
+}
@@ -0,0 +1,78 @@
+// Copyright 2017 All Contributors (see CONTRIBUTORS file)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+//
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+
+#![cfg(all(feature = "eventfd_listener", target_os = "linux"))]
+
+include!("includes/common.rs");
+
+use rapt::*;
+use rapt::eventfd::EventFdListener;
+
+use std::fs::File;
+use std::io::Read;
+use std::mem::ManuallyDrop;
+use std::os::unix::io::{AsRawFd, FromRawFd};
+
+#[derive(Serialize, Default)]
+struct Datapoint {
+    indicator: u32,
+}
+
+#[derive(Instruments)]
+struct TestInstruments<L: Listener> {
+    datapoint: Instrument<Datapoint, L>,
+}
+
+#[test]
+// Tests that every update increments eventfd counter
+fn eventfd_counter() {
+    let listener = EventFdListener::new().unwrap();
+    // The file doesn't own the descriptor
+    let mut file = ManuallyDrop::new(unsafe { File::from_raw_fd(listener.as_raw_fd()) });
+
+    let mut i = TestInstruments { datapoint: Instrument::default() };
+    i.wire_listener(listener);
+    i.datapoint.update(|v| v.indicator = 1).unwrap();
+
+    let mut buf = [0u8; 8];
+    file.read_exact(&mut buf).unwrap();
+    // Initial notification and the update
+    assert_eq!(u64::from_ne_bytes(buf), 2);
+}
@@ -0,0 +1,88 @@
+// Copyright 2017 All Contributors (see CONTRIBUTORS file)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+//
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+
+#![cfg(feature = "influx")]
+
+include!("includes/common.rs");
+
+use rapt::*;
+use rapt::influx::{LineProtocol, render_influx_line_protocol};
+
+#[derive(Serialize, Default)]
+struct Service {
+    name: &'static str,
+    status: u8,
+    load: f64,
+}
+
+#[derive(Instruments)]
+struct TestInstruments<L: Listener> {
+    #[rapt(unit = "requests")]
+    requests: Instrument<u64, L>,
+    up: Instrument<bool, L>,
+    service: Instrument<Service, L>,
+    #[rapt(name = "last error")]
+    last_error: Instrument<String, L>,
+}
+
+#[test]
+// Tests that numeric values become fields, units and tags become tags, and the rest is skipped
+fn line_protocol() {
+    let i = TestInstruments::<()> {
+        requests: Instrument::new(42),
+        up: Instrument::new(true),
+        service: Instrument::new(Service { name: "http", status: 1, load: 0.5 }),
+        last_error: Instrument::default(),
+    };
+
+    let mut output = String::new();
+    let skipped = LineProtocol::new(&i).with_tag("hosts", "web-1,web-2").write_to(&mut output).unwrap();
+    assert_eq!(skipped, vec!["last error"]);
+
+    let lines: Vec<Vec<&str>> = output.lines().map(|line| line.splitn(3, ' ').collect()).collect();
+    assert_eq!(lines.iter().map(|line| &line[..2]).collect::<Vec<_>>(), vec![
+        &["requests,unit=requests,hosts=web-1\\,web-2", "value=42i"][..],
+        &["up,hosts=web-1\\,web-2", "value=true"][..],
+        &["service,hosts=web-1\\,web-2", "load=0.5,status=1i"][..],
+    ]);
+    assert!(lines.iter().all(|line| line.len() == if cfg!(feature = "timestamp_instruments") { 3 } else { 2 }));
+
+    assert_eq!(render_influx_line_protocol(&i).lines().count(), 3);
+}
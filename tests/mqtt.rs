@@ -0,0 +1,547 @@
+// Copyright 2017 All Contributors (see CONTRIBUTORS file)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+//
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+#![cfg(all(feature = "mqtt_publisher", feature = "serde_json"))]
+
+include!("includes/common.rs");
+
+use rapt::*;
+use rapt::mqtt::{MapFormatter, PublishClient, PublisherBuilder};
+use rapt::mqtt::client::{self, PubOpt};
+use rapt::ser::JsonSerializer;
+
+use std::collections::HashMap;
+use std::sync::mpsc;
+use std::time::Duration;
+
+#[derive(Serialize, Default, Clone)]
+struct Service {
+    status: u8,
+}
+
+#[derive(Instruments)]
+struct TestInstruments<L: Listener> {
+    service: Instrument<Service, L>,
+    requests: Instrument<u64, L>,
+}
+
+/// Published message: topic, reading and whether it is retained
+type Message = (String, serde_json::Value, bool);
+
+/// Client forwarding published messages over a channel
+struct MockClient {
+    sender: mpsc::Sender<Message>,
+    published: usize,
+}
+
+impl PublishClient for MockClient {
+    fn publish(&mut self, topic: String, payload: Vec<u8>, pubopt: PubOpt) -> client::Result<()> {
+        self.published += 1;
+        // Empty payloads clear retained messages
+        let reading = if payload.is_empty() { serde_json::Value::Null } else { serde_json::from_slice(&payload).unwrap() };
+        self.sender.send((topic, reading, pubopt.is_retain())).unwrap();
+        Ok(())
+    }
+}
+
+fn recv(rx: &mpsc::Receiver<Message>) -> Message {
+    rx.recv_timeout(Duration::from_secs(5)).unwrap()
+}
+
+#[test]
+// Tests the publisher lifecycle: initial readings, updates, deduplication and shutdown
+fn publish() {
+    let (tx, rx) = mpsc::channel();
+    let mut topics = HashMap::new();
+    topics.insert("requests", "site/requests".to_string());
+    let publisher = PublisherBuilder::new(MockClient { sender: tx, published: 0 },
+                                          TestInstruments { service: Instrument::default(), requests: Instrument::new(0) })
+        .topic_formatter(MapFormatter(topics))
+        .retain(true)
+        .build();
+    let (service, requests) = (publisher.instruments().service.clone(), publisher.instruments().requests.clone());
+    let running = publisher.spawn(JsonSerializer);
+
+    // Wiring publishes initial readings of all instruments
+    let (topic, reading, retained) = recv(&rx);
+    assert_eq!((topic.as_str(), &reading["value"], retained), ("service", &serde_json::json!({"status": 0}), true));
+    let (topic, reading, _) = recv(&rx);
+    assert_eq!((topic.as_str(), &reading["value"]), ("site/requests", &serde_json::json!(0)));
+
+    service.update(|v| v.status = 1).unwrap();
+    let (topic, reading, _) = recv(&rx);
+    assert_eq!((topic.as_str(), &reading["value"]["status"]), ("service", &serde_json::json!(1)));
+
+    // Only the timestamp changes, so the reading is a repeated one
+    service.update(|v| v.status = 1).unwrap();
+    requests.update(|v| *v += 1).unwrap();
+    let (topic, reading, _) = recv(&rx);
+    assert_eq!((topic.as_str(), &reading["value"]), ("site/requests", &serde_json::json!(1)));
+
+    let client = running.stop_and_join().ok().unwrap().into_inner();
+    assert_eq!(client.published, 4);
+    assert!(rx.try_recv().is_err());
+}
+
+#[test]
+#[cfg(feature = "timestamp_instruments")]
+// Tests that timestamps count as changes if entire readings are compared
+fn publish_entire_readings() {
+    use rapt::dedup::Dedup;
+
+    let (tx, rx) = mpsc::channel();
+    let publisher = PublisherBuilder::new(MockClient { sender: tx, published: 0 },
+                                          TestInstruments { service: Instrument::default(), requests: Instrument::new(0) })
+        .dedup(Dedup::entire())
+        .build();
+    let service = publisher.instruments().service.clone();
+    let running = publisher.spawn(JsonSerializer);
+    assert_eq!(recv(&rx).0, "service");
+    assert_eq!(recv(&rx).0, "requests");
+
+    let first = recv_after_update(&service, &rx);
+    assert!(!first.2);
+    // Make sure the timestamp changes
+    std::thread::sleep(Duration::from_millis(10));
+    let second = recv_after_update(&service, &rx);
+    assert_eq!(first.1["value"], second.1["value"]);
+    assert_ne!(first.1["last_update_at"], second.1["last_update_at"]);
+
+    assert_eq!(running.stop_and_join().ok().unwrap().into_inner().published, 4);
+}
+
+#[cfg(feature = "timestamp_instruments")]
+fn recv_after_update(service: &Instrument<Service, mqtt::Handle>, rx: &mpsc::Receiver<Message>) -> Message {
+    service.update(|v| v.status = 1).unwrap();
+    recv(rx)
+}
+
+#[test]
+// Tests that publish options are set per instrument and determine whether to clear retained messages
+fn publish_options() {
+    let (tx, rx) = mpsc::channel();
+    let publisher = PublisherBuilder::new(MockClient { sender: tx, published: 0 },
+                                          TestInstruments { service: Instrument::default(), requests: Instrument::new(0) })
+        .publish_options(|name| if name == "service" { PubOpt::retain() } else { PubOpt::at_least_once() })
+        .build();
+    let handle = publisher.handle();
+    let running = publisher.spawn(JsonSerializer);
+    assert!(recv(&rx).2);
+    assert!(!recv(&rx).2);
+
+    handle.set_topic_name("requests", "site/requests");
+    let (topic, reading, retained) = recv(&rx);
+    assert_eq!((topic.as_str(), &reading["value"], retained), ("site/requests", &serde_json::json!(0), false));
+
+    handle.set_topic_name("service", "site/service");
+    assert_eq!(recv(&rx), ("service".to_string(), serde_json::Value::Null, true));
+    assert_eq!(recv(&rx).0, "site/service");
+
+    assert_eq!(running.stop_and_join().ok().unwrap().into_inner().published, 5);
+}
+
+#[test]
+// Tests that a dry run hands messages over to the callback
+fn dry_run() {
+    use rapt::mqtt::DryRun;
+
+    let (tx, rx) = mpsc::channel();
+    let client = DryRun(move |topic: &str, payload: &[u8], _| tx.send((topic.to_string(), payload.to_vec())).unwrap());
+    let publisher = PublisherBuilder::new(client, TestInstruments { service: Instrument::default(), requests: Instrument::new(0) })
+        .topic_formatter(MapFormatter(vec![("service", "site/service".to_string())].into_iter().collect()))
+        .build();
+    let requests = publisher.instruments().requests.clone();
+    let running = publisher.spawn(JsonSerializer);
+    let next = || rx.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(next().0, "site/service");
+    let (topic, payload) = next();
+    assert_eq!((topic.as_str(), payload.starts_with(b"{\"value\":0")), ("requests", true));
+
+    requests.update(|v| *v += 1).unwrap();
+    assert!(next().1.starts_with(b"{\"value\":1"));
+    running.stop_and_join().ok().unwrap();
+}
+
+#[test]
+// Tests that changes are published as patches from the reading published last
+fn publish_diffs() {
+    let (tx, rx) = mpsc::channel();
+    let publisher = PublisherBuilder::new(MockClient { sender: tx, published: 0 },
+                                          TestInstruments { service: Instrument::default(), requests: Instrument::new(0) })
+        .retain(true)
+        .publish_diffs(true)
+        .build();
+    let (handle, service) = (publisher.handle(), publisher.instruments().service.clone());
+    let running = publisher.spawn(JsonSerializer);
+    let (_, reading, retained) = recv(&rx);
+    assert_eq!((&reading["value"], retained), (&serde_json::json!({"status": 0}), true));
+    assert_eq!(recv(&rx).0, "requests");
+
+    service.update(|v| v.status = 1).unwrap();
+    let (topic, patch, retained) = recv(&rx);
+    assert_eq!((topic.as_str(), retained), ("service", false));
+    let patch = patch.as_array().unwrap();
+    assert!(patch.contains(&serde_json::json!({"op": "replace", "path": "/value/status", "value": 1})));
+
+    // Cleared readings are published in full again
+    handle.clear("service");
+    assert_eq!(recv(&rx).1, serde_json::Value::Null);
+    service.update(|v| v.status = 1).unwrap();
+    assert_eq!(recv(&rx).1["value"], serde_json::json!({"status": 1}));
+
+    running.stop_and_join().ok().unwrap();
+}
+
+/// Client failing to publish a number of messages before forwarding them to `MockClient`
+struct FailingClient {
+    client: MockClient,
+    failures: usize,
+}
+
+impl PublishClient for FailingClient {
+    fn publish(&mut self, topic: String, payload: Vec<u8>, pubopt: PubOpt) -> client::Result<()> {
+        if self.failures > 0 {
+            self.failures -= 1;
+            return Err(client::Error::Disconnected);
+        }
+        self.client.publish(topic, payload, pubopt)
+    }
+}
+
+#[test]
+// Tests that failing to publish is counted and doesn't stop the publisher
+fn failed_publishes() {
+    let (tx, rx) = mpsc::channel();
+    let publisher = PublisherBuilder::new(FailingClient { client: MockClient { sender: tx, published: 0 }, failures: 3 },
+                                          TestInstruments { service: Instrument::default(), requests: Instrument::new(0) })
+        .retain(true)
+        .build();
+    let (handle, requests) = (publisher.handle(), publisher.instruments().requests.clone());
+    let running = publisher.spawn(JsonSerializer);
+    handle.clear("service");
+    handle.flush();
+    assert_eq!(handle.failed_publishes(), 3);
+    assert!(rx.try_recv().is_err());
+
+    // The failed reading is published even though the value is the same
+    requests.update(|v| *v = 0).unwrap();
+    let (topic, reading, _) = recv(&rx);
+    assert_eq!((topic.as_str(), &reading["value"]), ("requests", &serde_json::json!(0)));
+
+    assert_eq!(running.stop_and_join().ok().unwrap().into_inner().client.published, 1);
+}
+
+#[test]
+// Tests that flushing waits for queued updates to be published and returns once shut down
+fn flush() {
+    let (tx, rx) = mpsc::channel();
+    let publisher = PublisherBuilder::new(MockClient { sender: tx, published: 0 },
+                                          TestInstruments { service: Instrument::default(), requests: Instrument::new(0) })
+        .build();
+    let (handle, requests) = (publisher.handle(), publisher.instruments().requests.clone());
+    let running = publisher.spawn(JsonSerializer);
+
+    for _ in 0..10 {
+        requests.update(|v| *v += 1).unwrap();
+    }
+    handle.flush();
+    // Readings are taken when updates are processed, so some of them might be repeated ones
+    let published: Vec<Message> = rx.try_iter().collect();
+    assert_eq!(published.last().unwrap().1["value"], serde_json::json!(10));
+
+    running.stop_and_join().ok().unwrap();
+    handle.flush();
+}
+
+#[test]
+// Tests that flapping values are held back until they settle
+fn flap_damping() {
+    use rapt::dedup::FlapDamping;
+    use std::time::Instant;
+
+    let (tx, rx) = mpsc::channel();
+    let publisher = PublisherBuilder::new(MockClient { sender: tx, published: 0 },
+                                          TestInstruments { service: Instrument::default(), requests: Instrument::new(0) })
+        .flap_damping(FlapDamping::new(Duration::from_secs(60), Duration::from_millis(500)))
+        .build();
+    let (handle, requests) = (publisher.handle(), publisher.instruments().requests.clone());
+    let running = publisher.spawn(JsonSerializer);
+    assert_eq!(recv(&rx).0, "service");
+    assert_eq!(recv(&rx).1["value"], 0);
+
+    requests.update(|v| *v = 1).unwrap();
+    assert_eq!(recv(&rx).1["value"], 1);
+
+    // Back to the value published last, nothing to publish
+    requests.update(|v| *v = 0).unwrap();
+    requests.update(|v| *v = 1).unwrap();
+    handle.flush();
+    assert!(rx.try_recv().is_err());
+
+    // Published once it has been current for the cooldown, without further updates
+    let flapped = Instant::now();
+    requests.update(|v| *v = 0).unwrap();
+    handle.flush();
+    assert!(rx.try_recv().is_err());
+    assert_eq!(recv(&rx).1["value"], 0);
+    assert!(flapped.elapsed() >= Duration::from_millis(500));
+
+    assert_eq!(running.stop_and_join().ok().unwrap().into_inner().published, 4);
+}
+
+#[test]
+// Tests that held back readings changed before settling are checked again
+fn flap_damping_changed_before_settling() {
+    use rapt::dedup::FlapDamping;
+
+    let (tx, rx) = mpsc::channel();
+    let publisher = PublisherBuilder::new(MockClient { sender: tx, published: 0 },
+                                          TestInstruments { service: Instrument::default(), requests: Instrument::new(0) })
+        .flap_damping(FlapDamping::new(Duration::from_secs(60), Duration::from_millis(200)))
+        .build();
+    let (handle, requests) = (publisher.handle(), publisher.instruments().requests.clone());
+    // Updates of this handle go unnoticed by the publisher
+    let mut unnoticed = requests.clone();
+    let (idle_tx, _idle_rx) = mpsc::channel();
+    unnoticed.replace_listener(PublisherBuilder::new(MockClient { sender: idle_tx, published: 0 }, counters()).build().handle());
+    let running = publisher.spawn(JsonSerializer);
+    assert_eq!(recv(&rx).0, "service");
+    assert_eq!(recv(&rx).1["value"], 0);
+    requests.update(|v| *v = 1).unwrap();
+    assert_eq!(recv(&rx).1["value"], 1);
+
+    // Held back, then back to the published value by the time it settles
+    requests.update(|v| *v = 0).unwrap();
+    handle.flush();
+    unnoticed.update(|v| *v = 1).unwrap();
+    std::thread::sleep(Duration::from_millis(400));
+    handle.flush();
+    assert!(rx.try_recv().is_err());
+
+    // Held back, then changed to a new value by the time it settles
+    requests.update(|v| *v = 0).unwrap();
+    handle.flush();
+    unnoticed.update(|v| *v = 2).unwrap();
+    assert_eq!(recv(&rx).1["value"], 2);
+
+    assert_eq!(running.stop_and_join().ok().unwrap().into_inner().published, 4);
+}
+
+#[derive(Instruments)]
+struct CounterInstruments<L: Listener> {
+    a: Instrument<u64, L>,
+    b: Instrument<u64, L>,
+    c: Instrument<u64, L>,
+}
+
+fn counters<L: Listener>() -> CounterInstruments<L> {
+    CounterInstruments { a: Instrument::new(0), b: Instrument::new(0), c: Instrument::new(0) }
+}
+
+/// Client that only publishes once let through the gate, announcing it is waiting for it
+struct GatedClient {
+    client: MockClient,
+    waiting: mpsc::Sender<()>,
+    gate: mpsc::Receiver<()>,
+}
+
+impl PublishClient for GatedClient {
+    fn publish(&mut self, topic: String, payload: Vec<u8>, pubopt: PubOpt) -> client::Result<()> {
+        let _ = self.waiting.send(());
+        let _ = self.gate.recv();
+        self.client.publish(topic, payload, pubopt)
+    }
+}
+
+/// Publisher of three counters, blocked publishing the initial reading of the first one
+struct Gated {
+    running: mqtt::RunningPublisher<(), CounterInstruments<mqtt::Handle>, GatedClient>,
+    counters: [Instrument<u64, mqtt::Handle>; 3],
+    /// Dropping it lets every publish through
+    gate: Option<mpsc::Sender<()>>,
+    published: mpsc::Receiver<Message>,
+}
+
+impl Gated {
+    fn run(backpressure: mqtt::Backpressure) -> Self {
+        let (tx, rx) = mpsc::channel();
+        let (waiting_tx, waiting_rx) = mpsc::channel();
+        let (gate_tx, gate_rx) = mpsc::channel();
+        let client = GatedClient { client: MockClient { sender: tx, published: 0 }, waiting: waiting_tx, gate: gate_rx };
+        let publisher = PublisherBuilder::new(client, counters())
+            .backpressure(backpressure)
+            .build();
+        let i = publisher.instruments();
+        let counters = [i.a.clone(), i.b.clone(), i.c.clone()];
+        let running = publisher.spawn(JsonSerializer);
+        waiting_rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        // Initial readings of the other two are queued
+        assert_eq!(running.handle().queue_len(), 2);
+        Gated { running, counters, gate: Some(gate_tx), published: rx }
+    }
+
+    /// Lets every publish through
+    fn open(&mut self) {
+        self.gate = None;
+    }
+
+    /// Waits for queued updates to be published, returns published values and the number of messages
+    fn finish(mut self) -> (Vec<(String, serde_json::Value)>, usize) {
+        self.open();
+        let handle = self.running.handle();
+        handle.flush();
+        let client = self.running.stop_and_join().ok().unwrap().into_inner();
+        let published = self.published.try_iter().map(|(topic, reading, _)| (topic, reading["value"].clone())).collect();
+        (published, client.client.published)
+    }
+}
+
+fn readings(readings: &[(&str, u64)]) -> Vec<(String, serde_json::Value)> {
+    readings.iter().map(|&(topic, value)| (topic.to_string(), serde_json::json!(value))).collect()
+}
+
+#[test]
+// Tests that a full queue merges updates of queued instruments instead of dropping others
+fn backpressure_drop_oldest() {
+    let gated = Gated::run(mqtt::Backpressure::DropOldest(2));
+    let [_, b, c] = &gated.counters;
+    c.update(|v| *v = 1).unwrap();
+    b.update(|v| *v = 1).unwrap();
+    c.update(|v| *v = 2).unwrap();
+    assert_eq!(gated.running.handle().queue_len(), 2);
+    let (published, count) = gated.finish();
+    assert_eq!(published, readings(&[("a", 0), ("b", 1), ("c", 2)]));
+    assert_eq!(count, 3);
+}
+
+#[test]
+// Tests that a full queue drops updates of instruments that aren't queued
+fn backpressure_drop_newest() {
+    let gated = Gated::run(mqtt::Backpressure::DropNewest(2));
+    let [a, b, _] = &gated.counters;
+    a.update(|v| *v = 1).unwrap();
+    b.update(|v| *v = 1).unwrap();
+    assert_eq!(gated.running.handle().queue_len(), 2);
+    let (published, count) = gated.finish();
+    assert_eq!(published, readings(&[("a", 0), ("b", 1), ("c", 0)]));
+    assert_eq!(count, 3);
+}
+
+#[test]
+// Tests that a full queue blocks updates of instruments that aren't queued until there is room,
+// and that a board with more instruments than the capacity can be wired before the publisher runs
+fn backpressure_block() {
+    use std::thread;
+
+    let mut gated = Gated::run(mqtt::Backpressure::Block(1));
+    let [a, b, _] = gated.counters.clone();
+    // Merged with the queued update, doesn't block
+    b.update(|v| *v = 1).unwrap();
+    let (done_tx, done_rx) = mpsc::channel();
+    let blocked = a.clone();
+    let updater = thread::spawn(move || {
+        blocked.update(|v| *v = 1).unwrap();
+        done_tx.send(()).unwrap();
+    });
+    assert!(done_rx.recv_timeout(Duration::from_millis(100)).is_err());
+    gated.open();
+    updater.join().unwrap();
+    let (published, count) = gated.finish();
+    assert_eq!(published, readings(&[("a", 0), ("b", 1), ("c", 0), ("a", 1)]));
+    assert_eq!(count, 4);
+}
+
+#[test]
+// Tests that threads blocked on a full queue don't keep the publisher from reading the value
+fn backpressure_block_concurrent_updates() {
+    use std::thread;
+
+    let (tx, _rx) = mpsc::channel();
+    let publisher = PublisherBuilder::new(MockClient { sender: tx, published: 0 }, counters())
+        .backpressure(mqtt::Backpressure::Block(1))
+        .build();
+    let i = publisher.instruments();
+    let (a, b) = (i.a.clone(), i.b.clone());
+    let running = publisher.spawn(JsonSerializer);
+    let (done_tx, done_rx) = mpsc::channel();
+    let updaters : Vec<_> = (0..2).map(|_| {
+        let (a, b, done) = (a.clone(), b.clone(), done_tx.clone());
+        thread::spawn(move || {
+            for _ in 0..1000 {
+                a.update(|v| *v += 1).unwrap();
+                b.update(|v| *v += 1).unwrap();
+            }
+            done.send(()).unwrap();
+        })
+    }).collect();
+    for _ in 0..2 {
+        done_rx.recv_timeout(Duration::from_secs(10)).expect("updaters are deadlocked");
+    }
+    for updater in updaters {
+        updater.join().unwrap();
+    }
+    running.handle().flush();
+    assert!(running.stop_and_join().is_ok());
+    assert_eq!(*a.read().unwrap(), 2000);
+}
+
+#[test]
+#[should_panic(expected = "backpressure capacity must be positive")]
+// Tests that bounded queues can't have zero capacity
+fn backpressure_zero_capacity() {
+    let (tx, _rx) = mpsc::channel();
+    let _ = PublisherBuilder::new(MockClient { sender: tx, published: 0 }, counters())
+        .backpressure(mqtt::Backpressure::Block(0));
+}
+
+#[test]
+// Tests that shutting down before the publisher runs waits for it to start
+fn shutdown_before_run() {
+    use std::thread;
+
+    let (tx, _rx) = mpsc::channel();
+    let mut publisher = PublisherBuilder::new(MockClient { sender: tx, published: 0 }, counters()).build();
+    let handle = publisher.handle();
+    // The request stays queued, so the publisher shuts down as soon as it runs
+    assert!(!handle.shutdown_timeout(Duration::from_millis(50)));
+    let shutdown = thread::spawn(move || handle.shutdown());
+    publisher.run(JsonSerializer);
+    shutdown.join().unwrap();
+    assert_eq!(publisher.into_inner().published, 0);
+}
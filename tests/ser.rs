@@ -0,0 +1,429 @@
+// Copyright 2017 All Contributors (see CONTRIBUTORS file)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+//
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+include!("includes/common.rs");
+
+use rapt::*;
+use rapt::ser::FieldFilter;
+
+#[derive(Serialize, Default)]
+struct Address {
+    city: &'static str,
+    zip: &'static str,
+}
+
+#[derive(Serialize, Default)]
+struct Service {
+    name: &'static str,
+    status: u8,
+    addresses: Vec<Address>,
+}
+
+#[derive(Instruments)]
+struct TestInstruments<L: Listener> {
+    service: Instrument<Service, L>,
+}
+
+#[test]
+// Tests that only included fields are serialized
+fn field_filter() {
+    let i = TestInstruments::<()> {
+        service: Instrument::new(Service {
+            name: "http",
+            status: 1,
+            addresses: vec![Address { city: "Vancouver", zip: "V6B" }],
+        }),
+    };
+
+    let filter = FieldFilter::new(vec!["value.status", "value.addresses.city"]);
+    let mut ser = serde_json::Serializer::new(Vec::with_capacity(128));
+    assert!(i.serialize_reading("service", filter.serializer(&mut ser)).is_ok());
+    let v: serde_json::Value = serde_json::from_slice(&ser.into_inner()).unwrap();
+
+    assert_eq!(v, serde_json::json!({
+        "value": { "status": 1, "addresses": [{ "city": "Vancouver" }] },
+    }));
+}
+
+#[test]
+// Tests that context is merged into instrument's fields without overriding them
+fn reading_with_context() {
+    use std::collections::HashMap;
+
+    let i = TestInstruments::<()> {
+        service: Instrument::new(Service { name: "http", status: 1, addresses: vec![] }),
+    };
+
+    let mut context = HashMap::new();
+    context.insert("tenant", "acme".to_string());
+    context.insert("value", "overridden".to_string());
+    let mut ser = serde_json::Serializer::new(Vec::with_capacity(128));
+    assert!(i.serialize_reading_with_context("service", &context, &mut ser).is_ok());
+    let v: serde_json::Value = serde_json::from_slice(&ser.into_inner()).unwrap();
+
+    assert_eq!(v["tenant"], "acme");
+    assert_eq!(v["value"]["name"], "http");
+    assert_eq!(v.as_object().unwrap().len(), if cfg!(feature = "timestamp_instruments") { 3 } else { 2 });
+}
+
+#[derive(Instruments)]
+struct NumericInstruments<L: Listener> {
+    requests: Instrument<u64, L>,
+    load: Instrument<f32, L>,
+    up: Instrument<bool, L>,
+    service: Instrument<Service, L>,
+}
+
+#[test]
+// Tests that flat readings only contain numeric instruments
+fn flat_readings() {
+    use rapt::flat::FlatReadings;
+
+    let i = NumericInstruments::<()> {
+        requests: Instrument::new(42),
+        load: Instrument::new(0.5),
+        up: Instrument::new(true),
+        service: Instrument::default(),
+    };
+
+    let v = serde_json::to_value(FlatReadings::new(&i)).unwrap();
+    assert_eq!(v, serde_json::json!({ "requests": 42, "load": 0.5, "up": 1 }));
+}
+
+#[test]
+#[cfg(feature = "serde_json")]
+// Tests that the board is serialized into a JSON object keyed by instrument names
+fn to_json_value() {
+    let i = NumericInstruments::<()> {
+        requests: Instrument::new(42),
+        load: Instrument::new(0.5),
+        up: Instrument::new(true),
+        service: Instrument::default(),
+    };
+
+    let v = i.to_json_value().unwrap();
+    assert_eq!(v["requests"]["value"], 42);
+    assert_eq!(v["up"]["value"], true);
+    assert_eq!(v["service"]["value"]["status"], 0);
+    assert_eq!(v.as_object().unwrap().len(), 4);
+}
+
+#[test]
+#[cfg(feature = "serde_json")]
+// Tests that readings are collected into a map of parsed values
+fn collect_readings() {
+    use std::collections::HashMap;
+
+    let i = NumericInstruments::<()> {
+        requests: Instrument::new(42),
+        load: Instrument::new(0.5),
+        up: Instrument::new(true),
+        service: Instrument::default(),
+    };
+
+    let readings: HashMap<&'static str, serde_json::Value> = i.collect_readings().unwrap();
+    assert_eq!(readings.len(), 4);
+    assert_eq!(readings["requests"]["value"], 42);
+    assert_eq!(readings["service"]["value"]["status"], 0);
+
+    #[derive(Deserialize)]
+    struct Reading {
+        value: serde_json::Value,
+    }
+    let readings: HashMap<&'static str, Reading> = i.collect_readings().unwrap();
+    assert_eq!(readings["up"].value, true);
+}
+
+#[derive(Instruments)]
+struct SecretInstruments<L: Listener> {
+    requests: Instrument<u64, L>,
+    #[rapt(no_bulk)]
+    secret: Instrument<u64, L>,
+}
+
+#[test]
+#[cfg(feature = "serde_json")]
+// Tests that instruments excluded from bulk reads can still be read by name
+fn no_bulk() {
+    use rapt::flat::FlatReadings;
+
+    let i = SecretInstruments::<()> { requests: Instrument::new(1), secret: Instrument::new(2) };
+
+    assert_eq!(i.instrument_names(), vec!["requests", "secret"]);
+    assert_eq!(i.bulk_instrument_names(), vec!["requests"]);
+    assert_eq!(i.to_json_value().unwrap().as_object().unwrap().len(), 1);
+    assert_eq!(serde_json::to_value(FlatReadings::new(&i)).unwrap(), serde_json::json!({ "requests": 1 }));
+
+    let mut ser = serde_json::Serializer::new(Vec::with_capacity(128));
+    assert!(i.serialize_reading("secret", &mut ser).is_ok());
+}
+
+#[test]
+// Tests that readings can be keyed by mapped names, while internal names are kept
+fn renamed_readings() {
+    use rapt::ser::Readings;
+
+    let i = SecretInstruments::<()> { requests: Instrument::new(1), secret: Instrument::new(2) };
+
+    let v = serde_json::to_value(Readings::new(&i).with_key_mapping(|name| name.to_uppercase())).unwrap();
+    assert_eq!(v.as_object().unwrap().keys().collect::<Vec<_>>(), vec!["REQUESTS"]);
+    assert_eq!(v["REQUESTS"]["value"], 1);
+    assert_eq!(serde_json::to_value(Readings::new(&i)).unwrap()["requests"]["value"], 1);
+
+    let mut ser = serde_json::Serializer::new(Vec::with_capacity(128));
+    assert!(i.serialize_reading("requests", &mut ser).is_ok());
+}
+
+#[test]
+// Tests that readings are emitted in declaration order unless sorting is requested
+fn sorted_readings() {
+    use rapt::ser::Readings;
+
+    let i = NumericInstruments::<()> {
+        requests: Instrument::new(42),
+        load: Instrument::new(0.5),
+        up: Instrument::new(true),
+        service: Instrument::default(),
+    };
+
+    let keys = |bytes: Vec<u8>| {
+        let s = String::from_utf8(bytes).unwrap();
+        let mut keys = vec!["requests", "load", "up", "service"];
+        keys.sort_by_key(|key| s.find(&format!("\"{}\":{{", key)).unwrap());
+        keys
+    };
+    assert_eq!(keys(serde_json::to_vec(&Readings::new(&i)).unwrap()), vec!["requests", "load", "up", "service"]);
+    assert_eq!(keys(serde_json::to_vec(&Readings::new(&i).sorted()).unwrap()), vec!["load", "requests", "service", "up"]);
+    let mapped = Readings::new(&i).sorted().with_key_mapping(|name| if name == "up" { "a_up".into() } else { name.into() });
+    assert!(serde_json::to_string(&mapped).unwrap().starts_with("{\"a_up\":"));
+}
+
+#[derive(Instruments)]
+struct VersionedInstruments<L: Listener> {
+    #[rapt(version = 2)]
+    service: Instrument<Service, L>,
+    requests: Instrument<u64, L>,
+}
+
+#[test]
+#[cfg(feature = "serde_json")]
+// Tests that schema version is only serialized if specified
+fn schema_version() {
+    let mut i = VersionedInstruments::<()> { service: Instrument::default(), requests: Instrument::new(1) };
+    i.wire_listener(());
+
+    let v = i.to_json_value().unwrap();
+    assert_eq!(v["service"]["schema_version"], 2);
+    assert!(v["requests"].get("schema_version").is_none());
+    assert_eq!(i.service.schema_version(), Some(2));
+}
+
+#[cfg(feature = "serde_json")]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Sample {
+    name: String,
+    status: u8,
+    load: f64,
+    tags: Vec<String>,
+    parent: Option<Box<Sample>>,
+}
+
+/// Instrument reading, as deserialized back
+#[cfg(feature = "serde_json")]
+#[derive(Deserialize, Debug)]
+struct Reading<T> {
+    value: Option<T>,
+}
+
+/// Serializes an instrument with a given serializer and deserializes it back with `parse`
+#[cfg(feature = "serde_json")]
+fn assert_round_trip<IS, S, P>(is: IS, parse: P)
+    where for<'a> IS: rapt::ser::InstantiateSerializer<'a, Vec<u8>, Target=S>,
+          S: rapt::ser::IntoWriter<Vec<u8>>, for<'a> &'a mut S: serde::Serializer,
+          P: Fn(&[u8]) -> Reading<Sample> {
+    let sample = Sample {
+        name: "worker \"1\"".into(),
+        status: 255,
+        load: 0.25,
+        tags: vec!["a".into(), "ü".into()],
+        parent: Some(Box::new(Sample { name: "main".into(), status: 0, load: -1.5, tags: vec![], parent: None })),
+    };
+    let i = Instrument::<Sample, ()>::new(sample.clone());
+
+    let mut ser = is.instantiate_serializer(Vec::new());
+    assert!(serde::Serialize::serialize(&i, &mut ser).is_ok());
+    let reading = parse(&ser.into_writer());
+    assert_eq!(reading.value, Some(sample));
+}
+
+#[test]
+#[cfg(feature = "serde_json")]
+// Tests that instruments serialized by every serializer can be read back
+fn round_trip() {
+    use rapt::ser::JsonSerializer;
+
+    assert_round_trip(JsonSerializer, |bytes| serde_json::from_slice(bytes).unwrap());
+}
+
+#[test]
+#[cfg(feature = "serde_json")]
+// Tests that patches turn one reading into another
+fn json_patch() {
+    use rapt::patch::diff;
+    use serde_json::json;
+
+    let from = json!({"value": {"status": 0, "tags": ["a"], "a/b": 1, "gone": true}, "last_update_at": "1"});
+    let to = json!({"value": {"status": 0, "tags": ["a", "b"], "a/b": 2, "new": null}, "last_update_at": "2"});
+    assert_eq!(diff(&from, &to), json!([
+        {"op": "replace", "path": "/last_update_at", "value": "2"},
+        {"op": "replace", "path": "/value/a~1b", "value": 2},
+        {"op": "remove", "path": "/value/gone"},
+        {"op": "replace", "path": "/value/tags", "value": ["a", "b"]},
+        {"op": "add", "path": "/value/new", "value": null},
+    ]));
+    assert_eq!(diff(&to, &to), json!([]));
+    assert_eq!(diff(&json!(1), &json!(2)), json!([{"op": "replace", "path": "", "value": 2}]));
+}
+
+#[test]
+#[cfg(feature = "serde_json")]
+// Tests that a board is restored from a snapshot of its readings without notifying the listener
+fn restore_from() {
+    use std::collections::HashMap;
+    use std::sync::mpsc;
+
+    let i = NumericInstruments::<()> {
+        requests: Instrument::new(42),
+        load: Instrument::new(0.5),
+        up: Instrument::new(true),
+        service: Instrument::new(Service { name: "http", status: 1, addresses: vec![] }),
+    };
+    let mut snapshot: HashMap<String, serde_json::Value> = serde_json::from_value(i.to_json_value().unwrap()).unwrap();
+    snapshot.remove("load");
+    snapshot.insert("unknown".into(), serde_json::json!({"value": 1}));
+
+    #[derive(Instruments)]
+    struct Restored<L: Listener> {
+        requests: Instrument<u64, L>,
+        load: Instrument<f32, L>,
+        up: Instrument<Option<bool>, L>,
+    }
+    let (tx, rx) = mpsc::channel();
+    let mut restored = Restored { requests: Instrument::new(0), load: Instrument::new(1.0), up: Instrument::new(None) };
+    restored.wire_listener(tx);
+    assert_eq!(rx.try_iter().count(), 3);
+
+    assert!(restored.restore_from(&snapshot).is_ok());
+    assert_eq!(*restored.requests.read().unwrap(), 42);
+    assert_eq!(*restored.load.read().unwrap(), 1.0);
+    assert_eq!(*restored.up.read().unwrap(), Some(true));
+    assert!(rx.try_recv().is_err());
+    assert!(restored.updated_instrument_names().is_empty());
+
+    snapshot.insert("requests".into(), serde_json::json!({"value": "many"}));
+    assert!(restored.restore_from(&snapshot).is_err());
+}
+
+#[test]
+#[cfg(feature = "serde_json")]
+// Tests that serialized sizes match the length of serialized readings
+fn serialized_sizes() {
+    use rapt::ser::JsonSerializer;
+
+    let i = SecretInstruments::<()> { requests: Instrument::new(1), secret: Instrument::new(2) };
+    let mut ser = serde_json::Serializer::new(Vec::new());
+    assert!(i.serialize_reading("secret", &mut ser).is_ok());
+    assert_eq!(i.serialized_size("secret", &JsonSerializer).unwrap(), ser.into_inner().len());
+    assert!(matches!(i.serialized_size("missing", &JsonSerializer), Err(ReadError::NotFound)));
+
+    let sizes = i.serialized_sizes(&JsonSerializer).unwrap();
+    assert_eq!(sizes.keys().collect::<Vec<_>>(), vec![&"requests"]);
+    assert_eq!(sizes["requests"], serde_json::to_vec(&i.requests).unwrap().len());
+}
+
+#[test]
+#[cfg(feature = "json_schema")]
+// Tests that the schema of a board describes readings of its bulk instruments
+fn json_schema() {
+    use rapt::schema::{board_schema, reading_schema};
+
+    let schema = serde_json::to_value(board_schema(SecretInstruments::<()>::describe_readings)).unwrap();
+    let properties = schema["properties"].as_object().unwrap();
+    assert_eq!(properties.keys().collect::<Vec<_>>(), vec!["requests"]);
+    let requests = &properties["requests"];
+    assert_eq!(requests["type"], "object");
+    assert_eq!(requests["properties"]["value"]["type"], "integer");
+    assert_eq!(requests["properties"]["error"]["const"], "poisoned");
+    #[cfg(feature = "timestamp_instruments")]
+    assert_eq!(requests["properties"]["last_update_at"]["format"], "date-time");
+
+    let schema = serde_json::to_value(reading_schema::<Instrument<String, ()>>()).unwrap();
+    assert_eq!(schema["properties"]["value"]["type"], "string");
+    assert!(schema["required"].as_array().unwrap().contains(&serde_json::json!("value")));
+}
+
+#[test]
+#[cfg(feature = "serde_json")]
+// Tests that the full report lists serialized bulk readings with timestamps in declaration order
+fn full_report() {
+    use rapt::ser::JsonSerializer;
+    use std::collections::HashMap;
+
+    #[derive(Instruments)]
+    struct ReportInstruments<L: Listener> {
+        requests: Instrument<u64, L>,
+        #[rapt(no_bulk)]
+        secret: Instrument<u64, L>,
+        // JSON object keys must be strings
+        broken: Instrument<HashMap<(u8, u8), u8>, L>,
+        load: Instrument<f64, L>,
+    }
+
+    let i = ReportInstruments::<()> {
+        requests: Instrument::new(1),
+        secret: Instrument::new(2),
+        broken: Instrument::new(vec![((0, 0), 0)].into_iter().collect()),
+        load: Instrument::new(0.5),
+    };
+    let report = i.full_report(&JsonSerializer);
+    assert_eq!(report.iter().map(|r| r.0).collect::<Vec<_>>(), vec!["requests", "load"]);
+    let reading: serde_json::Value = serde_json::from_slice(&report[1].1).unwrap();
+    assert_eq!(reading["value"], 0.5);
+    assert_eq!(report.iter().all(|r| r.2.is_some()), cfg!(feature = "timestamp_instruments"));
+}
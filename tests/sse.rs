@@ -0,0 +1,120 @@
+// Copyright 2017 All Contributors (see CONTRIBUTORS file)
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+//
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+
+
+#![cfg(all(feature = "sse_publisher", feature = "serde_json"))]
+
+include!("includes/common.rs");
+
+use rapt::*;
+use rapt::sse::Publisher;
+use rapt::ser::JsonSerializer;
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use std::time::Duration;
+
+#[derive(Serialize, Default, Clone)]
+struct Service {
+    status: u8,
+}
+
+#[derive(Instruments)]
+struct TestInstruments<L: Listener> {
+    service: Instrument<Service, L>,
+}
+
+fn read_event<R: BufRead>(reader: &mut R) -> Vec<String> {
+    let mut lines = vec![];
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        let line = line.trim_end().to_string();
+        if line.is_empty() {
+            return lines;
+        }
+        lines.push(line);
+    }
+}
+
+fn read_update<R: BufRead>(reader: &mut R) -> Vec<String> {
+    loop {
+        let event = read_event(reader);
+        if event != vec![": heartbeat"] {
+            return event;
+        }
+    }
+}
+
+#[test]
+// Tests that clients receive current readings, updates and heartbeats
+fn event_stream() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let mut publisher = Publisher::new(listener, TestInstruments { service: Instrument::default() }).unwrap();
+    publisher.set_heartbeat(Duration::from_millis(300));
+    let service = publisher.instruments().service.clone();
+    let handle = publisher.handle();
+    let t = thread::spawn(move || publisher.run(JsonSerializer));
+
+    let mut stream = TcpStream::connect(address).unwrap();
+    stream.write_all(b"GET /events HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+    let mut reader = BufReader::new(stream);
+
+    let headers = read_event(&mut reader);
+    assert_eq!(headers[0], "HTTP/1.1 200 OK");
+    assert!(headers.contains(&"Content-Type: text/event-stream".to_string()));
+
+    let event = read_update(&mut reader);
+    assert_eq!(event[0], "event: update");
+    assert!(event[1].starts_with(r#"data: {"name":"service","reading":{"value":{"status":0}"#));
+
+    service.update(|v| v.status = 1).unwrap();
+    let event = read_update(&mut reader);
+    assert!(event[1].starts_with(r#"data: {"name":"service","reading":{"value":{"status":1}"#));
+
+    // Repeated readings are not sent
+    service.update(|v| v.status = 1).unwrap();
+    assert_eq!(read_event(&mut reader), vec![": heartbeat"]);
+
+    handle.shutdown();
+    t.join().unwrap();
+}